@@ -0,0 +1,13 @@
+//! Records the version of rustc echelon0 is built with, plugin libraries built by another
+//! compiler are rejected (see `plugin::library`).
+
+use std::env;
+use std::process::Command;
+
+fn main() {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let output = Command::new(rustc).arg("--version").output().expect("cannot run rustc");
+    let version = String::from_utf8(output.stdout).expect("rustc version is not UTF-8");
+    println!("cargo:rustc-env=ECHELON0_RUSTC_VERSION={}", version.trim());
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -1,82 +1,219 @@
 extern crate env_logger;
-// extern crate getopts;
+extern crate getopts;
+extern crate log;
 
 extern crate echelon0;
 
-// use std::env;
-// use std::io;
-// use std::io::BufRead;
-// use std::process;
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::process;
+use std::time::Duration;
 
-// use getopts::Options;
-use echelon0::Runner;
+use getopts::{Matches, Options};
+use log::LogLevelFilter;
+use echelon0::{Options as RunnerOptions, Runner};
+use echelon0::config::graph::graph;
+use echelon0::config::load::load;
+use echelon0::config::parse::parse;
+use echelon0::config::print::print;
 
-// fn print_usage(opts: &Options, program: &String) {
-//     let brief = format!("Usage: {} parse_rule [glob ...] [-i include_pattern | -e \
-//                          exclude_pattern]",
-//                         program);
-//     println!("{}", opts.usage(&brief));
-// }
+fn print_usage(opts: &Options, program: &str) {
+    let brief = format!("Usage: {} [-f CONFIG_PATH | -e CONFIG_STRING] [options]\n       \
+                         {} fmt [-w] [CONFIG_PATH]\n       \
+                         {} graph [-F FORMAT] CONFIG_PATH",
+                        program, program, program);
+    println!("{}", opts.usage(&brief));
+}
+
+fn handle_bad_opts(err: &str, program: &str) -> ! {
+    eprintln!("{} Try \"{} -h\" for help.", err, program);
+    process::exit(1);
+}
+
+fn parse_number<T: std::str::FromStr>(args: &Matches, name: &str) -> Result<Option<T>, String> {
+    match args.opt_str(name) {
+        Some(v) => {
+            v.parse()
+                .map(Some)
+                .map_err(|_| format!("Invalid value `{}` of --{}.", v, name))
+        }
+        None => Ok(None),
+    }
+}
+
+fn parse_log_level(level: &str) -> Result<LogLevelFilter, String> {
+    match level {
+        "fatal" | "error" => Ok(LogLevelFilter::Error),
+        "warn" => Ok(LogLevelFilter::Warn),
+        "info" => Ok(LogLevelFilter::Info),
+        "debug" => Ok(LogLevelFilter::Debug),
+        "trace" => Ok(LogLevelFilter::Trace),
+        _ => Err(format!("Invalid value `{}` of --log.level.", level)),
+    }
+}
+
+fn runner_options(args: &Matches) -> Result<RunnerOptions, String> {
+    let mut options = RunnerOptions {
+        config_path: args.opt_str("path.config"),
+        config_string: args.opt_str("config.string"),
+        test_and_exit: args.opt_present("config.test_and_exit"),
+        keystore_path: args.opt_str("path.keystore"),
+        plugins_path: args.opt_str("path.plugins"),
+        ..Default::default()
+    };
+
+    if let Some(workers) = parse_number(args, "pipeline.workers")? {
+        options.pipeline.workers = workers;
+    }
+    if let Some(size) = parse_number(args, "pipeline.batch.size")? {
+        options.pipeline.batch_size = size;
+    }
+    if let Some(delay) = parse_number(args, "pipeline.batch.delay")? {
+        options.pipeline.batch_delay = Duration::from_millis(delay);
+    }
+    if options.pipeline.workers == 0 || options.pipeline.batch_size == 0 {
+        return Err("Pipeline workers and batch size must be positive.".to_string());
+    }
+    Ok(options)
+}
+
+/// Reformats a config file (or stdin) and prints it or writes it back.
+fn format_config(args: &Matches) -> Result<(), String> {
+    let path = args.free.first();
+    let mut conf = Vec::new();
+    match path {
+        Some(path) => fs::File::open(path).and_then(|mut f| f.read_to_end(&mut conf)),
+        None => io::stdin().read_to_end(&mut conf),
+    }.map_err(|e| format!("Cannot read `{}`: {}", path.map_or("stdin", |p| p.as_str()), e))?;
+
+    let config = parse(&conf).map_err(|e| match path {
+        Some(path) => e.in_file(path).to_string(),
+        None => e.to_string(),
+    })?;
+    let formatted = print(&config);
+    match path {
+        Some(path) if args.opt_present("write") => {
+            fs::write(path, formatted).map_err(|e| format!("Cannot write `{}`: {}", path, e))
+        }
+        _ => {
+            print!("{}", formatted);
+            Ok(())
+        }
+    }
+}
 
-// fn handle_bad_opts(err: &String, program: &String) {
-//     println!("{} Try \"{} -h\" for help.", err, program);
-//     process::exit(1);
-// }
+fn fmt_main(program: &str, args: &[String]) {
+    let mut opts = Options::new();
+    opts.optflag("w", "write", "write the result to the file instead of stdout");
+    opts.optflag("h", "help", "show this message");
+    let args = match opts.parse(args) {
+        Ok(m) => m,
+        Err(f) => handle_bad_opts(&f.to_string(), program),
+    };
+
+    if args.opt_present("h") {
+        let brief = format!("Usage: {} fmt [-w] [CONFIG_PATH]\n\n\
+                             Prints the config in the canonical form. Reads stdin if no path \
+                             is given.",
+                            program);
+        println!("{}", opts.usage(&brief));
+        return;
+    }
+    if args.free.len() > 1 {
+        handle_bad_opts("Only one config can be formatted at a time.", program);
+    }
+    if args.opt_present("write") && args.free.is_empty() {
+        handle_bad_opts("--write requires a config path.", program);
+    }
+
+    if let Err(e) = format_config(&args) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn graph_main(program: &str, args: &[String]) {
+    let mut opts = Options::new();
+    opts.optopt("F", "format", "json (default) or dot", "FORMAT");
+    opts.optflag("h", "help", "show this message");
+    let args = match opts.parse(args) {
+        Ok(m) => m,
+        Err(f) => handle_bad_opts(&f.to_string(), program),
+    };
+
+    if args.opt_present("h") {
+        let brief = format!("Usage: {} graph [-F FORMAT] CONFIG_PATH\n\n\
+                             Prints the pipeline graph of the config.",
+                            program);
+        println!("{}", opts.usage(&brief));
+        return;
+    }
+    let path = match args.free.as_slice() {
+        [path] => path,
+        _ => handle_bad_opts("Exactly one config path is expected.", program),
+    };
+    let format = args.opt_str("format").unwrap_or_else(|| "json".to_string());
+    if format != "json" && format != "dot" {
+        handle_bad_opts(&format!("Invalid value `{}` of --format.", format), program);
+    }
+
+    match load(path) {
+        Ok(config) if format == "dot" => print!("{}", graph(&config).to_dot()),
+        Ok(config) => println!("{}", graph(&config).to_json()),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
 
 fn main() {
-    env_logger::init().expect("Cannot initialize logger");
-
-    // let args: Vec<_> = env::args().collect();
-    // let program = args[0].clone();
-
-    // let mut opts = Options::new();
-    // opts.optopt("i",
-    //             "include",
-    //             "if presents only entries matched to this pattern will be parsed",
-    //             "INCLUDE");
-    // opts.optopt("e",
-    //             "exclude",
-    //             "if presents only entries not matched to this pattern will be processed",
-    //             "EXCLUDE");
-    // opts.optflag("h", "help", "show this message");
-    // let args = match opts.parse(&args[1..]) {
-    //     Ok(m) => m,
-    //     Err(f) => return handle_bad_opts(&f.to_string(), &program),
-    // };
-
-    // if args.opt_present("h") {
-    //     print_usage(&opts, &program);
-    //     return;
-    // }
-
-    // if args.free.len() == 0 {
-    //     print_usage(&opts, &program);
-    //     return;
-    // }
-
-    // let stdin;
-    // let mut stdin_lock;
-    // let mut glob_in;
-    // let input: &mut BufRead = if args.free.len() > 1 {
-    //     glob_in = monstrio::Input::glob_with_capacity(4 * 4096, args.free[1..].into_iter());
-    //     glob_in.as_mut()
-    // } else {
-    //     stdin = io::stdin();
-    //     stdin_lock = stdin.lock();
-    //     &mut stdin_lock
-    // };
-    // let stdout = io::stdout();
-    // let output = &mut stdout.lock();
-
-    // let parser = Parser::new(&args.free[0]).unwrap();
-    // let mut echelon0 = Echelon0::new(input, output, &parser);
-    // if let Some(ref include) = args.opt_str("i") {
-    //     echelon0.set_include_filter(include).expect("Cannot set include filter");
-    // }
-    // if let Some(ref exclude) = args.opt_str("e") {
-    //     echelon0.set_exclude_filter(exclude).expect("Cannot set exclude filter");
-    // }
-
-    let mut runner = Runner::new();
-    runner.run();
+    let args: Vec<_> = env::args().collect();
+    let program = args[0].clone();
+
+    match args.get(1).map(|a| a.as_str()) {
+        Some("fmt") => return fmt_main(&program, &args[2..]),
+        Some("graph") => return graph_main(&program, &args[2..]),
+        _ => {}
+    }
+
+    let mut opts = Options::new();
+    opts.optopt("f", "path.config", "load the config from a file", "CONFIG_PATH");
+    opts.optopt("e", "config.string", "use the given string as the config", "CONFIG_STRING");
+    opts.optflag("t", "config.test_and_exit", "check the config and exit");
+    opts.optopt("", "path.keystore", "read `${VAR}` values from a keystore", "KEYSTORE_PATH");
+    opts.optopt("", "path.plugins", "load plugin libraries from a directory", "PLUGINS_PATH");
+    opts.optopt("w", "pipeline.workers", "number of pipeline workers", "COUNT");
+    opts.optopt("b", "pipeline.batch.size", "max number of events per batch", "SIZE");
+    opts.optopt("u", "pipeline.batch.delay", "max time to wait for a batch", "DELAY_IN_MS");
+    opts.optopt("", "log.level", "fatal, error, warn, info, debug or trace", "LEVEL");
+    opts.optflag("h", "help", "show this message");
+    let args = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => handle_bad_opts(&f.to_string(), &program),
+    };
+
+    if args.opt_present("h") {
+        print_usage(&opts, &program);
+        return;
+    }
+
+    let mut logger = env_logger::LogBuilder::new();
+    logger.filter(None, LogLevelFilter::Warn);
+    if let Ok(filters) = env::var("RUST_LOG") {
+        logger.parse(&filters);
+    }
+    if let Some(level) = args.opt_str("log.level") {
+        let level = parse_log_level(&level).unwrap_or_else(|e| handle_bad_opts(&e, &program));
+        logger.filter(None, level);
+    }
+    logger.init().expect("Cannot initialize logger");
+
+    let options = runner_options(&args).unwrap_or_else(|e| handle_bad_opts(&e, &program));
+    let mut runner = Runner::new(options);
+    if let Err(e) = runner.run() {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
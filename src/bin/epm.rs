@@ -1,3 +1,149 @@
+extern crate getopts;
+
+extern crate echelon0;
+
+use std::env;
+use std::error::Error;
+use std::path::Path;
+use std::process;
+
+use getopts::{Matches, Options};
+use echelon0::plugin::library::CORE_VERSION;
+use echelon0::plugin::package::{Package, PackageError, PluginDir, PluginKind};
+use echelon0::plugin::scaffold::{ScaffoldError, Skeleton};
+
+const DEFAULT_PLUGINS_PATH: &str = "plugins";
+
+fn print_usage(opts: &Options, program: &str) {
+    let brief = format!("Usage: {0} [options] list\n       \
+                         {0} [options] install PACKAGE_PATH\n       \
+                         {0} [options] remove PLUGIN\n       \
+                         {0} [options] info PLUGIN\n       \
+                         {0} [options] generate --type TYPE --name NAME [DIR]\n\n\
+                         Manages plugins of echelon0 {1}. Packages are directories or .tar(.gz) \
+                         archives with a plugin.toml manifest and the plugin library. Crates \
+                         of new plugins are generated in DIR, ./NAME by default.",
+                        program,
+                        CORE_VERSION);
+    println!("{}", opts.usage(&brief));
+}
+
+fn handle_bad_opts(err: &str, program: &str) -> ! {
+    eprintln!("{} Try \"{} -h\" for help.", err, program);
+    process::exit(1);
+}
+
+fn list(plugins: &PluginDir) -> Result<(), PackageError> {
+    let manifests = plugins.list()?;
+    let width = manifests.iter().map(|m| m.name.len()).max().unwrap_or(0);
+    for m in manifests {
+        let note = if m.is_compatible() { "" } else { " (incompatible)" };
+        println!("{:width$}  {:8}  {}{}", m.name, m.version, m.kind, note, width = width);
+    }
+    Ok(())
+}
+
+fn install(plugins: &PluginDir, path: &str, force: bool) -> Result<(), PackageError> {
+    let package = Package::open(Path::new(path))?;
+    package.verify()?;
+    plugins.install(&package, force)?;
+    println!("Installed {} {}", package.manifest.name, package.manifest.version);
+    Ok(())
+}
+
+fn remove(plugins: &PluginDir, name: &str) -> Result<(), PackageError> {
+    let manifest = plugins.remove(name)?;
+    println!("Removed {} {}", manifest.name, manifest.version);
+    Ok(())
+}
+
+fn info(plugins: &PluginDir, name: &str) -> Result<(), PackageError> {
+    let m = plugins.get(name)?;
+    println!("Name:        {}", m.name);
+    println!("Version:     {}", m.version);
+    println!("Kind:        {}", m.kind);
+    println!("Echelon0:    {}{}",
+             m.echelon0,
+             if m.is_compatible() { "" } else { " (incompatible)" });
+    println!("Library:     {}", plugins.library_path(name).display());
+    if let Some(ref description) = m.description {
+        println!("Description: {}", description);
+    }
+    Ok(())
+}
+
+fn generate(args: &Matches, dir: Option<&str>, program: &str) -> Result<(), ScaffoldError> {
+    let (kind, name) = match (args.opt_str("type"), args.opt_str("name")) {
+        (Some(kind), Some(name)) => (kind, name),
+        _ => handle_bad_opts("Both --type and --name are required.", program),
+    };
+    let kind: PluginKind = match kind.parse() {
+        Ok(kind) => kind,
+        Err(e) => handle_bad_opts(&format!("Invalid --type: {}.", e), program),
+    };
+    let mut skeleton = Skeleton::new(&name, kind)?;
+    if let Some(path) = args.opt_str("path.echelon0") {
+        // Relative paths would be taken relative to the generated crate.
+        let path = Path::new(&path);
+        skeleton = skeleton.core_path(path.canonicalize()
+            .map_err(|e| ScaffoldError::Io(path.to_path_buf(), e))?);
+    }
+
+    let dir = Path::new(dir.unwrap_or(&name));
+    skeleton.write(dir)?;
+    println!("Generated {} plugin `{}` in {}", kind, name, dir.display());
+    Ok(())
+}
+
+fn run(args: &Matches, program: &str) -> Result<(), Box<dyn Error>> {
+    let path = args.opt_str("path.plugins").unwrap_or_else(|| DEFAULT_PLUGINS_PATH.to_string());
+    let plugins = PluginDir::new(path);
+
+    let free: Vec<_> = args.free.iter().map(|a| a.as_str()).collect();
+    match free.as_slice() {
+        ["list"] => list(&plugins)?,
+        ["install", path] => install(&plugins, path, args.opt_present("force"))?,
+        ["remove", name] => remove(&plugins, name)?,
+        ["info", name] => info(&plugins, name)?,
+        ["generate"] => generate(args, None, program)?,
+        ["generate", dir] => generate(args, Some(dir), program)?,
+        [] => handle_bad_opts("No command given.", program),
+        [command, ..] => {
+            handle_bad_opts(&format!("Invalid arguments of `{}` command.", command), program)
+        }
+    }
+    Ok(())
+}
+
 fn main() {
-    println!("epm goes here...");
+    let args: Vec<_> = env::args().collect();
+    let program = args[0].clone();
+
+    let mut opts = Options::new();
+    opts.optopt("p",
+                "path.plugins",
+                &format!("plugins directory (`{}` by default)", DEFAULT_PLUGINS_PATH),
+                "PLUGINS_PATH");
+    opts.optflag("f", "force", "replace an installed plugin of the same name");
+    opts.optopt("t", "type", "kind of the plugin to generate", "input|filter|output|codec");
+    opts.optopt("n", "name", "name of the plugin to generate", "NAME");
+    opts.optopt("",
+                "path.echelon0",
+                "make the generated crate depend on a local echelon0 checkout",
+                "ECHELON0_PATH");
+    opts.optflag("h", "help", "show this message");
+    let args = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => handle_bad_opts(&f.to_string(), &program),
+    };
+
+    if args.opt_present("h") {
+        print_usage(&opts, &program);
+        return;
+    }
+
+    if let Err(e) = run(&args, &program) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
@@ -1,21 +1,60 @@
+use std::fmt;
+use std::ops::Not;
+
 #[derive(Debug, PartialEq)]
 pub struct Config {
     pub sections: Vec<PluginSection>,
+    /// Comments in order of their appearance. Locations tell which nodes they belong to.
+    pub comments: Vec<Comment>,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct PluginSection {
     pub plugin_type: PluginType,
     pub block: Block,
+    /// File the section comes from (if the config was loaded from files).
+    pub file: Option<String>,
+    pub location: Location,
 }
 
-#[derive(Debug, PartialEq)]
+impl PluginSection {
+    pub fn new(plugin_type: PluginType, block: Block) -> PluginSection {
+        PluginSection {
+            plugin_type,
+            block,
+            file: None,
+            location: Location::default(),
+        }
+    }
+}
+
+/// Comment, i.e. `# text` till the end of the line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comment {
+    /// Text after `#` without trailing whitespace.
+    pub text: String,
+    /// Number of blocks, hashes and arrays the comment is inside of.
+    pub depth: usize,
+    pub location: Location,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PluginType {
     Input,
     Filter,
     Output,
 }
 
+impl fmt::Display for PluginType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PluginType::Input => write!(f, "input"),
+            PluginType::Filter => write!(f, "filter"),
+            PluginType::Output => write!(f, "output"),
+        }
+    }
+}
+
 /// Block represents statements inside `{ ... }`.
 pub type Block = Vec<BranchOrPlugin>;
 
@@ -25,9 +64,130 @@ pub enum BranchOrPlugin {
     Plugin(Plugin),
 }
 
-#[derive(Debug, PartialEq)]
+/// Position of a node in the config source.
+///
+/// Locations are ignored when nodes are compared, so hand-built ASTs are equal to parsed ones.
+/// Hand-built nodes have a zero (i.e. unknown) location.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Location {
+    /// Byte offset from the beginning of the source.
+    pub offset: usize,
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column number (in characters).
+    pub column: usize,
+}
+
+impl Location {
+    pub fn is_known(&self) -> bool {
+        self.line > 0
+    }
+}
+
+impl PartialEq for Location {
+    fn eq(&self, _: &Location) -> bool {
+        true
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Plugin {
     pub name: String,
+    pub attributes: Vec<Attribute>,
+    pub location: Location,
+}
+
+impl Plugin {
+    pub fn new(name: String, attributes: Vec<Attribute>) -> Plugin {
+        Plugin {
+            name,
+            attributes,
+            location: Location::default(),
+        }
+    }
+}
+
+/// Plugin attribute, i.e. `name => value` statement inside plugin's `{ ... }`.
+///
+/// Attributes are kept in the order of their appearance in the config.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attribute {
+    pub name: String,
+    pub value: Value,
+    pub location: Location,
+}
+
+impl Attribute {
+    pub fn new(name: String, value: Value) -> Attribute {
+        Attribute {
+            name,
+            value,
+            location: Location::default(),
+        }
+    }
+}
+
+/// Value of an attribute.
+///
+/// Logstash rule: `plugin / bareword / string / number / array / hash`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Plugin(Plugin),
+    Bareword(String),
+    String(String),
+    Number(f64),
+    Array(Vec<Value>),
+    Hash(Vec<HashEntry>),
+}
+
+impl From<Plugin> for Value {
+    fn from(v: Plugin) -> Self {
+        Value::Plugin(v)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Number(v)
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::String(v)
+    }
+}
+
+impl From<&'static str> for Value {
+    fn from(v: &'static str) -> Self {
+        Value::String(v.to_string())
+    }
+}
+
+/// Entry of a hash value, i.e. `key => value` statement inside `{ ... }`.
+///
+/// Keys can be numbers, barewords or strings but all of them are stored as strings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HashEntry {
+    pub key: String,
+    pub value: Value,
+    pub location: Location,
+}
+
+impl HashEntry {
+    pub fn new(key: String, value: Value) -> HashEntry {
+        HashEntry {
+            key,
+            value,
+            location: Location::default(),
+        }
+    }
 }
 
 /// A branch is essentially a vec of cases `if {...} else if {...} else if {...} else {...}`.
@@ -37,6 +197,7 @@ pub struct Plugin {
 #[derive(Debug, PartialEq)]
 pub struct Branch {
     pub cases: Vec<Case>,
+    pub location: Location,
 }
 
 impl Branch {
@@ -47,7 +208,10 @@ impl Branch {
         if let Some(c) = case_else {
             cases.push(c);
         }
-        Branch { cases: cases }
+        Branch {
+            cases,
+            location: Location::default(),
+        }
     }
 }
 
@@ -58,6 +222,18 @@ impl Branch {
 pub struct Case {
     pub condition: Condition,
     pub block: Block,
+    /// Location of `if` or `else` keyword.
+    pub location: Location,
+}
+
+impl Case {
+    pub fn new(condition: Condition, block: Block) -> Case {
+        Case {
+            condition,
+            block,
+            location: Location::default(),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -83,11 +259,17 @@ pub enum BoolExpr {
     Parens(Box<Condition>),
     Negative(Box<BoolExpr>), // TODO: maybe use Condition instead of BoolExpr here?
     Compare(CompareOperator, Rvalue, Rvalue),
+    /// Membership test, i.e. `lhs in rhs` or `lhs not in rhs`.
+    In(InOperator, Rvalue, Rvalue),
+    /// Regular expression matching, i.e. `lhs =~ /re/` or `lhs !~ "re"`.
+    Match(MatchOperator, Rvalue, Rvalue),
     Rvalue(Rvalue),
 }
 
-impl BoolExpr {
-    pub fn not(self) -> BoolExpr {
+impl Not for BoolExpr {
+    type Output = BoolExpr;
+
+    fn not(self) -> BoolExpr {
         BoolExpr::Negative(Box::new(self))
     }
 }
@@ -98,16 +280,35 @@ impl From<Rvalue> for BoolExpr {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Selector {
     pub elements: Vec<String>,
 }
 
+impl<'a> From<&'a str> for Selector {
+    /// Converts field references like `[a][b][0]` or just `a` to selectors.
+    fn from(v: &'a str) -> Self {
+        let elements = if v.starts_with('[') && v.ends_with(']') {
+            v[1..v.len() - 1].split("][").map(|e| e.to_string()).collect()
+        } else {
+            vec![v.to_string()]
+        };
+        Selector { elements }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Rvalue {
     Number(f64),
     String(String),
     Selector(Selector),
+    /// Regular expression literal, i.e. `/pattern/`.
+    Regex(String),
+    /// Array literal, i.e. `[rvalue, ...]`.
+    Array(Vec<Rvalue>),
+    /// Hash literal, i.e. `{ key => rvalue ... }`. Keys are kept in order of appearance.
+    Hash(Vec<(String, Rvalue)>),
+    MethodCall(MethodCall),
 }
 
 impl From<f64> for Rvalue {
@@ -128,12 +329,37 @@ impl From<&'static str> for Rvalue {
     }
 }
 
+impl From<Vec<Rvalue>> for Rvalue {
+    fn from(v: Vec<Rvalue>) -> Self {
+        Rvalue::Array(v)
+    }
+}
+
 impl From<Selector> for Rvalue {
     fn from(v: Selector) -> Self {
         Rvalue::Selector(v)
     }
 }
 
+impl From<MethodCall> for Rvalue {
+    fn from(v: MethodCall) -> Self {
+        Rvalue::MethodCall(v)
+    }
+}
+
+/// Function call, e.g. `length([tags])`.
+#[derive(Debug, PartialEq)]
+pub struct MethodCall {
+    pub name: String,
+    pub args: Vec<Rvalue>,
+}
+
+impl MethodCall {
+    pub fn new(name: String, args: Vec<Rvalue>) -> MethodCall {
+        MethodCall { name, args }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BoolOperator {
     And,
@@ -172,3 +398,33 @@ impl CompareOperator {
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InOperator {
+    In,
+    NotIn,
+}
+
+impl InOperator {
+    pub fn to_string(&self) -> &'static str {
+        match *self {
+            InOperator::In => "in",
+            InOperator::NotIn => "not in",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MatchOperator {
+    Match,
+    NotMatch,
+}
+
+impl MatchOperator {
+    pub fn to_string(&self) -> &'static str {
+        match *self {
+            MatchOperator::Match => "=~",
+            MatchOperator::NotMatch => "!~",
+        }
+    }
+}
@@ -1,36 +1,569 @@
-use pipeline::{InputSection, FilterSection, OutputSection};
-use plugin::{InputPlugin, FilterPlugin, OutputPlugin};
-use plugin::factory::PluginProvider;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::sync::Mutex;
+
+use pipeline::{ArgType, Case, ConditionError, FunctionRegistry, Predicate, Statement};
+use pipeline::{InputSection, FilterSection, FilterStatement, OutputSection, OutputStatement};
+use plugin::{FilterPlugin, InputPlugin, OutputPlugin};
+use plugin::factory::{did_you_mean, Error as PluginError, PluginProvider};
+use plugin::settings::SettingError;
 use super::ast::*;
+use super::variables::Variables;
 use super::visit;
 use super::visit::Visitor;
 
+/// Place in the config an error refers to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Span {
+    pub file: Option<String>,
+    /// Unknown for hand-built ASTs.
+    pub location: Location,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.file, self.location.is_known()) {
+            (Some(file), true) => write!(f, "{}:{}", file, self.location),
+            (Some(file), false) => write!(f, "{}", file),
+            (None, true) => write!(f, "config:{}", self.location),
+            (None, false) => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CompileError {
+    UnknownPlugin {
+        section: PluginType,
+        name: String,
+        /// Known plugins with similar names.
+        suggestions: Vec<String>,
+        span: Span,
+    },
+    InvalidAttribute {
+        section: PluginType,
+        name: String,
+        error: SettingError,
+        span: Span,
+    },
+    CannotCreatePlugin {
+        section: PluginType,
+        name: String,
+        span: Span,
+    },
+    /// `${VAR}` reference to a variable which is neither in the keystore nor in the
+    /// environment and has no default.
+    UndefinedVariable {
+        section: PluginType,
+        name: String,
+        variable: String,
+        span: Span,
+    },
+    ConditionalInput { span: Span },
+    /// Comparison of values which are never comparable, e.g. `1 < "a"`.
+    TypeMismatch {
+        section: PluginType,
+        operator: CompareOperator,
+        span: Span,
+    },
+    InvalidRegex {
+        section: PluginType,
+        error: String,
+        span: Span,
+    },
+    UnknownFunction {
+        section: PluginType,
+        name: String,
+        span: Span,
+    },
+    /// `=~` or `!~` with something else than a string or regex literal on the right.
+    NonLiteralRegex { section: PluginType, span: Span },
+    WrongArity {
+        section: PluginType,
+        function: String,
+        expected: usize,
+        given: usize,
+        span: Span,
+    },
+    /// Argument of a type the function never accepts, e.g. `exists("a")`. Arguments are
+    /// numbered from 1.
+    InvalidArgument {
+        section: PluginType,
+        function: String,
+        position: usize,
+        expected: ArgType,
+        span: Span,
+    },
+}
+
+impl CompileError {
+    pub fn span(&self) -> &Span {
+        match self {
+            CompileError::UnknownPlugin { span, .. } |
+            CompileError::InvalidAttribute { span, .. } |
+            CompileError::CannotCreatePlugin { span, .. } |
+            CompileError::UndefinedVariable { span, .. } |
+            CompileError::ConditionalInput { span } |
+            CompileError::TypeMismatch { span, .. } |
+            CompileError::InvalidRegex { span, .. } |
+            CompileError::UnknownFunction { span, .. } |
+            CompileError::NonLiteralRegex { span, .. } |
+            CompileError::WrongArity { span, .. } |
+            CompileError::InvalidArgument { span, .. } => span,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            CompileError::UnknownPlugin { section, name, suggestions, .. } => {
+                format!("Unknown {} plugin `{}`{}", section, name, did_you_mean(suggestions))
+            }
+            CompileError::InvalidAttribute { section, name, error, .. } => {
+                format!("Invalid {} plugin `{}`: {}", section, name, error)
+            }
+            CompileError::CannotCreatePlugin { section, name, .. } => {
+                format!("Cannot create {} plugin `{}`", section, name)
+            }
+            CompileError::UndefinedVariable { section, name, variable, .. } => {
+                format!("Invalid {} plugin `{}`: variable `{}` is not defined",
+                        section,
+                        name,
+                        variable)
+            }
+            CompileError::ConditionalInput { .. } => "Conditional inputs are forbidden".to_string(),
+            CompileError::TypeMismatch { section, operator, .. } => {
+                format!("Type mismatch in {} condition: numbers and strings cannot be \
+                         compared with `{}`",
+                        section,
+                        operator.to_string())
+            }
+            CompileError::InvalidRegex { section, error, .. } => {
+                format!("Invalid regex in {} condition: {}", section, error)
+            }
+            CompileError::UnknownFunction { section, name, .. } => {
+                format!("Unknown function `{}` in {} condition", name, section)
+            }
+            CompileError::NonLiteralRegex { section, .. } => {
+                format!("Regex in {} condition must be a string or regex literal", section)
+            }
+            CompileError::WrongArity { section, function, expected, given, .. } => {
+                format!("Function `{}` in {} condition takes {} argument(s) but {} given",
+                        function,
+                        section,
+                        expected,
+                        given)
+            }
+            CompileError::InvalidArgument { section, function, position, expected, .. } => {
+                format!("Argument {} of function `{}` in {} condition must be {}",
+                        position,
+                        function,
+                        section,
+                        expected)
+            }
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let span = self.span().to_string();
+        if span.is_empty() {
+            write!(f, "{}", self.message())
+        } else {
+            write!(f, "{}: {}", span, self.message())
+        }
+    }
+}
+
+impl error::Error for CompileError {}
+
 struct Compiler<'a> {
-    plugin_provider: &'a PluginProvider,
+    plugin_provider: &'a dyn PluginProvider,
+    functions: &'a FunctionRegistry,
+    variables: &'a Variables,
     sess: Session,
+    /// Type and file of the section being compiled.
+    section: PluginType,
+    file: Option<&'a str>,
+    /// Blocks being compiled, the innermost one goes last.
+    filters: Vec<Vec<FilterStatement>>,
+    outputs: Vec<Vec<OutputStatement>>,
 }
 
-impl<'a, 'ast> Visitor<'ast> for Compiler<'a> {
-    fn visit_input_plugin(&mut self, plugin: &'ast Plugin) {
-        match self.plugin_provider.create_input(&plugin.name) {
-            Ok(p) => self.sess.inputs.add_plugin(p),
-            Err(_) => self.sess.errors.push("Cannot create input plugin"),
+impl<'a> Compiler<'a> {
+    fn span(&self, location: Location) -> Span {
+        Span {
+            file: self.file.map(|f| f.to_string()),
+            location,
         }
     }
 
-    fn visit_input_branch(&mut self, _: &'ast Branch) {
-        self.sess.errors.push("Conditional inputs are forbidden")
+    /// Validates plugin attributes against the plugin schema and creates the plugin.
+    ///
+    /// All the problems found are reported, not just the first one.
+    fn compile_plugin(&mut self, plugin: &Plugin) {
+        let provider = self.plugin_provider;
+        let schema = match self.section {
+            PluginType::Input => provider.input_schema(&plugin.name),
+            PluginType::Filter => provider.filter_schema(&plugin.name),
+            PluginType::Output => provider.output_schema(&plugin.name),
+        };
+        let schema = match schema {
+            Ok(s) => s,
+            Err(PluginError::PluginNotFound { suggestions, .. }) => {
+                let err = CompileError::UnknownPlugin {
+                    section: self.section,
+                    name: plugin.name.clone(),
+                    suggestions,
+                    span: self.span(plugin.location),
+                };
+                return self.sess.errors.push(err);
+            }
+        };
+
+        let mut attributes = plugin.attributes.clone();
+        let mut undefined = vec![];
+        // Config text of the string attributes with variables substituted.
+        let mut sources = HashMap::new();
+        for attr in &mut attributes {
+            let original = attr.value.clone();
+            substitute(&mut attr.value, self.variables, attr.location, &mut undefined);
+            if let Value::String(source) = original {
+                if attr.value != Value::String(source.clone()) {
+                    sources.insert(attr.name.clone(), source);
+                }
+            }
+        }
+        if !undefined.is_empty() {
+            for (variable, location) in undefined {
+                let err = CompileError::UndefinedVariable {
+                    section: self.section,
+                    name: plugin.name.clone(),
+                    variable,
+                    span: self.span(location),
+                };
+                self.sess.errors.push(err);
+            }
+            return;
+        }
+
+        let settings = match schema.validate(&attributes) {
+            Ok(settings) => settings,
+            Err(errors) => {
+                let mut duplicates = HashMap::new();
+                for error in errors {
+                    // Errors are reported at the attribute, repetitions of duplicate ones
+                    // are reported in order.
+                    let find = |name: &str, n: usize| {
+                        attributes.iter()
+                            .filter(|a| a.name == name)
+                            .nth(n)
+                            .map_or(plugin.location, |a| a.location)
+                    };
+                    let location = match error {
+                        SettingError::Missing(_) => plugin.location,
+                        SettingError::Duplicate(ref name) => {
+                            let n = duplicates.entry(name.clone()).or_insert(0);
+                            *n += 1;
+                            find(name, *n)
+                        }
+                        SettingError::Unknown(ref name) |
+                        SettingError::Obsolete(ref name, _) |
+                        SettingError::InvalidType(ref name, _) |
+                        SettingError::NotAllowed(ref name, ..) |
+                        SettingError::InvalidTemplate(ref name, _) => find(name, 0),
+                    };
+                    // Substituted values may be secrets, so the config text is quoted instead.
+                    let error = match error {
+                        SettingError::NotAllowed(name, value, allowed) => {
+                            let value = sources.get(&name).cloned().unwrap_or(value);
+                            SettingError::NotAllowed(name, value, allowed)
+                        }
+                        error => error,
+                    };
+                    let err = CompileError::InvalidAttribute {
+                        section: self.section,
+                        name: plugin.name.clone(),
+                        error,
+                        span: self.span(location),
+                    };
+                    self.sess.errors.push(err);
+                }
+                return;
+            }
+        };
+
+        let created = match self.section {
+            PluginType::Input => {
+                provider.create_input(&plugin.name, &settings)
+                    .map(|p| self.sess.inputs.add_plugin(InputPlugin::new(settings, p)))
+            }
+            PluginType::Filter => {
+                let block = self.filters.last_mut().unwrap();
+                provider.create_filter(&plugin.name, &settings)
+                    .map(|p| block.push(Statement::Plugin(FilterPlugin::new(settings, p))))
+            }
+            PluginType::Output => {
+                let block = self.outputs.last_mut().unwrap();
+                provider.create_output(&plugin.name, &settings).map(|p| {
+                    block.push(Statement::Plugin(Mutex::new(OutputPlugin::new(settings, p))))
+                })
+            }
+        };
+        if created.is_err() {
+            let err = CompileError::CannotCreatePlugin {
+                section: self.section,
+                name: plugin.name.clone(),
+                span: self.span(plugin.location),
+            };
+            self.sess.errors.push(err);
+        }
+    }
+
+    /// Compiles every case of a conditional into a predicate and a nested block of plugins.
+    fn compile_branch(&mut self, branch: &'a Branch) {
+        let mut filter_cases = vec![];
+        let mut output_cases = vec![];
+        for case in &branch.cases {
+            let errors = self.sess.errors.len();
+            self.check_condition(&case.condition, case.location);
+            // Blocks are compiled even if the condition is not, to report all the errors.
+            let predicate = if self.sess.errors.len() > errors {
+                Err(())
+            } else {
+                Predicate::compile(&case.condition, self.functions).map_err(|e| {
+                    let err = match e {
+                        ConditionError::InvalidRegex(e) => {
+                            CompileError::InvalidRegex {
+                                section: self.section,
+                                error: e.to_string(),
+                                span: self.span(case.location),
+                            }
+                        }
+                        ConditionError::UnknownFunction(name) => {
+                            CompileError::UnknownFunction {
+                                section: self.section,
+                                name,
+                                span: self.span(case.location),
+                            }
+                        }
+                        ConditionError::NonLiteralRegex => {
+                            CompileError::NonLiteralRegex {
+                                section: self.section,
+                                span: self.span(case.location),
+                            }
+                        }
+                    };
+                    self.sess.errors.push(err);
+                })
+            };
+
+            if self.section == PluginType::Filter {
+                self.filters.push(vec![]);
+                visit::walk_filter_block(self, &case.block);
+                let block = self.filters.pop().unwrap();
+                if let Ok(predicate) = predicate {
+                    filter_cases.push(Case::new(predicate, block));
+                }
+            } else {
+                self.outputs.push(vec![]);
+                visit::walk_output_block(self, &case.block);
+                let block = self.outputs.pop().unwrap();
+                if let Ok(predicate) = predicate {
+                    output_cases.push(Case::new(predicate, block));
+                }
+            }
+        }
+
+        if self.section == PluginType::Filter {
+            self.filters.last_mut().unwrap().push(Statement::Branch(filter_cases));
+        } else {
+            self.outputs.last_mut().unwrap().push(Statement::Branch(output_cases));
+        }
+    }
+
+    /// Reports comparisons which can never be evaluated and invalid function calls.
+    fn check_condition(&mut self, condition: &Condition, location: Location) {
+        match condition {
+            Condition::Leaf(expr) => self.check_bool_expr(expr, location),
+            Condition::Branch(_, left, right) => {
+                self.check_condition(left, location);
+                self.check_condition(right, location);
+            }
+        }
+    }
+
+    fn check_bool_expr(&mut self, expr: &BoolExpr, location: Location) {
+        match expr {
+            BoolExpr::Parens(c) => self.check_condition(c, location),
+            BoolExpr::Negative(e) => self.check_bool_expr(e, location),
+            BoolExpr::Compare(op, left, right) => {
+                let ordering = !matches!(op, CompareOperator::Eq | CompareOperator::Ne);
+                let mismatch = matches!((left, right),
+                                        (Rvalue::Number(_), Rvalue::String(_)) |
+                                        (Rvalue::String(_), Rvalue::Number(_)));
+                if ordering && mismatch {
+                    let err = CompileError::TypeMismatch {
+                        section: self.section,
+                        operator: *op,
+                        span: self.span(location),
+                    };
+                    self.sess.errors.push(err);
+                }
+                self.check_rvalue(left, location);
+                self.check_rvalue(right, location);
+            }
+            BoolExpr::In(_, left, right) | BoolExpr::Match(_, left, right) => {
+                self.check_rvalue(left, location);
+                self.check_rvalue(right, location);
+            }
+            BoolExpr::Rvalue(v) => self.check_rvalue(v, location),
+        }
+    }
+
+    /// Checks that functions called exist and get as many arguments of the types they accept.
+    fn check_rvalue(&mut self, rvalue: &Rvalue, location: Location) {
+        match rvalue {
+            Rvalue::Array(items) => {
+                for item in items {
+                    self.check_rvalue(item, location);
+                }
+            }
+            Rvalue::Hash(entries) => {
+                for (_, value) in entries {
+                    self.check_rvalue(value, location);
+                }
+            }
+            Rvalue::MethodCall(call) => {
+                for arg in &call.args {
+                    self.check_rvalue(arg, location);
+                }
+                self.check_call(call, location);
+            }
+            Rvalue::Number(_) | Rvalue::String(_) | Rvalue::Selector(_) | Rvalue::Regex(_) => {}
+        }
+    }
+
+    fn check_call(&mut self, call: &MethodCall, location: Location) {
+        let function = match self.functions.get(&call.name) {
+            Some(f) => f,
+            None => {
+                let err = CompileError::UnknownFunction {
+                    section: self.section,
+                    name: call.name.clone(),
+                    span: self.span(location),
+                };
+                return self.sess.errors.push(err);
+            }
+        };
+
+        if function.params.len() != call.args.len() {
+            let err = CompileError::WrongArity {
+                section: self.section,
+                function: call.name.clone(),
+                expected: function.params.len(),
+                given: call.args.len(),
+                span: self.span(location),
+            };
+            return self.sess.errors.push(err);
+        }
+        for (i, (param, arg)) in function.params.iter().zip(&call.args).enumerate() {
+            if !param.accepts(arg) {
+                let err = CompileError::InvalidArgument {
+                    section: self.section,
+                    function: call.name.clone(),
+                    position: i + 1,
+                    expected: *param,
+                    span: self.span(location),
+                };
+                self.sess.errors.push(err);
+            }
+        }
+    }
+}
+
+/// Replaces `${VAR}` references in string values, including nested ones. Names of undefined
+/// variables are collected along with the location of the attribute they are referenced in.
+fn substitute(value: &mut Value,
+              variables: &Variables,
+              location: Location,
+              undefined: &mut Vec<(String, Location)>) {
+    match value {
+        Value::String(s) => {
+            match variables.substitute(s) {
+                Ok(substituted) => *s = substituted,
+                Err(variable) => undefined.push((variable, location)),
+            }
+        }
+        Value::Array(values) => {
+            for v in values {
+                substitute(v, variables, location, undefined);
+            }
+        }
+        Value::Hash(entries) => {
+            for entry in entries {
+                substitute(&mut entry.value, variables, location, undefined);
+            }
+        }
+        Value::Plugin(plugin) => {
+            for attr in &mut plugin.attributes {
+                substitute(&mut attr.value, variables, attr.location, undefined);
+            }
+        }
+        Value::Bareword(_) | Value::Number(_) => {}
+    }
+}
+
+impl<'a> Visitor<'a> for Compiler<'a> {
+    fn visit_section(&mut self, section: &'a PluginSection) {
+        self.section = section.plugin_type;
+        self.file = section.file.as_deref();
+        visit::walk_plugin_section(self, section)
+    }
+
+    fn visit_input_plugin(&mut self, plugin: &'a Plugin) {
+        self.compile_plugin(plugin)
+    }
+
+    fn visit_input_branch(&mut self, branch: &'a Branch) {
+        let err = CompileError::ConditionalInput { span: self.span(branch.location) };
+        self.sess.errors.push(err);
+    }
+
+    fn visit_filter_plugin(&mut self, plugin: &'a Plugin) {
+        self.compile_plugin(plugin)
+    }
+
+    fn visit_filter_branch(&mut self, branch: &'a Branch) {
+        self.compile_branch(branch)
+    }
+
+    fn visit_output_plugin(&mut self, plugin: &'a Plugin) {
+        self.compile_plugin(plugin)
+    }
+
+    fn visit_output_branch(&mut self, branch: &'a Branch) {
+        self.compile_branch(branch)
     }
 }
 
 pub struct Session {
-    pub errors: Vec<&'static str>,
+    pub errors: Vec<CompileError>,
     pub inputs: InputSection,
     pub filters: FilterSection,
     pub outputs: OutputSection,
 }
 
-pub fn compile(config: &Config, plugin_provider: &PluginProvider) -> Session {
+/// Compiles the config into pipeline sections reporting all the errors found at once.
+///
+/// Conditions may call the functions of the registry given, `${VAR}` references in attribute
+/// values are replaced with the variables given.
+pub fn compile<'a>(config: &'a Config,
+                   plugin_provider: &'a dyn PluginProvider,
+                   functions: &'a FunctionRegistry,
+                   variables: &'a Variables)
+                   -> Session {
     let sess = Session {
         errors: vec![],
         inputs: InputSection::new(),
@@ -38,21 +571,37 @@ pub fn compile(config: &Config, plugin_provider: &PluginProvider) -> Session {
         outputs: OutputSection::new(),
     };
     let mut compiler = Compiler {
-        plugin_provider: plugin_provider,
-        sess: sess,
+        plugin_provider,
+        functions,
+        variables,
+        sess,
+        section: PluginType::Input,
+        file: None,
+        filters: vec![vec![]],
+        outputs: vec![vec![]],
     };
 
     visit::walk_config(&mut compiler, config);
+    for statement in compiler.filters.pop().unwrap() {
+        compiler.sess.filters.add_statement(statement);
+    }
+    for statement in compiler.outputs.pop().unwrap() {
+        compiler.sess.outputs.add_statement(statement);
+    }
     compiler.sess
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashSet;
+    use std::collections::{HashMap, HashSet};
 
-    use config::ast::*;
+    use event::Event;
+    use pipeline::QueueWriter;
+    use plugin::{Codec, Filter, Input, Output, StopSignal};
     use plugin::factory::Result as PFResult;
     use plugin::factory::Error as PFError;
+    use plugin::settings::{Schema, Setting, Settings, SettingType};
+    use config::variables::Keystore;
     use super::*;
 
     struct DummyFactory {
@@ -84,44 +633,298 @@ mod tests {
         }
     }
 
+    #[derive(Clone)]
+    struct Dummy;
+
+    impl Input for Dummy {
+        fn run(&mut self, _queue: &QueueWriter, _stop: &StopSignal) {}
+    }
+
+    impl Filter for Dummy {
+        fn filter(&mut self, _events: &mut Vec<Event>) {}
+    }
+
+    impl Output for Dummy {
+        fn receive(&mut self, _events: &[&Event]) {}
+    }
+
+    fn not_found(name: &str) -> PFError {
+        PFError::PluginNotFound { name: name.to_string(), suggestions: vec![] }
+    }
+
+    fn schema(known: &HashSet<&'static str>, name: &str, base: Schema) -> PFResult<Schema> {
+        if !known.contains(name) {
+            return Err(not_found(name));
+        }
+        match name {
+            "file" => {
+                Ok(base.setting(Setting::new("path", SettingType::Array).required())
+                    .setting(Setting::new("stat_interval", SettingType::Number))
+                    .setting(Setting::new("start_position", SettingType::String)
+                        .allowed(&["beginning", "end"])))
+            }
+            _ => Ok(base),
+        }
+    }
+
     impl PluginProvider for DummyFactory {
-        fn create_input(&self, name: &str) -> PFResult<InputPlugin> {
+        fn input_schema(&self, name: &str) -> PFResult<Schema> {
+            schema(&self.inputs, name, Schema::input())
+        }
+
+        fn filter_schema(&self, name: &str) -> PFResult<Schema> {
+            schema(&self.filters, name, Schema::filter())
+        }
+
+        fn output_schema(&self, name: &str) -> PFResult<Schema> {
+            schema(&self.outputs, name, Schema::output())
+        }
+
+        fn codec_schema(&self, name: &str) -> PFResult<Schema> {
+            Err(not_found(name))
+        }
+
+        fn create_input(&self, name: &str, _: &Settings) -> PFResult<Box<dyn Input>> {
             if self.inputs.contains(name) {
-                Ok(InputPlugin {})
+                Ok(Box::new(Dummy))
             } else {
-                Err(PFError::PluginNotFound)
+                Err(not_found(name))
             }
         }
+
+        fn create_filter(&self, name: &str, _: &Settings) -> PFResult<Box<dyn Filter>> {
+            if self.filters.contains(name) {
+                Ok(Box::new(Dummy))
+            } else {
+                Err(not_found(name))
+            }
+        }
+
+        fn create_output(&self, name: &str, _: &Settings) -> PFResult<Box<dyn Output>> {
+            if self.outputs.contains(name) {
+                Ok(Box::new(Dummy))
+            } else {
+                Err(not_found(name))
+            }
+        }
+
+        fn create_codec(&self, name: &str, _: &Settings) -> PFResult<Box<dyn Codec>> {
+            Err(not_found(name))
+        }
+    }
+
+    fn functions() -> FunctionRegistry {
+        FunctionRegistry::builtin()
+    }
+
+    fn messages(sess: &Session) -> Vec<String> {
+        sess.errors.iter().map(|e| e.to_string()).collect::<Vec<_>>()
     }
 
     #[test]
     fn test_compile_simple() {
+        let path = || Attribute::new("path".to_string(), Value::from("/tmp/a.log"));
         let config = Config {
             sections: vec![
+                PluginSection::new(PluginType::Input, vec![
+                    BranchOrPlugin::Plugin(Plugin::new("stdin".to_string(), vec![])),
+                    BranchOrPlugin::Plugin(Plugin::new("file".to_string(), vec![path()])),
+                ]),
+                PluginSection::new(PluginType::Filter, vec![]),
+                PluginSection::new(PluginType::Output, vec![
+                    BranchOrPlugin::Plugin(Plugin::new("stdout".to_string(), vec![])),
+                    BranchOrPlugin::Plugin(Plugin::new("file".to_string(), vec![path()])),
+                ])
+            ],
+            comments: vec![],
+        };
+
+        let factory = DummyFactory::new(vec!["stdin", "file"], vec![], vec!["stdout", "file"]);
+
+        let sess = compile(&config, &factory, &functions(), &Variables::default());
+        assert_eq!(sess.errors.len(), 0);
+        assert_eq!(2, sess.inputs.count());
+        assert_eq!(0, sess.filters.count());
+        assert_eq!(2, sess.outputs.count());
+    }
+
+    #[test]
+    fn test_compile_report_locations() {
+        let conf = "input {\n  stdin { }\n  if [a] { stdin {} }\n}\n\
+                    filter {\n  if [a] {\n    grok {}\n  } else {\n    \
+                    drop { foo => 1 add_tag => 'a' add_tag => 'b' }\n  }\n}\n\
+                    output { stdout {} }";
+        let config = ::config::parse::parse(conf.as_bytes()).unwrap();
+        let factory = DummyFactory::new(vec!["stdin"], vec!["drop"], vec!["stdout"]);
+
+        let sess = compile(&config, &factory, &functions(), &Variables::default());
+        assert_eq!(vec!["config:3:3: Conditional inputs are forbidden",
+                        "config:7:5: Unknown filter plugin `grok`",
+                        "config:9:12: Invalid filter plugin `drop`: unknown setting `foo`",
+                        "config:9:36: Invalid filter plugin `drop`: setting `add_tag` is \
+                         specified more than once"],
+                   messages(&sess));
+        assert_eq!(1, sess.inputs.count());
+        assert_eq!(0, sess.filters.count());
+        assert_eq!(1, sess.outputs.count());
+    }
+
+    #[test]
+    fn test_compile_conditionals() {
+        let conf = "filter {\n  if [a] { drop {} } else if [b] { if [c] { drop {} } mutate {} }\n\
+                    drop {}\n}\noutput { if [x] { stdout {} } else { } }";
+        let config = ::config::parse::parse(conf.as_bytes()).unwrap();
+        let factory = DummyFactory::new(vec![], vec!["drop", "mutate"], vec!["stdout"]);
+
+        let sess = compile(&config, &factory, &functions(), &Variables::default());
+        assert!(sess.errors.is_empty());
+        assert_eq!(4, sess.filters.count());
+        assert_eq!(1, sess.outputs.count());
+    }
+
+    #[test]
+    fn test_compile_type_mismatch() {
+        let conf = "filter { if [a] == 1 or (1 >= \"b\") { } }\noutput { if \"a\" < 2 { } }";
+        let config = ::config::parse::parse(conf.as_bytes()).unwrap();
+        let factory = DummyFactory::new(vec![], vec![], vec![]);
+
+        let sess = compile(&config, &factory, &functions(), &Variables::default());
+        assert_eq!(2, sess.errors.len());
+        match sess.errors[0] {
+            CompileError::TypeMismatch { section, operator, ref span } => {
+                assert_eq!(PluginType::Filter, section);
+                assert_eq!(CompareOperator::Ge, operator);
+                assert_eq!((1, 10), (span.location.line, span.location.column));
+            }
+            ref e => panic!("unexpected error {:?}", e),
+        }
+        assert_eq!("config:2:10: Type mismatch in output condition: numbers and strings \
+                    cannot be compared with `<`",
+                   sess.errors[1].to_string());
+    }
+
+    #[test]
+    fn test_compile_invalid_regex() {
+        let conf = "filter {\n  if [a] =~ /(/ { drop {} } else if [b] !~ \"[\" { }\n}";
+        let config = ::config::parse::parse(conf.as_bytes()).unwrap();
+        let factory = DummyFactory::new(vec![], vec!["drop"], vec![]);
+
+        let sess = compile(&config, &factory, &functions(), &Variables::default());
+        assert_eq!(2, sess.errors.len());
+        // Errors of `else if` point at its `else`.
+        for (err, column) in sess.errors.iter().zip(&[3, 29]) {
+            match err {
+                CompileError::InvalidRegex { section: PluginType::Filter, span, .. } => {
+                    assert_eq!((2, *column), (span.location.line, span.location.column))
+                }
+                e => panic!("unexpected error {:?}", e),
+            }
+        }
+        assert!(sess.errors[0].to_string().starts_with("config:2:3: Invalid regex in filter"));
+        assert!(sess.errors[1].to_string().starts_with("config:2:29: Invalid regex in filter"));
+    }
+
+    #[test]
+    fn test_compile_non_literal_regex() {
+        let conf = "output {\n  if [a] =~ /x/ { }\n}";
+        let mut config = ::config::parse::parse(conf.as_bytes()).unwrap();
+        // Matches against a field instead of the regex.
+        if let BranchOrPlugin::Branch(ref mut b) = config.sections[0].block[0] {
+            if let Condition::Leaf(ref mut expr) = b.cases[0].condition {
+                if let BoolExpr::Match(_, _, ref mut right) = **expr {
+                    *right = Rvalue::Selector(Selector::from("b"));
+                }
+            }
+        }
+        let factory = DummyFactory::new(vec![], vec![], vec![]);
+
+        let sess = compile(&config, &factory, &FunctionRegistry::builtin(), &Variables::default());
+        assert_eq!(vec!["config:2:3: Regex in output condition must be a string or regex \
+                         literal"],
+                   messages(&sess));
+    }
+
+    #[test]
+    fn test_compile_function_calls() {
+        let conf = "filter {\n  if length([tags]) > 2 and lowercase([level]) == 'warn' {\n\
+                    drop {} }\n  if upper([a]) or length([a], 1) { }\n}\n\
+                    output { if exists('a') or length(1) { } }";
+        let config = ::config::parse::parse(conf.as_bytes()).unwrap();
+        let factory = DummyFactory::new(vec![], vec!["drop"], vec![]);
+
+        let sess = compile(&config, &factory, &functions(), &Variables::default());
+        assert_eq!(vec!["config:4:3: Unknown function `upper` in filter condition",
+                        "config:4:3: Function `length` in filter condition takes 1 argument(s) \
+                         but 2 given",
+                        "config:6:10: Argument 1 of function `exists` in output condition must \
+                         be a field reference",
+                        "config:6:10: Argument 1 of function `length` in output condition must \
+                         be a string, an array or a hash"],
+                   messages(&sess));
+        assert_eq!(1, sess.filters.count());
+
+        // Functions come from the registry given.
+        let sess = compile(&config, &factory, &FunctionRegistry::new(), &Variables::default());
+        assert_eq!(6, sess.errors.len());
+    }
+
+    #[test]
+    fn test_compile_variables() {
+        let conf = "input {\n  file { path => ['${LOG_DIR}/*.log'] stat_interval => '${N:1}' }\n\
+                    file { path => '${LOG_DIR:/tmp}/a' stat_interval => '${N:often}' }\n  \
+                    file { path => '${USER_DIR}' codec => json { charset => '${CHARSET}' } }\n  \
+                    file { path => 'a' start_position => 'from ${POSITION}' }\n}";
+        let config = ::config::parse::parse(conf.as_bytes()).unwrap();
+        let factory = DummyFactory::new(vec!["file"], vec![], vec![]);
+        let mut keystore = Keystore::new();
+        keystore.set("LOG_DIR", "/var/log");
+        keystore.set("POSITION", "s3cr3t");
+        let variables = Variables::new(keystore, HashMap::new());
+
+        let sess = compile(&config, &factory, &FunctionRegistry::builtin(), &variables);
+        assert_eq!(vec!["config:3:36: Invalid input plugin `file`: setting `stat_interval` \
+                         must be of type number",
+                        "config:4:10: Invalid input plugin `file`: variable `USER_DIR` is not \
+                         defined",
+                        "config:4:48: Invalid input plugin `file`: variable `CHARSET` is not \
+                         defined",
+                        "config:5:22: Invalid input plugin `file`: setting `start_position` has \
+                         invalid value `from ${POSITION}`, expected one of: beginning, end"],
+                   messages(&sess));
+        assert_eq!(1, sess.inputs.count());
+    }
+
+    #[test]
+    fn test_compile_invalid_attributes() {
+        let outputs = vec![
+            BranchOrPlugin::Plugin(Plugin::new("stdout".to_string(), vec![
+                Attribute::new("workers".to_string(), Value::from(2.0)),
+            ])),
+        ];
+        let config = Config {
+            sections: vec![
+                PluginSection::new(PluginType::Input, vec![
+                    BranchOrPlugin::Plugin(Plugin::new("file".to_string(), vec![
+                        Attribute::new("pathh".to_string(), Value::from("/tmp/a")),
+                    ])),
+                    BranchOrPlugin::Plugin(Plugin::new("kafka".to_string(), vec![])),
+                ]),
                 PluginSection {
-                    plugin_type: PluginType::Input,
-                    block: vec![
-                        BranchOrPlugin::Plugin(Plugin { name: "stdin".to_string() }),
-                        BranchOrPlugin::Plugin(Plugin { name: "file".to_string() }),
-                    ]
+                    file: Some("90-outputs.conf".to_string()),
+                    ..PluginSection::new(PluginType::Output, outputs)
                 },
-                PluginSection { plugin_type: PluginType::Filter, block: vec![] },
-                PluginSection {
-                    plugin_type: PluginType::Output,
-                    block: vec![
-                        BranchOrPlugin::Plugin(Plugin { name: "stdout".to_string() }),
-                        BranchOrPlugin::Plugin(Plugin { name: "file".to_string() }),
-                    ],
-                }
             ],
+            comments: vec![],
         };
 
-        let mut factory = DummyFactory::new(vec!["stdin", "file"], vec![], vec!["stdout", "file"]);
+        let factory = DummyFactory::new(vec!["file"], vec![], vec!["stdout"]);
 
-        let sess = compile(&config, &factory);
-        assert_eq!(sess.errors.len(), 0);
-        // TODO: assert_eq!(2, sess.inputs.count())
-        // TODO: assert_eq!(0, sess.filters.count())
-        // TODO: assert_eq!(2, sess.outputs.count())
+        let sess = compile(&config, &factory, &functions(), &Variables::default());
+        assert_eq!(vec!["Invalid input plugin `file`: unknown setting `pathh`",
+                        "Invalid input plugin `file`: required setting `path` is missing",
+                        "Unknown input plugin `kafka`",
+                        "90-outputs.conf: Invalid output plugin `stdout`: \
+                         setting `workers` is obsolete, use pipeline workers instead"],
+                   messages(&sess));
     }
 }
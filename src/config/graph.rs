@@ -0,0 +1,392 @@
+//! Pipeline graph for visualisation, exported as JSON or Graphviz DOT.
+//!
+//! Inputs feed the queue, filters are chained one after another and every output gets all
+//! the events which reach it. Branches are nodes of their own with an edge per case labelled
+//! with the case condition.
+
+use std::collections::{HashMap, HashSet};
+
+use event::{Map, Value as JsonValue};
+use super::ast::*;
+use super::visit::{self, Visitor};
+
+/// Id of the node events go through between inputs and filters.
+const QUEUE_ID: &str = "queue";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeKind {
+    Plugin(PluginType, String),
+    Branch,
+    Queue,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    /// Plugin's `id` attribute if there is one, otherwise an id generated from the name and
+    /// the order of appearance, e.g. `grok-2`. So ids are the same for the same config.
+    ///
+    /// Ids are unique: generated ones skip the ids given explicitly and repeated explicit
+    /// ones get a suffix, e.g. `console-2`.
+    pub id: String,
+    pub kind: NodeKind,
+    pub location: Location,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    /// Condition of the case for edges going out of branches, `else` for the rest of events.
+    pub condition: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+/// Builds the pipeline graph of a config.
+pub fn graph(config: &Config) -> Graph {
+    let mut explicit = ExplicitIds(HashSet::new());
+    visit::walk_config(&mut explicit, config);
+    let mut builder = Builder {
+        graph: Graph::default(),
+        tails: vec![(QUEUE_ID.to_string(), None)],
+        outputs: vec![],
+        counters: HashMap::new(),
+        used: HashSet::new(),
+        reserved: explicit.0,
+    };
+    builder.add_node(QUEUE_ID.to_string(), NodeKind::Queue, Location::default());
+    visit::walk_config(&mut builder, config);
+
+    // Outputs are connected once all the filters are known, sections can go in any order.
+    for block in builder.outputs.clone() {
+        visit::walk_output_block(&mut builder, block);
+    }
+    builder.graph
+}
+
+impl Graph {
+    pub fn to_json(&self) -> String {
+        let nodes = self.nodes.iter().map(|n| {
+            let mut node = Map::new();
+            node.insert("id".to_string(), JsonValue::from(n.id.as_str()));
+            match n.kind {
+                NodeKind::Plugin(plugin_type, ref name) => {
+                    node.insert("type".to_string(), JsonValue::from("plugin"));
+                    node.insert("section".to_string(), JsonValue::from(plugin_type.to_string()));
+                    node.insert("name".to_string(), JsonValue::from(name.as_str()));
+                }
+                NodeKind::Branch => {
+                    node.insert("type".to_string(), JsonValue::from("if"));
+                }
+                NodeKind::Queue => {
+                    node.insert("type".to_string(), JsonValue::from("queue"));
+                }
+            }
+            if n.location.is_known() {
+                node.insert("line".to_string(), JsonValue::Int(n.location.line as i64));
+                node.insert("column".to_string(), JsonValue::Int(n.location.column as i64));
+            }
+            JsonValue::Map(node)
+        });
+        let edges = self.edges.iter().map(|e| {
+            let mut edge = Map::new();
+            edge.insert("from".to_string(), JsonValue::from(e.from.as_str()));
+            edge.insert("to".to_string(), JsonValue::from(e.to.as_str()));
+            if let Some(ref condition) = e.condition {
+                edge.insert("condition".to_string(), JsonValue::from(condition.as_str()));
+            }
+            JsonValue::Map(edge)
+        });
+
+        let mut graph = Map::new();
+        graph.insert("nodes".to_string(), JsonValue::Array(nodes.collect()));
+        graph.insert("edges".to_string(), JsonValue::Array(edges.collect()));
+        JsonValue::Map(graph).to_string()
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph pipeline {\n");
+        for n in &self.nodes {
+            let (label, shape) = match n.kind {
+                NodeKind::Plugin(_, ref name) => (name.as_str(), "box"),
+                NodeKind::Branch => ("if", "diamond"),
+                NodeKind::Queue => ("queue", "cylinder"),
+            };
+            dot.push_str(&format!("  {} [label={}, shape={}];\n",
+                                  quote(&n.id), quote(label), shape));
+        }
+        for e in &self.edges {
+            dot.push_str(&format!("  {} -> {}", quote(&e.from), quote(&e.to)));
+            if let Some(ref condition) = e.condition {
+                dot.push_str(&format!(" [label={}]", quote(condition)));
+            }
+            dot.push_str(";\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Quotes DOT identifiers and labels.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+struct Builder<'ast> {
+    graph: Graph,
+    /// Nodes (along with edge conditions) the next filter or output gets events from.
+    tails: Vec<(String, Option<String>)>,
+    outputs: Vec<&'ast Block>,
+    /// Number of nodes generated so far per name, for the ids.
+    counters: HashMap<String, usize>,
+    /// Ids of the nodes added so far.
+    used: HashSet<String>,
+    /// Ids given explicitly anywhere in the config, never generated.
+    reserved: HashSet<String>,
+}
+
+impl<'ast> Builder<'ast> {
+    fn add_node(&mut self, id: String, kind: NodeKind, location: Location) -> String {
+        self.used.insert(id.clone());
+        self.graph.nodes.push(Node { id: id.clone(), kind, location });
+        id
+    }
+
+    fn is_free(&self, id: &str) -> bool {
+        !self.used.contains(id) && !self.reserved.contains(id)
+    }
+
+    fn generate_id(&mut self, name: &str) -> String {
+        loop {
+            let counter = self.counters.entry(name.to_string()).or_insert(0);
+            *counter += 1;
+            let id = format!("{}-{}", name, counter);
+            if self.is_free(&id) {
+                return id;
+            }
+        }
+    }
+
+    fn add_plugin(&mut self, plugin_type: PluginType, plugin: &Plugin) -> String {
+        let id = match explicit_id(plugin) {
+            Some(id) if !self.used.contains(&id) => id,
+            Some(id) => {
+                (2..).map(|n| format!("{}-{}", id, n)).find(|id| self.is_free(id)).unwrap()
+            }
+            None => self.generate_id(&plugin.name),
+        };
+        self.add_node(id, NodeKind::Plugin(plugin_type, plugin.name.clone()), plugin.location)
+    }
+
+    fn add_branch(&mut self, branch: &Branch) -> String {
+        let id = self.generate_id("if");
+        let id = self.add_node(id, NodeKind::Branch, branch.location);
+        self.connect(&id);
+        id
+    }
+
+    /// Adds edges from the current tails to the node.
+    fn connect(&mut self, to: &str) {
+        for (from, condition) in &self.tails {
+            self.graph.edges.push(Edge {
+                from: from.clone(),
+                to: to.to_string(),
+                condition: condition.clone(),
+            });
+        }
+    }
+}
+
+fn explicit_id(plugin: &Plugin) -> Option<String> {
+    plugin.attributes.iter().find(|a| a.name == "id").and_then(|a| match a.value {
+        Value::String(ref id) | Value::Bareword(ref id) => Some(id.clone()),
+        _ => None,
+    })
+}
+
+/// Collects the `id` attributes of all the plugins.
+struct ExplicitIds(HashSet<String>);
+
+impl ExplicitIds {
+    fn add(&mut self, plugin: &Plugin) {
+        self.0.extend(explicit_id(plugin));
+    }
+}
+
+impl<'ast> Visitor<'ast> for ExplicitIds {
+    fn visit_input_plugin(&mut self, plugin: &'ast Plugin) {
+        self.add(plugin);
+    }
+
+    fn visit_filter_plugin(&mut self, plugin: &'ast Plugin) {
+        self.add(plugin);
+    }
+
+    fn visit_output_plugin(&mut self, plugin: &'ast Plugin) {
+        self.add(plugin);
+    }
+}
+
+/// Labels the edge going to the case.
+fn case_label(branch: &Branch, i: usize) -> String {
+    let case = &branch.cases[i];
+    if i > 0 && i == branch.cases.len() - 1 && case.condition == Condition::truth() {
+        "else".to_string()
+    } else {
+        case.condition.to_string()
+    }
+}
+
+fn has_else(branch: &Branch) -> bool {
+    branch.cases.len() > 1 && branch.cases.last().is_some_and(|c| c.condition == Condition::truth())
+}
+
+impl<'ast> Visitor<'ast> for Builder<'ast> {
+    fn visit_section(&mut self, section: &'ast PluginSection) {
+        match section.plugin_type {
+            PluginType::Output => self.outputs.push(&section.block),
+            _ => visit::walk_plugin_section(self, section),
+        }
+    }
+
+    fn visit_input_plugin(&mut self, plugin: &'ast Plugin) {
+        let id = self.add_plugin(PluginType::Input, plugin);
+        self.graph.edges.push(Edge { from: id, to: QUEUE_ID.to_string(), condition: None });
+    }
+
+    fn visit_filter_plugin(&mut self, plugin: &'ast Plugin) {
+        let id = self.add_plugin(PluginType::Filter, plugin);
+        self.connect(&id);
+        self.tails = vec![(id, None)];
+    }
+
+    fn visit_filter_branch(&mut self, branch: &'ast Branch) {
+        let id = self.add_branch(branch);
+        let mut tails = vec![];
+        for (i, case) in branch.cases.iter().enumerate() {
+            self.tails = vec![(id.clone(), Some(case_label(branch, i)))];
+            visit::walk_filter_block(self, &case.block);
+            tails.append(&mut self.tails);
+        }
+        if !has_else(branch) {
+            tails.push((id, Some("else".to_string())));
+        }
+        self.tails = tails;
+    }
+
+    fn visit_output_plugin(&mut self, plugin: &'ast Plugin) {
+        let id = self.add_plugin(PluginType::Output, plugin);
+        self.connect(&id);
+    }
+
+    fn visit_output_branch(&mut self, branch: &'ast Branch) {
+        let id = self.add_branch(branch);
+        let tails = self.tails.clone();
+        for (i, case) in branch.cases.iter().enumerate() {
+            self.tails = vec![(id.clone(), Some(case_label(branch, i)))];
+            visit::walk_output_block(self, &case.block);
+        }
+        self.tails = tails;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::parse::parse;
+    use super::*;
+
+    fn edges(graph: &Graph) -> Vec<(&str, &str, Option<&str>)> {
+        graph.edges
+            .iter()
+            .map(|e| (e.from.as_str(), e.to.as_str(), e.condition.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn test_graph() {
+        let conf = br#"
+            output { if [level] == "error" { email {} } stdout { id => console } }
+            input { stdin {} beats { port => 5044 } }
+            filter {
+                grok {}
+                if [type] in ["nginx", "apache"] { geoip {} } else if ![tags] {} else { drop {} }
+                grok {}
+                if "x" in [tags] { mutate {} }
+            }
+        "#;
+        let graph = graph(&parse(conf).unwrap());
+
+        let ids: Vec<_> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(vec!["queue", "stdin-1", "beats-1", "grok-1", "if-1", "geoip-1", "drop-1",
+                        "grok-2", "if-2", "mutate-1", "if-3", "email-1", "console"],
+                   ids);
+        assert_eq!(NodeKind::Plugin(PluginType::Output, "stdout".to_string()),
+                   graph.nodes[12].kind);
+        assert_eq!((2, 57), (graph.nodes[12].location.line, graph.nodes[12].location.column));
+
+        let cond = Some(r#"[type] in ["nginx", "apache"]"#);
+        assert_eq!(vec![("stdin-1", "queue", None),
+                        ("beats-1", "queue", None),
+                        ("queue", "grok-1", None),
+                        ("grok-1", "if-1", None),
+                        ("if-1", "geoip-1", cond),
+                        ("if-1", "drop-1", Some("else")),
+                        ("geoip-1", "grok-2", None),
+                        ("if-1", "grok-2", Some("![tags]")),
+                        ("drop-1", "grok-2", None),
+                        ("grok-2", "if-2", None),
+                        ("if-2", "mutate-1", Some(r#""x" in [tags]"#)),
+                        ("mutate-1", "if-3", None),
+                        ("if-2", "if-3", Some("else")),
+                        ("if-3", "email-1", Some(r#"[level] == "error""#)),
+                        ("mutate-1", "console", None),
+                        ("if-2", "console", Some("else"))],
+                   edges(&graph));
+
+        let conf = br#"
+            input { stdin { id => queue } }
+            filter { grok {} grok { id => "grok-1" } mutate { id => mt } mutate { id => mt } }
+        "#;
+        let graph = super::graph(&parse(conf).unwrap());
+        let ids: Vec<_> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(vec!["queue", "queue-2", "grok-2", "grok-1", "mt", "mt-2"], ids);
+        assert_eq!(vec![("queue-2", "queue", None),
+                        ("queue", "grok-2", None),
+                        ("grok-2", "grok-1", None),
+                        ("grok-1", "mt", None),
+                        ("mt", "mt-2", None)],
+                   edges(&graph));
+    }
+
+    #[test]
+    fn test_export() {
+        let conf = b"input { stdin {} }\noutput { if [a] == \"b\" { stdout {} } }";
+        let graph = graph(&parse(conf).unwrap());
+
+        assert_eq!(r#"{"edges":[{"from":"stdin-1","to":"queue"},{"from":"queue","to":"if-1"},"#
+                       .to_string() +
+                   r#"{"condition":"[a] == \"b\"","from":"if-1","to":"stdout-1"}],"nodes":["# +
+                   r#"{"id":"queue","type":"queue"},"# +
+                   r#"{"column":9,"id":"stdin-1","line":1,"name":"stdin","section":"input","# +
+                   r#""type":"plugin"},{"column":10,"id":"if-1","line":2,"type":"if"},"# +
+                   r#"{"column":26,"id":"stdout-1","line":2,"name":"stdout","# +
+                   r#""section":"output","type":"plugin"}]}"#,
+                   graph.to_json());
+
+        assert_eq!(r#"digraph pipeline {
+  "queue" [label="queue", shape=cylinder];
+  "stdin-1" [label="stdin", shape=box];
+  "if-1" [label="if", shape=diamond];
+  "stdout-1" [label="stdout", shape=box];
+  "stdin-1" -> "queue";
+  "queue" -> "if-1";
+  "if-1" -> "stdout-1" [label="[a] == \"b\""];
+}
+"#,
+                   graph.to_dot());
+    }
+}
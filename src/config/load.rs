@@ -0,0 +1,146 @@
+//! Loads configs split into multiple files (e.g. `10-inputs.conf`, `90-outputs.conf`).
+
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use glob::glob;
+
+use super::ast::Config;
+use super::parse::{parse, ParseError};
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(String, io::Error),
+    Pattern(String, String),
+    NoFiles(String),
+    Parse(ParseError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(path, err) => write!(f, "Cannot read config `{}`: {}", path, err),
+            LoadError::Pattern(pattern, msg) => {
+                write!(f, "Invalid config pattern `{}`: {}", pattern, msg)
+            }
+            LoadError::NoFiles(path) => write!(f, "No config files found at `{}`", path),
+            LoadError::Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for LoadError {}
+
+/// Loads a config from a file, a directory or a glob pattern.
+///
+/// Files are read in lexical order and their sections are concatenated. Every section
+/// remembers the file it comes from. Comments are dropped since their locations are per file.
+pub fn load(path: &str) -> Result<Config, LoadError> {
+    let mut config = Config { sections: vec![], comments: vec![] };
+    for file in files(path)? {
+        let file = file.to_string_lossy().into_owned();
+        let mut conf = Vec::new();
+        fs::File::open(&file)
+            .and_then(|mut f| f.read_to_end(&mut conf))
+            .map_err(|e| LoadError::Io(file.clone(), e))?;
+
+        let loaded = parse(&conf).map_err(|e| LoadError::Parse(e.in_file(&file)))?;
+        for mut section in loaded.sections {
+            section.file = Some(file.clone());
+            config.sections.push(section);
+        }
+    }
+    Ok(config)
+}
+
+fn files(path: &str) -> Result<Vec<PathBuf>, LoadError> {
+    let io_error = |p: &Path, e| LoadError::Io(p.to_string_lossy().into_owned(), e);
+
+    let mut files = Vec::new();
+    if Path::new(path).is_dir() {
+        for entry in fs::read_dir(path).map_err(|e| io_error(Path::new(path), e))? {
+            let entry = entry.map_err(|e| io_error(Path::new(path), e))?;
+            if entry.path().is_file() {
+                files.push(entry.path());
+            }
+        }
+    } else if path.contains(['*', '?', '[']) {
+        let paths = glob(path).map_err(|e| LoadError::Pattern(path.to_string(), e.to_string()))?;
+        for p in paths {
+            let p = p.map_err(|e| {
+                io_error(e.path(), io::Error::new(e.error().kind(), e.error().to_string()))
+            })?;
+            if p.is_file() {
+                files.push(p);
+            }
+        }
+    } else {
+        files.push(PathBuf::from(path));
+    }
+
+    if files.is_empty() {
+        return Err(LoadError::NoFiles(path.to_string()));
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use config::ast::PluginType;
+    use super::*;
+
+    const CONF_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/config/tests/assets/conf.d");
+
+    fn sources(config: &Config) -> Vec<(&PluginType, String)> {
+        config.sections
+            .iter()
+            .map(|s| {
+                let file = s.file.as_ref().unwrap();
+                (&s.plugin_type, file.rsplit('/').next().unwrap().to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_load_dir_and_glob() {
+        let expected = vec![(&PluginType::Input, "10-inputs.conf".to_string()),
+                            (&PluginType::Filter, "50-filters.conf".to_string()),
+                            (&PluginType::Output, "90-outputs.conf".to_string())];
+
+        let config = load(CONF_DIR).unwrap();
+        assert_eq!(expected, sources(&config));
+
+        let config = load(&format!("{}/*.conf", CONF_DIR)).unwrap();
+        assert_eq!(expected, sources(&config));
+
+        let config = load(&format!("{}/9*.conf", CONF_DIR)).unwrap();
+        assert_eq!(vec![(&PluginType::Output, "90-outputs.conf".to_string())],
+                   sources(&config));
+    }
+
+    #[test]
+    fn test_load_errors() {
+        match load(&format!("{}/*.yml", CONF_DIR)) {
+            Err(LoadError::NoFiles(_)) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match load("/no/such/file.conf") {
+            Err(LoadError::Io(path, _)) => assert_eq!("/no/such/file.conf", path),
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        let invalid = concat!(env!("CARGO_MANIFEST_DIR"), "/src/config/tests/assets/invalid.conf");
+        match load(invalid) {
+            Err(LoadError::Parse(err)) => {
+                assert_eq!(Some(invalid.to_string()), err.file);
+                assert_eq!((8, 3), (err.line, err.column));
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+}
@@ -1,5 +1,9 @@
 pub mod ast;
 pub mod parse;
 pub mod compile;
+pub mod graph;
+pub mod load;
+pub mod print;
+pub mod variables;
 
-mod visit;
+pub mod visit;
@@ -1,126 +1,413 @@
+use std::error;
+use std::fmt;
+use std::ops::Not;
 use std::str;
 
-use nom::{alphanumeric, is_digit, multispace, IResult};
+use nom::{alphanumeric, is_digit, multispace, rest, ErrorKind, IResult, Needed};
+use nom::Err as NomError;
 
 use super::ast::*;
 
-// rule attribute
-//   name _ "=>" _ value
-//   <LogStash::Config::AST::Attribute>
-// end
+/// Number of source lines preceding the erroneous one shown in error reports.
+const SNIPPET_CONTEXT: usize = 2;
 
-// rule value
-//   plugin / bareword / string / number / array / hash
-// end
+/// Config parsing error.
+///
+/// Points to the place where parsing stopped and tells what was expected there.
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub file: Option<String>,
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column number (in characters).
+    pub column: usize,
+    pub expected: Vec<&'static str>,
+    /// Source lines up to the erroneous one, as `(line number, text)` pairs.
+    pub snippet: Vec<(usize, String)>,
+}
 
-// rule array_value
-//   bareword / string / number / array / hash
-// end
+impl ParseError {
+    fn new(conf: &[u8], offset: usize, expected: Vec<&'static str>) -> ParseError {
+        let (line, column) = Lines::new(conf).line_column(offset);
+
+        let first = line.saturating_sub(SNIPPET_CONTEXT).max(1);
+        let snippet = conf.split(|&c| c == b'\n')
+            .enumerate()
+            .map(|(n, text)| (n + 1, text))
+            .skip(first - 1)
+            .take(line - first + 1)
+            .map(|(n, text)| (n, String::from_utf8_lossy(text).trim_end_matches('\r').to_string()))
+            .collect();
+
+        ParseError {
+            file: None,
+            line,
+            column,
+            expected,
+            snippet,
+        }
+    }
 
-// rule bareword
-//   [A-Za-z_] [A-Za-z0-9_]+
-//   <LogStash::Config::AST::Bareword>
-// end
+    /// Attaches the name of the file being parsed to the error.
+    pub fn in_file(mut self, file: &str) -> ParseError {
+        self.file = Some(file.to_string());
+        self
+    }
 
-// rule regexp
-//   ( '/' ( '\/' / !'/' . )* '/'  <LogStash::Config::AST::RegExp>)
-// end
+    pub fn message(&self) -> String {
+        match self.expected.split_last() {
+            None if self.is_eof() => "unexpected end of file".to_string(),
+            None => "invalid syntax".to_string(),
+            Some((last, [])) => format!("expected {}", last),
+            Some((last, init)) => format!("expected {} or {}", init.join(", "), last),
+        }
+    }
 
-// rule array
-//   "["
-//   _
-//   (
-//     value (_ "," _ value)*
-//   )?
-//   _
-//   "]"
-//   <LogStash::Config::AST::Array>
-// end
+    fn is_eof(&self) -> bool {
+        match self.snippet.last() {
+            Some((_, text)) => self.column > text.chars().count(),
+            None => true,
+        }
+    }
+}
 
-// rule hash
-//   "{"
-//     _
-//     hashentries?
-//     _
-//   "}"
-//   <LogStash::Config::AST::Hash>
-// end
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.line.to_string().len();
+        let file = self.file.as_ref().map_or("config", |f| f.as_str());
 
-// rule hashentries
-//   hashentry (whitespace hashentry)*
-//   <LogStash::Config::AST::HashEntries>
-// end
+        writeln!(f, "{}", self.message())?;
+        writeln!(f, "{:w$}--> {}:{}:{}", "", file, self.line, self.column, w = width)?;
+        writeln!(f, "{:w$} |", "", w = width)?;
+        for (n, text) in &self.snippet {
+            writeln!(f, "{:>w$} | {}", n, text, w = width)?;
+        }
 
-// rule hashentry
-//   name:(number / bareword / string) _ "=>" _ value
-//   <LogStash::Config::AST::HashEntry>
-// end
+        // Keep tabs in the caret line to align it with the source line.
+        let indent: String = self.snippet
+            .last()
+            .map_or("", |(_, text)| text.as_str())
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        write!(f, "{:w$} | {}^", "", indent, w = width)
+    }
+}
 
-// rule in_expression
-//   rvalue _ in_operator _ rvalue
-//   <LogStash::Config::AST::InExpression>
-// end
+impl error::Error for ParseError {}
+
+// Codes of unrecoverable errors. Each one describes what was expected at the error position.
+const ERR_SECTION: u32 = 1;
+const ERR_BLOCK_START: u32 = 2;
+const ERR_BLOCK_END: u32 = 3;
+const ERR_PLUGIN_END: u32 = 4;
+const ERR_ARROW: u32 = 5;
+const ERR_VALUE: u32 = 6;
+const ERR_ARRAY_END: u32 = 7;
+const ERR_HASH_END: u32 = 8;
+const ERR_CONDITION: u32 = 9;
+const ERR_ARGS_END: u32 = 10;
+
+fn expected(code: u32) -> Vec<&'static str> {
+    match code {
+        ERR_SECTION => vec!["`input`", "`filter`", "`output`"],
+        ERR_BLOCK_START => vec!["`{`"],
+        ERR_BLOCK_END => vec!["`}`", "plugin name"],
+        ERR_PLUGIN_END => vec!["`}`", "attribute name"],
+        ERR_ARROW => vec!["`=>`"],
+        ERR_VALUE => vec!["value"],
+        ERR_ARRAY_END => vec!["`,`", "`]`"],
+        ERR_HASH_END => vec!["`}`", "hash key"],
+        ERR_CONDITION => vec!["condition"],
+        ERR_ARGS_END => vec!["`,`", "`)`"],
+        _ => vec![],
+    }
+}
 
-// rule not_in_expression
-//   rvalue _ not_in_operator _ rvalue
-//   <LogStash::Config::AST::NotInExpression>
-// end
+pub fn parse(conf: &[u8]) -> Result<Config, ParseError> {
+    match config(conf) {
+        IResult::Done(_, mut c) => {
+            let lines = Lines::new(conf);
+            resolve_locations(&mut c, &lines);
+            c.comments = scan_comments(&lines);
+            Ok(c)
+        }
+        IResult::Error(NomError::Position(ErrorKind::Custom(code), pos)) => {
+            Err(ParseError::new(conf, conf.len() - pos.len(), expected(code)))
+        }
+        IResult::Error(e) => {
+            let offset = error_input(&e).map_or(0, |pos| conf.len() - pos.len());
+            Err(ParseError::new(conf, offset, vec![]))
+        }
+        IResult::Incomplete(_) => Err(ParseError::new(conf, conf.len(), vec![])),
+    }
+}
 
-// rule in_operator
-//   "in"
-// end
+/// Returns the input at which the error occurred, if the error carries it.
+fn error_input<'a>(err: &NomError<&'a [u8]>) -> Option<&'a [u8]> {
+    match *err {
+        NomError::Position(_, pos) | NomError::NodePosition(_, pos, _) => Some(pos),
+        NomError::Code(_) | NomError::Node(_, _) => None,
+    }
+}
 
-// rule not_in_operator
-//   "not " _ "in"
-// end
+/// Line starts of a config, to turn byte offsets into lines and columns.
+struct Lines<'a> {
+    conf: &'a [u8],
+    /// Byte offsets of the line starts, the first line starts at 0.
+    starts: Vec<usize>,
+}
 
-// rule method_call
-//     method _ "(" _
-//       (
-//         rvalue ( _ "," _ rvalue )*
-//       )?
-//     _ ")"
-//   <LogStash::Config::AST::MethodCall>
-// end
+impl<'a> Lines<'a> {
+    fn new(conf: &'a [u8]) -> Lines<'a> {
+        let newlines = conf.iter().enumerate().filter(|&(_, &c)| c == b'\n').map(|(i, _)| i + 1);
+        Lines {
+            conf,
+            starts: Some(0).into_iter().chain(newlines).collect(),
+        }
+    }
 
-// rule method
-//   bareword
-// end
+    /// Returns 1-based line and column (in characters) numbers of the byte offset.
+    fn line_column(&self, offset: usize) -> (usize, usize) {
+        let line = self.starts.partition_point(|&start| start <= offset);
+        let text = &self.conf[self.starts[line - 1]..offset];
+        (line, String::from_utf8_lossy(text).chars().count() + 1)
+    }
+}
 
-// rule regexp_expression
-//   rvalue _  regexp_operator _ (string / regexp)
-//   <LogStash::Config::AST::RegexpExpression>
-// end
+/// Collects comments of a successfully parsed config.
+///
+/// Parsers skip comments as blanks, so they are picked up by a separate pass which only has to
+/// step over the tokens `#` can appear in, i.e. strings, regexes and selectors.
+fn scan_comments(lines: &Lines) -> Vec<Comment> {
+    // Finds the end of a token which starts at `start` and is terminated by `close`.
+    fn skip(conf: &[u8], start: usize, close: u8) -> usize {
+        let mut i = start + 1;
+        while i < conf.len() && conf[i] != close {
+            i += if conf[i] == b'\\' && conf.get(i + 1) == Some(&close) { 2 } else { 1 };
+        }
+        i + 1
+    }
 
-// rule regexp_operator
-//   ("=~" / "!~") <LogStash::Config::AST::RegExpOperator>
-// end
+    let conf = lines.conf;
+    let mut comments = vec![];
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < conf.len() {
+        match conf[i] {
+            b'"' | b'\'' | b'/' => i = skip(conf, i, conf[i]),
+            b'[' => {
+                let len = conf[i + 1..].iter().position(|c| b"[],\n".contains(c));
+                match len {
+                    Some(len) if conf[i + 1 + len] == b']' => i += len + 2,
+                    _ => {
+                        depth += 1;
+                        i += 1;
+                    }
+                }
+            }
+            b'{' => {
+                depth += 1;
+                i += 1;
+            }
+            b']' | b'}' => {
+                depth = depth.saturating_sub(1);
+                i += 1;
+            }
+            b'#' => {
+                let end = conf[i..].iter().position(|&c| c == b'\n').map_or(conf.len(), |p| p + i);
+                let (line, column) = lines.line_column(i);
+                comments.push(Comment {
+                    text: String::from_utf8_lossy(&conf[i + 1..end]).trim_end().to_string(),
+                    depth,
+                    location: Location { offset: i, line, column },
+                });
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+    comments
+}
 
-#[inline]
-pub fn parse(conf: &[u8]) -> Result<Config, String> {
-    match config(conf) {
-        IResult::Done(_, c) => Ok(c),
-        _ => {
-            // TODO: improve error report
-            Err("Config parsing failed".to_string())
+/// Remembers the position of the node being parsed.
+///
+/// Parsers see only the rest of the input, so the offset is counted from the end of the config
+/// here. It is turned into a real one by `resolve_locations` once the whole config is parsed.
+fn location(input: &[u8]) -> IResult<&[u8], Location> {
+    IResult::Done(input, Location { offset: input.len(), line: 0, column: 0 })
+}
+
+fn resolve_locations(config: &mut Config, lines: &Lines) {
+    fn resolve(location: &mut Location, lines: &Lines) {
+        location.offset = lines.conf.len() - location.offset;
+        let (line, column) = lines.line_column(location.offset);
+        location.line = line;
+        location.column = column;
+    }
+
+    fn resolve_value(value: &mut Value, lines: &Lines) {
+        match value {
+            Value::Plugin(p) => resolve_plugin(p, lines),
+            Value::Array(values) => {
+                for v in values {
+                    resolve_value(v, lines);
+                }
+            }
+            Value::Hash(entries) => {
+                for e in entries {
+                    resolve(&mut e.location, lines);
+                    resolve_value(&mut e.value, lines);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn resolve_plugin(plugin: &mut Plugin, lines: &Lines) {
+        resolve(&mut plugin.location, lines);
+        for a in &mut plugin.attributes {
+            resolve(&mut a.location, lines);
+            resolve_value(&mut a.value, lines);
+        }
+    }
+
+    fn resolve_block(block: &mut Block, lines: &Lines) {
+        for bp in block {
+            match bp {
+                BranchOrPlugin::Plugin(p) => resolve_plugin(p, lines),
+                BranchOrPlugin::Branch(b) => {
+                    resolve(&mut b.location, lines);
+                    for case in &mut b.cases {
+                        resolve(&mut case.location, lines);
+                        resolve_block(&mut case.block, lines);
+                    }
+                }
+            }
         }
     }
+
+    for section in &mut config.sections {
+        resolve(&mut section.location, lines);
+        resolve_block(&mut section.block, lines);
+    }
 }
 
-named!(
-/// Entry point to parse the configuration.
-,
-    config<Config>,
-    map!(many1!(delimited!(blank0, plugin_section, blank0)),
-        |sections| Config { sections: sections } )
+/// Checks whether the error is an unrecoverable one (i.e. produced by `cut!`).
+fn is_cut(err: &NomError<&[u8]>) -> bool {
+    matches!(*err, NomError::Position(ErrorKind::Custom(_), _))
+}
+
+/// Makes an error unrecoverable pointing it to `input`.
+///
+/// Already unrecoverable errors are passed through as is, so the most precise position wins.
+fn cut<'a>(err: NomError<&'a [u8]>, code: u32, input: &'a [u8]) -> NomError<&'a [u8]> {
+    if is_cut(&err) {
+        err
+    } else {
+        NomError::Position(ErrorKind::Custom(code), input)
+    }
+}
+
+/// Makes errors of the child parser unrecoverable.
+///
+/// Unrecoverable errors must not be swallowed by `alt!`, `many0!` and friends, so
+/// the parsers on the way from `config` to a `cut!` avoid those combinators.
+macro_rules! cut (
+    ($i:expr, $code:expr, $submac:ident!( $($args:tt)* )) => (
+        match $submac!($i, $($args)*) {
+            IResult::Error(e) => IResult::Error(cut(e, $code, $i)),
+            r => r,
+        }
+    );
+    ($i:expr, $code:expr, $f:expr) => (
+        cut!($i, $code, call!($f))
+    );
 );
 
+/// Parses `item`s until `end`. Blanks are allowed around the items.
+///
+/// E.g. statements of a block or attributes of a plugin.
+fn repeated<'a, O, F>(input: &'a [u8], item: F, end: &str, code: u32) -> IResult<&'a [u8], Vec<O>>
+    where F: Fn(&'a [u8]) -> IResult<&'a [u8], O>
+{
+    let mut items = vec![];
+    let mut input = input;
+    loop {
+        input = try_parse!(input, blank0).0;
+        if input.starts_with(end.as_bytes()) {
+            return IResult::Done(&input[end.len()..], items);
+        }
+
+        let (i, o) = try_parse!(input, cut!(code, item));
+        items.push(o);
+        input = i;
+    }
+}
+
+/// Parses `item`s separated by `sep` until `end`. Blanks are allowed around the items.
+///
+/// E.g. elements of an array.
+fn separated<'a, O, F>(input: &'a [u8],
+                       item: F,
+                       sep: &str,
+                       end: &str,
+                       item_code: u32,
+                       end_code: u32)
+                       -> IResult<&'a [u8], Vec<O>>
+    where F: Fn(&'a [u8]) -> IResult<&'a [u8], O>
+{
+    let mut items = vec![];
+    let mut input = try_parse!(input, blank0).0;
+    if input.starts_with(end.as_bytes()) {
+        return IResult::Done(&input[end.len()..], items);
+    }
+
+    loop {
+        let (i, o) = try_parse!(input, cut!(item_code, item));
+        items.push(o);
+
+        input = try_parse!(i, blank0).0;
+        if input.starts_with(end.as_bytes()) {
+            return IResult::Done(&input[end.len()..], items);
+        }
+        if !input.starts_with(sep.as_bytes()) {
+            return IResult::Error(NomError::Position(ErrorKind::Custom(end_code), input));
+        }
+        input = try_parse!(&input[sep.len()..], blank0).0;
+    }
+}
+
+/// Checks whether the input starts with the keyword (and not just with a name prefixed by it).
+fn keyword(input: &[u8], kw: &str) -> bool {
+    input.starts_with(kw.as_bytes()) &&
+    !matches!(input.get(kw.len()), Some(&c) if c.is_ascii_alphanumeric() || c == b'_' || c == b'-')
+}
+
+/// Entry point to parse the configuration.
+fn config(input: &[u8]) -> IResult<&[u8], Config> {
+    let mut sections = vec![];
+    let mut input = input;
+    loop {
+        input = try_parse!(input, blank0).0;
+        if input.is_empty() && !sections.is_empty() {
+            return IResult::Done(input, Config { sections, comments: vec![] });
+        }
+
+        let (i, section) = try_parse!(input, cut!(ERR_SECTION, plugin_section));
+        sections.push(section);
+        input = i;
+    }
+}
+
 named!(plugin_section<PluginSection>,
     do_parse!(
-        ptype: plugin_type >>
-        blank0             >>
-        block: block       >>
-        (PluginSection { plugin_type: ptype, block: block })
+        loc: location                        >>
+        ptype: plugin_type                   >>
+        blank0                               >>
+        block: cut!(ERR_BLOCK_START, block)  >>
+        (PluginSection { plugin_type: ptype, block, file: None, location: loc })
     )
 );
 
@@ -141,71 +428,178 @@ named!(
     block<Block>,
     do_parse!(
         tag!("{") >>
-        blank0    >>
-        bps: many0!(delimited!(blank0, branch_or_plugin, blank0)) >>
-        tag!("}") >>
+        bps: call!(repeated, branch_or_plugin, "}", ERR_BLOCK_END) >>
         (bps)
     )
 );
 
-named!(branch_or_plugin<BranchOrPlugin>,
-    alt!(
-        branch => { |b| BranchOrPlugin::Branch(b) }
-      | plugin => { |p| BranchOrPlugin::Plugin(p) }
+fn branch_or_plugin(input: &[u8]) -> IResult<&[u8], BranchOrPlugin> {
+    if keyword(input, "if") {
+        branch(input).map(BranchOrPlugin::Branch)
+    } else {
+        plugin(input).map(BranchOrPlugin::Plugin)
+    }
+}
+
+named!(
+/// Parses plugin with its attributes.
+///
+/// Logstash rule: `name _ "{" _ ( attribute (whitespace _ attribute)*)? _ "}"`.
+,
+    plugin<Plugin>,
+    do_parse!(
+        loc: location                    >>
+        name: name                       >>
+        blank0                           >>
+        cut!(ERR_BLOCK_START, tag!("{")) >>
+        attributes: call!(repeated, attribute, "}", ERR_PLUGIN_END) >>
+        (Plugin { name, attributes, location: loc })
     )
 );
 
-named!(plugin<Plugin>,
+named!(
+/// Recognizes the beginning of a plugin, i.e. `name {`.
+,
+    plugin_start,
+    recognize!(tuple!(name, blank0, tag!("{")))
+);
+
+named!(
+/// Parses plugin attribute.
+///
+/// E.g. `path => "/var/log/*.log"`.
+///
+/// Logstash rule: `name _ "=>" _ value`.
+,
+    attribute<Attribute>,
     do_parse!(
-        name: name >>
-        blank0     >>
-        tag!("{")  >>
-        blank0     >>
-// TODO: attributes:( attribute (whitespace _ attribute)*)?
-        blank0     >>
-        tag!("}")  >>
-        (Plugin { name: name })
+        loc: location                  >>
+        name: name                     >>
+        blank0                         >>
+        cut!(ERR_ARROW, tag!("=>"))    >>
+        blank0                         >>
+        value: cut!(ERR_VALUE, value)  >>
+        (Attribute { name, value, location: loc })
     )
 );
 
-named!(branch<Branch>,
+/// Parses attribute value.
+///
+/// Logstash rule: `plugin / bareword / string / number / array / hash`.
+fn value(input: &[u8]) -> IResult<&[u8], Value> {
+    match input.first() {
+        Some(&b'[') => array(input).map(Value::Array),
+        Some(&b'{') => hash(input).map(Value::Hash),
+        Some(&b'-') | Some(&(b'0'..=b'9')) => complete!(input, number).map(Value::Number),
+        _ if plugin_start(input).is_done() => plugin(input).map(Value::Plugin),
+        _ => {
+            alt!(input,
+                complete!(bareword) => { Value::Bareword }
+              | complete!(string)   => { Value::String   }
+            )
+        }
+    }
+}
+
+named!(
+/// Parses array value.
+///
+/// E.g. `["foo", 42, [bar, baz]]`.
+///
+/// Logstash rule: `"[" _ ( value (_ "," _ value)* )? _ "]"`.
+,
+    array<Vec<Value>>,
     do_parse!(
-        case_if:   case_if                                 >>
-        else_ifs:  many0!(preceded!(blank0, case_else_if)) >>
-        case_else: opt!(preceded!(blank0, case_else))      >>
-        (Branch::new(case_if, else_ifs, case_else))
+        tag!("[") >>
+        values: call!(separated, value, ",", "]", ERR_VALUE, ERR_ARRAY_END) >>
+        (values)
     )
 );
 
-named!(case_if<Case>,
+named!(
+/// Parses hash value.
+///
+/// E.g. `{ "message" => "%{COMBINEDAPACHELOG}" 200 => ok }`.
+///
+/// Logstash rule: `"{" _ hashentries? _ "}"`.
+,
+    hash<Vec<HashEntry>>,
     do_parse!(
-        tag!("if")   >>
-        blank0       >>
-        c: condition >>
-        blank0       >>
-        b: block     >>
-        (Case { condition: c, block: b })
+        tag!("{") >>
+        entries: call!(repeated, hash_entry, "}", ERR_HASH_END) >>
+        (entries)
     )
 );
 
-named!(case_else_if<Case>,
+named!(
+/// Parses hash entry.
+///
+/// Logstash rule: `name:(number / bareword / string) _ "=>" _ value`.
+,
+    hash_entry<HashEntry>,
     do_parse!(
-        tag!("else")  >>
-        blank0        >>
-        case: case_if >>
-        (case)
+        loc: location                  >>
+        key: hash_key                  >>
+        blank0                         >>
+        cut!(ERR_ARROW, tag!("=>"))    >>
+        blank0                         >>
+        value: cut!(ERR_VALUE, value)  >>
+        (HashEntry { key, value, location: loc })
+    )
+);
+
+named!(hash_key<String>,
+    alt!(
+        map_res!(recognize!(number), str::from_utf8) => { str::to_string }
+      | bareword
+      | string
     )
 );
 
-named!(case_else<Case>,
+/// Parses `if {...} else if {...} else {...}` statement.
+fn branch(input: &[u8]) -> IResult<&[u8], Branch> {
+    let (input, loc) = try_parse!(input, location);
+    let at = |mut branch: Branch| {
+        branch.location = loc;
+        branch
+    };
+
+    let (mut input, first) = try_parse!(input, case_if);
+    let mut else_ifs = vec![];
+    loop {
+        let i = try_parse!(input, blank0).0;
+        if !keyword(i, "else") {
+            return IResult::Done(input, at(Branch::new(first, else_ifs, None)));
+        }
+
+        let (i, else_loc) = try_parse!(i, location);
+        let i = try_parse!(&i["else".len()..], blank0).0;
+        if keyword(i, "if") {
+            let (i, mut case) = try_parse!(i, case_if);
+            case.location = else_loc;
+            else_ifs.push(case);
+            input = i;
+        } else {
+            let (i, b) = try_parse!(i, cut!(ERR_BLOCK_START, block));
+            let case_else = Case { condition: Condition::truth(), block: b, location: else_loc };
+            return IResult::Done(i, at(Branch::new(first, else_ifs, Some(case_else))));
+        }
+    }
+}
+
+named!(case_if<Case>,
     do_parse!(
-        tag!("else") >>
-        blank0       >>
-        b: block     >>
-        (Case { condition: Condition::truth(), block: b })
+        loc: location                        >>
+        tag!("if")                           >>
+        blank0                               >>
+        c: cut!(ERR_CONDITION, condition)    >>
+        blank0                               >>
+        b: cut!(ERR_BLOCK_START, block)      >>
+        (Case { condition: c, block: b, location: loc })
     )
 );
 
+
 named!(
 /// Parses compound conditions.
 ///
@@ -277,10 +671,9 @@ named!(
     alt!(
         complete!(parens_expr)
       | complete!(negative_expr)
-// TODO: in_expr
-// TODO: not_in_expr
+      | complete!(in_expr)
+      | complete!(match_expr)
       | complete!(compare_expr)
-// TODO: re_expr
       | complete!(rvalue_expr)
     )
 );
@@ -304,7 +697,11 @@ named!(
 );
 
 named!(
-/// Parses either `!<parenthesized expr>` or `!<selector expr>`.
+/// Parses `!` followed by a parenthesized expression, a selector or a method call.
+///
+/// E.g. `!([a] > 1)`, `![a]` or `!exists([a])`. Negated method calls are an extension.
+///
+/// Logstash rule: `"!" _ "(" _ condition _ ")" / "!" _ selector`.
 ,
     negative_expr<BoolExpr>,
     preceded!(
@@ -312,6 +709,7 @@ named!(
         alt!(
             parens_expr => { |expr: BoolExpr| expr.not() }
           | selector    => { |sel| BoolExpr::from(Rvalue::from(sel)).not() }
+          | method_call => { |call| BoolExpr::from(Rvalue::MethodCall(call)).not() }
         )
     )
 );
@@ -334,13 +732,76 @@ named!(
     )
 );
 
+named!(
+/// Parses a membership expression.
+///
+/// E.g. `"error" in [tags]` or `[status] not in [allowed_statuses]`.
+///
+/// Logstash rules: `rvalue _ in_operator _ rvalue` and `rvalue _ not_in_operator _ rvalue`.
+,
+    in_expr<BoolExpr>,
+    do_parse!(
+        lhs: rvalue     >>
+        blank0          >>
+        op: in_operator >>
+        blank0          >>
+        rhs: rvalue     >>
+        (BoolExpr::In(op, lhs, rhs))
+    )
+);
+
+named!(
+/// Logstash rules: `"in"` and `"not " _ "in"`.
+,
+    in_operator<InOperator>,
+    alt!(
+        tag!("in")                                => { |_| InOperator::In    }
+      | tuple!(tag!("not "), blank0, tag!("in")) => { |_| InOperator::NotIn }
+    )
+);
+
+named!(
+/// Parses a regular expression matching.
+///
+/// E.g. `[message] =~ /^ERROR/` or `[path] !~ "\.log$"`.
+///
+/// Logstash rule: `rvalue _ regexp_operator _ (string / regexp)`.
+,
+    match_expr<BoolExpr>,
+    do_parse!(
+        lhs: rvalue        >>
+        blank0             >>
+        op: match_operator >>
+        blank0             >>
+        rhs: alt!(
+            string => { Rvalue::String }
+          | regexp => { Rvalue::Regex  }
+        )                  >>
+        (BoolExpr::Match(op, lhs, rhs))
+    )
+);
+
+named!(
+/// Logstash rule: `"=~" / "!~"`.
+,
+    match_operator<MatchOperator>,
+    alt!(
+        tag!("=~") => { |_| MatchOperator::Match    }
+      | tag!("!~") => { |_| MatchOperator::NotMatch }
+    )
+);
+
 named!(
 /// Parses a (r)value which will be then converted to a `bool` value.
 ///
 /// Does it use `ruby`'s conversions rules?
 ,
     rvalue_expr<BoolExpr>,
-    map!(rvalue, |v| BoolExpr::Rvalue(v))
+    // Hashes are not allowed here, otherwise `if {` would be taken for a condition.
+    map_opt!(rvalue, |v| match v {
+        Rvalue::Hash(_) => None,
+        v => Some(BoolExpr::Rvalue(v)),
+    })
 );
 
 named!(bool_operator<BoolOperator>,
@@ -366,10 +827,67 @@ named!(compare_operator<CompareOperator>,
 // end
 named!(rvalue<Rvalue>,
     alt!(
-        number   => { |v| Rvalue::from(v) }
-      | string   => { |v| Rvalue::from(v) }
-      | selector => { |v| Rvalue::from(v) }
-// TODO: add remaining cases
+        number   => { Rvalue::Number   }
+      | string   => { Rvalue::String   }
+      | selector => { Rvalue::Selector }
+      | regexp   => { Rvalue::Regex    }
+      | rvalue_array
+      | rvalue_hash
+      | method_call => { Rvalue::MethodCall }
+    )
+);
+
+named!(
+/// Parses function calls.
+///
+/// E.g. `length([tags])` or `lowercase([level])`.
+///
+/// Logstash rule: `method _ "(" _ (rvalue (_ "," _ rvalue)*)? _ ")"`.
+,
+    method_call<MethodCall>,
+    do_parse!(
+        name: bareword >>
+        blank0         >>
+        tag!("(")      >>
+        args: call!(separated, rvalue, ",", ")", ERR_VALUE, ERR_ARGS_END) >>
+        (MethodCall::new(name, args))
+    )
+);
+
+named!(
+/// Parses array literals in conditions.
+///
+/// E.g. `[500, 502, 503]`. Note, that `[500]` is a selector rather than an array.
+,
+    rvalue_array<Rvalue>,
+    do_parse!(
+        tag!("[") >>
+        values: call!(separated, rvalue, ",", "]", ERR_VALUE, ERR_ARRAY_END) >>
+        (Rvalue::Array(values))
+    )
+);
+
+named!(
+/// Parses hash literals in conditions.
+///
+/// E.g. `{ "200" => "ok" "404" => [not, found] }`.
+,
+    rvalue_hash<Rvalue>,
+    do_parse!(
+        tag!("{") >>
+        entries: call!(repeated, rvalue_hash_entry, "}", ERR_HASH_END) >>
+        (Rvalue::Hash(entries))
+    )
+);
+
+named!(rvalue_hash_entry<(String, Rvalue)>,
+    do_parse!(
+        key: hash_key                  >>
+        blank0                         >>
+        cut!(ERR_ARROW, tag!("=>"))    >>
+        blank0                         >>
+        value: cut!(ERR_VALUE, rvalue) >>
+        (key, value)
     )
 );
 
@@ -389,7 +907,7 @@ fn parse_f64(minus: Option<&[u8]>, integer: &[u8], fractional: Option<&[u8]>) ->
     // Since this function is only for internal usage with the `number` parser
     // we assume that input data is always valid, so we can unwrap() fearlessly.
     let mut res = String::new();
-    if let Some(_) = minus {
+    if minus.is_some() {
         res.push('-');
     }
 
@@ -402,6 +920,38 @@ fn parse_f64(minus: Option<&[u8]>, integer: &[u8], fractional: Option<&[u8]>) ->
     res.parse().unwrap()
 }
 
+/// Parses regular expression literals. Escaped slashes are unescaped, other escape sequences
+/// are left as is for the regex engine.
+///
+/// Logstash rule: `'/' ( '\/' / !'/' . )* '/'`.
+fn regexp(input: &[u8]) -> IResult<&[u8], String> {
+    if input.first() != Some(&b'/') {
+        return IResult::Error(error_position!(ErrorKind::Tag, input));
+    }
+
+    let mut pattern = Vec::new();
+    let mut i = 1;
+    while i < input.len() {
+        match (input[i], input.get(i + 1)) {
+            (b'/', _) => {
+                return match String::from_utf8(pattern) {
+                    Ok(p) => IResult::Done(&input[i + 1..], p),
+                    Err(_) => IResult::Error(error_position!(ErrorKind::Custom(0), input)),
+                };
+            }
+            (b'\\', Some(&b'/')) => {
+                pattern.push(b'/');
+                i += 2;
+            }
+            (c, _) => {
+                pattern.push(c);
+                i += 1;
+            }
+        }
+    }
+    IResult::Incomplete(Needed::Unknown)
+}
+
 named!(
 /// Parses strings (double or single quoted).
 ,
@@ -462,11 +1012,32 @@ named!(name<String>,
     )
 );
 
+/// Parses barewords, i.e. unquoted words like `true` or `json_lines`.
+///
+/// Logstash rule: `[A-Za-z_] [A-Za-z0-9_]+`.
+fn bareword(input: &[u8]) -> IResult<&[u8], String> {
+    let is_head = |c: u8| c.is_ascii_alphabetic() || c == b'_';
+    let is_tail = |c: u8| c.is_ascii_alphanumeric() || c == b'_';
+
+    match input.first() {
+        Some(&c) if is_head(c) => {}
+        _ => return IResult::Error(error_position!(ErrorKind::AlphaNumeric, input)),
+    }
+
+    let len = 1 + input[1..].iter().take_while(|&&c| is_tail(c)).count();
+    if len < 2 {
+        return IResult::Error(error_position!(ErrorKind::AlphaNumeric, input));
+    }
+
+    // Only ASCII characters have been consumed, so the slice is always valid UTF-8.
+    IResult::Done(&input[len..], str::from_utf8(&input[..len]).unwrap().to_string())
+}
+
 named!(selector<Selector>,
     map!(
         many1!(
             map_res!(
-                delimited!(tag!("["), take_until_either!("],"), tag!("]")),
+                delimited!(tag!("["), take_until_either!("[],"), tag!("]")),
                 str::from_utf8
             )
         ),
@@ -482,7 +1053,7 @@ named!(
         many1!(
             preceded!(
                 opt!(multispace),
-                delimited!(tag!("#"), take_until!("\n"), tag!("\n"))
+                preceded!(tag!("#"), alt!(complete!(take_until_and_consume!("\n")) | rest))
             )
         ),
         |_| b""
@@ -501,41 +1072,234 @@ mod tests {
         let conf = include_bytes!("./tests/assets/simplest.conf");
         let expected = Config {
             sections: vec![
-                PluginSection {
-                    plugin_type: PluginType::Input,
-                    block: vec![
-                        BranchOrPlugin::Plugin(Plugin { name: "stdin".to_string() }),
-                        BranchOrPlugin::Plugin(Plugin { name: "file".to_string() })
-                    ]
-                },
-                PluginSection { plugin_type: PluginType::Filter, block: vec![] },
-                PluginSection { plugin_type: PluginType::Filter, block: vec![] },
-                PluginSection { plugin_type: PluginType::Filter, block: vec![] },
-                PluginSection { plugin_type: PluginType::Filter, block: vec![] },
-                PluginSection { plugin_type: PluginType::Filter, block: vec![] },
-                PluginSection {
-                    plugin_type: PluginType::Output,
-                    block: vec![
-                        BranchOrPlugin::Plugin(Plugin { name: "stdout".to_string() })
-                    ],
-                }
+                PluginSection::new(PluginType::Input, vec![
+                    BranchOrPlugin::Plugin(Plugin::new("stdin".to_string(), vec![])),
+                    BranchOrPlugin::Plugin(Plugin::new("file".to_string(), vec![]))
+                ]),
+                PluginSection::new(PluginType::Filter, vec![]),
+                PluginSection::new(PluginType::Filter, vec![]),
+                PluginSection::new(PluginType::Filter, vec![]),
+                PluginSection::new(PluginType::Filter, vec![]),
+                PluginSection::new(PluginType::Filter, vec![]),
+                PluginSection::new(PluginType::Output, vec![
+                    BranchOrPlugin::Plugin(Plugin::new("stdout".to_string(), vec![]))
+                ])
             ],
+            comments: vec![],
         };
 
         assert_eq!(IResult::Done(&b""[..], expected), config(conf));
     }
 
+    #[test]
+    fn test_parse_ok() {
+        let conf = b"filter {\n  if [a] { drop {} } else if [b] { drop {} } else { }\n}\n# EOF";
+        let config = parse(conf).unwrap();
+        match config.sections[0].block[0] {
+            BranchOrPlugin::Branch(ref b) => assert_eq!(3, b.cases.len()),
+            _ => panic!("branch expected"),
+        }
+    }
+
+    #[test]
+    fn test_parse_locations() {
+        let conf = "input {\n  stdin {}\n}\nfilter {\n  if [a] {\n    drop {}\n  }\n}\n\
+                    output { stdout { id => \"☃\" codec => json { } } }";
+        let config = parse(conf.as_bytes()).unwrap();
+
+        let location = |bp: &BranchOrPlugin| match bp {
+            BranchOrPlugin::Plugin(p) => (p.location.line, p.location.column),
+            BranchOrPlugin::Branch(b) => (b.location.line, b.location.column),
+        };
+        assert_eq!((2, 3), location(&config.sections[0].block[0]));
+        assert_eq!((5, 3), location(&config.sections[1].block[0]));
+        match config.sections[1].block[0] {
+            BranchOrPlugin::Branch(ref b) => assert_eq!((6, 5), location(&b.cases[0].block[0])),
+            _ => panic!("branch expected"),
+        }
+        match config.sections[2].block[0] {
+            BranchOrPlugin::Plugin(ref p) => {
+                assert_eq!((68, 9, 10), (p.location.offset, p.location.line, p.location.column));
+                match p.attributes[1].value {
+                    Value::Plugin(ref codec) => assert_eq!(38, codec.location.column),
+                    _ => panic!("plugin expected"),
+                }
+            }
+            _ => panic!("plugin expected"),
+        }
+    }
+
+    #[test]
+    fn test_line_column() {
+        let lines = Lines::new("ab\n\nсd\n".as_bytes());
+        assert_eq!((1, 1), lines.line_column(0));
+        assert_eq!((1, 3), lines.line_column(2));
+        assert_eq!((2, 1), lines.line_column(3));
+        assert_eq!((3, 2), lines.line_column(6));
+        assert_eq!((4, 1), lines.line_column(8));
+    }
+
+    #[test]
+    fn test_parse_collects_comments() {
+        let conf = "# head  \ninput {\n  stdin { tags => ['#1', \"#2\"] } # tail\n}\n\
+                    filter { if [#a] =~ /#b/ { drop { # d\n} } }\n#";
+        let config = parse(conf.as_bytes()).unwrap();
+        let comments: Vec<_> = config.comments
+            .iter()
+            .map(|c| (c.text.as_str(), c.depth, c.location.line, c.location.column))
+            .collect();
+        assert_eq!(vec![(" head", 0, 1, 1), (" tail", 1, 3, 34), (" d", 3, 5, 35), ("", 0, 7, 1)],
+                   comments);
+        assert_eq!((2, 1), (config.sections[0].location.line, config.sections[0].location.column));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases: Vec<(&str, usize, usize, Vec<&str>)> = vec![
+            ("", 1, 1, vec![]),
+            ("inptu {}", 1, 1, vec!["`input`", "`filter`", "`output`"]),
+            ("input {}\nfilter\n  [", 3, 3, vec!["`{`"]),
+            ("input {\n    stdin {}\n    )\n}", 3, 5, vec!["`}`", "plugin name"]),
+            ("input {\n    stdin }", 2, 11, vec!["`{`"]),
+            ("input {\n  file { path \"a\" }\n}", 2, 15, vec!["`=>`"]),
+            ("input {\n  file { path => }\n}", 2, 18, vec!["value"]),
+            ("input {\n  file { path => [\"a\" \"b\"] }\n}", 2, 23, vec!["`,`", "`]`"]),
+            ("input {\n  file { path => [\"a\", ] }\n}", 2, 24, vec!["value"]),
+            ("input {\n  file { codec => json { charset => } }\n}", 2, 37, vec!["value"]),
+            ("output { x { y => { 'a' => 1 ! } } }", 1, 30, vec!["`}`", "hash key"]),
+            ("output { x { 'y' => 1 'z' } }", 1, 27, vec!["`=>`"]),
+            ("output { x { y => 1 @ } }", 1, 21, vec!["`}`", "attribute name"]),
+            ("filter { if { } }", 1, 13, vec!["condition"]),
+            ("filter { if [a] > 1 { } else if [b] { x {} } else ) }", 1, 51, vec!["`{`"]),
+            ("filter {\n\tif [a] {\n\t\tx { y }\n\t}\n}", 3, 9, vec!["`=>`"]),
+            ("filter { x {", 1, 13, vec![]),
+        ];
+
+        for (conf, line, column, expected) in cases {
+            let err = parse(conf.as_bytes()).unwrap_err();
+            assert_eq!((line, column, expected), (err.line, err.column, err.expected), "{}", conf);
+        }
+    }
+
+    #[test]
+    fn test_parse_error_report() {
+        let conf = b"input {\n    stdin {}\n\n    )\n}";
+        let err = parse(conf).unwrap_err().in_file("10-inputs.conf");
+        assert_eq!("expected `}` or plugin name\n \
+                    --> 10-inputs.conf:4:5\n  \
+                    |\n\
+                    2 |     stdin {}\n\
+                    3 | \n\
+                    4 |     )\n  \
+                    |     ^",
+                   err.to_string());
+
+        let err = parse(b"filter {\n\tif [a] {\n\t\tx { y }\n\t}\n}").unwrap_err();
+        assert_eq!("expected `=>`\n \
+                    --> config:3:9\n  \
+                    |\n\
+                    1 | filter {\n\
+                    2 | \tif [a] {\n\
+                    3 | \t\tx { y }\n  \
+                    | \t\t      ^",
+                   err.to_string());
+
+        let err = parse(b"filter {").unwrap_err();
+        assert_eq!("unexpected end of file", err.message());
+    }
+
     #[test]
     fn test_plugin() {
         let config = &b"stdin {}"[..];
-        assert_eq!(IResult::Done(&b""[..], Plugin { name: "stdin".to_string() }),
+        assert_eq!(IResult::Done(&b""[..], Plugin::new("stdin".to_string(), vec![])),
                    plugin(config));
 
         let config = &b"file {\n\n    \n}"[..];
-        assert_eq!(IResult::Done(&b""[..], Plugin { name: "file".to_string() }),
+        assert_eq!(IResult::Done(&b""[..], Plugin::new("file".to_string(), vec![])),
                    plugin(config));
     }
 
+    #[test]
+    fn test_config_attributes() {
+        let conf = include_bytes!("./tests/assets/attributes.conf");
+        let file = Plugin::new("file".to_string(), vec![
+            Attribute::new("path".to_string(),
+                           Value::Array(vec![Value::from("/var/log/*.log"),
+                                             Value::from("/var/log/messages")])),
+            Attribute::new("start_position".to_string(),
+                           Value::Bareword("beginning".to_string())),
+            Attribute::new("sincedb_path".to_string(), Value::from("/dev/null")),
+            Attribute::new("codec".to_string(), Value::from(Plugin::new("json".to_string(), vec![
+                Attribute::new("charset".to_string(), Value::from("UTF-8")),
+            ]))),
+        ]);
+        let beats = Plugin::new("beats".to_string(), vec![
+            Attribute::new("port".to_string(), Value::from(5044.0)),
+        ]);
+        let grok = Plugin::new("grok".to_string(), vec![
+            Attribute::new("match".to_string(), Value::Hash(vec![
+                HashEntry::new("message".to_string(), Value::from("%{COMBINEDAPACHELOG}")),
+                HashEntry::new("200".to_string(), Value::Array(vec![
+                    Value::Bareword("ok".to_string()),
+                    Value::from("OK"),
+                ])),
+            ])),
+            Attribute::new("add_tag".to_string(), Value::Array(vec![])),
+        ]);
+        let stdout = Plugin::new("stdout".to_string(), vec![
+            Attribute::new("codec".to_string(), Value::Bareword("rubydebug".to_string())),
+        ]);
+        let expected = Config {
+            sections: vec![
+                PluginSection::new(PluginType::Input,
+                                   vec![BranchOrPlugin::Plugin(file),
+                                        BranchOrPlugin::Plugin(beats)]),
+                PluginSection::new(PluginType::Filter, vec![BranchOrPlugin::Plugin(grok)]),
+                PluginSection::new(PluginType::Output, vec![BranchOrPlugin::Plugin(stdout)]),
+            ],
+            comments: vec![],
+        };
+
+        assert_eq!(IResult::Done(&b""[..], expected), config(conf));
+    }
+
+    #[test]
+    fn test_attribute() {
+        for config in &["path => '/tmp/a.in'", "path=>\"/tmp/a.in\"", "path\n  =>\n  '/tmp/a.in'"] {
+            let expected = Attribute::new("path".to_string(), Value::from("/tmp/a.in"));
+            assert_eq!(IResult::Done(&b""[..], expected), attribute(config.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn test_value() {
+        assert_eq!(IResult::Done(&b""[..], Value::from(-1.5)), value(&b"-1.5"[..]));
+        assert_eq!(IResult::Done(&b""[..], Value::from("foo bar")), value(&b"'foo bar'"[..]));
+        assert_eq!(IResult::Done(&b"}"[..], Value::Bareword("true".to_string())),
+                   value(&b"true}"[..]));
+        assert_eq!(IResult::Done(&b""[..], Value::from(Plugin::new("plain".to_string(), vec![]))),
+                   value(&b"plain {}"[..]));
+
+        let nested = Value::Array(vec![Value::from(1.0),
+                                       Value::Array(vec![]),
+                                       Value::Hash(vec![HashEntry::new(
+                                           "foo".to_string(),
+                                           Value::Array(vec![Value::from("bar")]),
+                                       )])]);
+        assert_eq!(IResult::Done(&b""[..], nested),
+                   value(&b"[ 1 , [],{ foo => [ 'bar' ] } ]"[..]));
+    }
+
+    #[test]
+    fn test_bareword() {
+        assert_eq!(IResult::Done(&b" foo"[..], "json_lines".to_string()),
+                   bareword(&b"json_lines foo"[..]));
+        assert_eq!(IResult::Done(&b""[..], "_x1".to_string()), bareword(&b"_x1"[..]));
+        assert!(bareword(&b"1abc"[..]).is_err());
+        assert!(bareword(&b"a"[..]).is_err());
+        assert!(bareword(&b"'abc'"[..]).is_err());
+    }
+
     #[test]
     fn test_rvalue() {
         assert_eq!(IResult::Done(&b""[..], Rvalue::from(123.0)),
@@ -552,7 +1316,29 @@ mod tests {
         assert_eq!(IResult::Done(&b""[..], Rvalue::from(sel)),
                    rvalue(&b"[foo][bar]"[..]));
 
-        // TODO: array, method_call, regexp
+        let array = Rvalue::Array(vec![Rvalue::from(500.0),
+                                       Rvalue::from(Selector::from("code")),
+                                       Rvalue::Array(vec![])]);
+        assert_eq!(IResult::Done(&b""[..], array), rvalue(&b"[500, [code], []]"[..]));
+
+        let hash = Rvalue::Hash(vec![
+            ("200".to_string(), Rvalue::from("ok")),
+            ("codes".to_string(), Rvalue::from(vec![Rvalue::from(1.0), Rvalue::from(2.0)])),
+            ("empty".to_string(), Rvalue::Hash(vec![])),
+        ]);
+        let conf = "{ 200 => 'ok' # comment\n  codes => [ 1 , # one\n 2 # two\n ]\n\
+                    \"empty\"=>{}\n}";
+        assert_eq!(IResult::Done(&b""[..], hash), rvalue(conf.as_bytes()));
+
+        // A single element in brackets is a selector, not an array.
+        assert_eq!(IResult::Done(&b""[..], Rvalue::from(Selector::from("500"))),
+                   rvalue(&b"[500]"[..]));
+
+        let call = MethodCall::new("length".to_string(),
+                                   vec![Rvalue::from(Selector::from("tags")),
+                                        Rvalue::from(MethodCall::new("now".to_string(), vec![]))]);
+        assert_eq!(IResult::Done(&b""[..], Rvalue::from(call)),
+                   rvalue(&b"length ( [tags],now() )"[..]));
     }
 
     #[test]
@@ -649,6 +1435,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_bool_expr_in() {
+        let tags = || Rvalue::from(Selector::from("tags"));
+        let expr = |op| BoolExpr::In(op, Rvalue::from("error"), tags());
+        for &(conf, op) in &[("'error' in [tags]", InOperator::In),
+                             ("'error'in[tags]", InOperator::In),
+                             ("'error' not in [tags]", InOperator::NotIn),
+                             ("'error'\nnot \n  in [tags]", InOperator::NotIn)] {
+            assert_eq!(IResult::Done(&b""[..], expr(op)), bool_expr(conf.as_bytes()));
+        }
+
+        let codes = vec![Rvalue::from(500.0), Rvalue::from(502.0), Rvalue::from(503.0)];
+        assert_eq!(IResult::Done(&b""[..], BoolExpr::In(InOperator::In,
+                                                        Rvalue::from(Selector::from("code")),
+                                                        Rvalue::from(codes))),
+                   bool_expr(&b"[code] in [500, 502,\n 503 ]"[..]));
+
+        let call = |name: &str, arg| Rvalue::from(MethodCall::new(name.to_string(), vec![arg]));
+        assert_eq!(IResult::Done(&b""[..], BoolExpr::In(InOperator::In,
+                                                        Rvalue::from("warn"),
+                                                        call("lowercase", tags()))),
+                   bool_expr(&b"'warn' in lowercase([tags])"[..]));
+
+        // `not` must be followed by a space.
+        assert!(parse(b"filter { if 'a' notin [b] {} }").is_err());
+    }
+
+    #[test]
+    fn test_regexp() {
+        assert_eq!(IResult::Done(&b" x"[..], "^a.*".to_string()), regexp(&b"/^a.*/ x"[..]));
+        assert_eq!(IResult::Done(&b""[..], r"a/b\d".to_string()), regexp(&br"/a\/b\d/"[..]));
+        assert_eq!(IResult::Done(&b""[..], "".to_string()), regexp(&b"//"[..]));
+        assert_eq!(IResult::Incomplete(Needed::Unknown), regexp(&b"/abc"[..]));
+        assert!(regexp(&b"abc/"[..]).is_err());
+    }
+
+    #[test]
+    fn test_bool_expr_match() {
+        let sel = || Rvalue::from(Selector::from("message"));
+        let cases = vec![
+            ("[message] =~ /^ERROR/",
+             BoolExpr::Match(MatchOperator::Match, sel(), Rvalue::Regex("^ERROR".to_string()))),
+            ("[message]!~'^ERROR'",
+             BoolExpr::Match(MatchOperator::NotMatch, sel(), Rvalue::from("^ERROR"))),
+        ];
+        for (conf, expr) in cases {
+            assert_eq!(IResult::Done(&b""[..], expr), bool_expr(conf.as_bytes()));
+        }
+
+        // Only strings and regexes can be matched against.
+        assert!(parse(b"filter { if [message] =~ [pattern] {} }").is_err());
+    }
+
     #[test]
     fn test_bool_expr_parens() {
         // TODO: add test cases.
@@ -685,7 +1524,8 @@ mod tests {
         }
 
         assert_eq!(IResult::Done(&b"abc"[..], -0.123), number(&b"-0.123abc"[..]));
-        assert_eq!(IResult::Error(ErrorKind::TakeWhile1), number(&b"+1"[..]));
+        assert_eq!(IResult::Error(error_position!(ErrorKind::TakeWhile1, &b"+1"[..])),
+                   number(&b"+1"[..]));
     }
 
     #[test]
@@ -0,0 +1,476 @@
+//! Pretty-printer producing configs in the canonical form.
+//!
+//! Blocks are indented with two spaces, `=>` of attributes and hash entries are aligned and all
+//! strings are double quoted. Comments are put back into the blocks they were in, next to the
+//! nodes they precede, so `parse(print(config))` gives the same config, comments included.
+
+use std::fmt;
+
+use super::ast::*;
+
+const INDENT: &str = "  ";
+
+/// Prints the config in the canonical form.
+pub fn print(config: &Config) -> String {
+    let mut printer = Printer { out: String::new(), comments: &config.comments, indent: 0 };
+    for (i, section) in config.sections.iter().enumerate() {
+        if i > 0 {
+            printer.out.push('\n');
+        }
+        let end = config.sections.get(i + 1).map_or(usize::MAX, |s| s.location.offset);
+        printer.leading(section.location.offset);
+        printer.line(&format!("{} ", section.plugin_type));
+        printer.block(&section.block, &section.location, end);
+    }
+    printer.leading(usize::MAX);
+    printer.out
+}
+
+struct Printer<'a> {
+    out: String,
+    /// Comments which are not printed yet.
+    comments: &'a [Comment],
+    indent: usize,
+}
+
+impl<'a> Printer<'a> {
+    /// Starts a new line.
+    fn line(&mut self, s: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(s);
+    }
+
+    /// Ends the current line, the one the node at `location` starts on. A comment following
+    /// the node on the same line (and before the next node at `end`) is kept there unless it is
+    /// inside of a nested block.
+    fn end_line(&mut self, location: &Location, end: usize) {
+        if let Some(c) = self.comments.first() {
+            if location.is_known() && c.location.line == location.line &&
+               c.location.offset < end && c.depth == self.indent {
+                self.out.push_str(&format!(" #{}", c.text));
+                self.comments = &self.comments[1..];
+            }
+        }
+        self.out.push('\n');
+    }
+
+    fn comment(&mut self) {
+        let text = format!("#{}", self.comments[0].text);
+        self.comments = &self.comments[1..];
+        self.line(&text);
+        self.out.push('\n');
+    }
+
+    /// Prints comments preceding the node at `offset`.
+    fn leading(&mut self, offset: usize) {
+        while self.comments.first().is_some_and(|c| c.location.offset < offset) {
+            self.comment();
+        }
+    }
+
+    /// Tells whether the next comment is before `end` and inside of a block (hash, array) the
+    /// contents of which are at `depth`.
+    fn has_inner(&self, depth: usize, end: usize) -> bool {
+        self.comments.first().is_some_and(|c| c.location.offset < end && c.depth >= depth)
+    }
+
+    /// Prints comments left at the end of the block being printed, which ends before `end`.
+    fn trailing(&mut self, end: usize) {
+        while self.has_inner(self.indent, end) {
+            self.comment();
+        }
+    }
+
+    /// Opens a block, hash or array with `open`, prints its contents with `f` and closes it.
+    fn nested<F: FnOnce(&mut Self)>(&mut self, open: char, close: char, end: usize, f: F) {
+        self.out.push(open);
+        self.out.push('\n');
+        self.indent += 1;
+        f(self);
+        self.trailing(end);
+        self.indent -= 1;
+        self.line(&close.to_string());
+        self.out.push('\n');
+    }
+
+    /// Prints `{ ... }` of a section or a case. `location` and `end` are of the node it
+    /// belongs to.
+    fn block(&mut self, block: &Block, location: &Location, end: usize) {
+        if block.is_empty() && !self.has_inner(self.indent + 1, end) {
+            self.out.push_str("{}");
+            self.end_line(location, end);
+            return;
+        }
+
+        self.nested('{', '}', end, |p| {
+            for (i, statement) in block.iter().enumerate() {
+                let next = block.get(i + 1).map_or(end, |s| statement_location(s).offset);
+                match statement {
+                    BranchOrPlugin::Branch(b) => p.branch(b, next),
+                    BranchOrPlugin::Plugin(plugin) => {
+                        p.leading(plugin.location.offset);
+                        p.line("");
+                        p.plugin(plugin, &plugin.location, next);
+                    }
+                }
+            }
+        });
+    }
+
+    fn branch(&mut self, branch: &Branch, end: usize) {
+        let last = branch.cases.len() - 1;
+        for (i, case) in branch.cases.iter().enumerate() {
+            let next = branch.cases.get(i + 1).map_or(end, |c| c.location.offset);
+            if i == 0 {
+                self.leading(case.location.offset);
+                self.line("if ");
+            } else {
+                if self.comments.first().is_some_and(|c| c.location.offset < case.location.offset) {
+                    self.out.push('\n');
+                    self.leading(case.location.offset);
+                    self.line("");
+                } else {
+                    self.out.push(' ');
+                }
+                self.out.push_str("else ");
+                if i == last && case.condition == Condition::truth() {
+                    self.block(&case.block, &case.location, next);
+                    return;
+                }
+                self.out.push_str("if ");
+            }
+            self.out.push_str(&format!("{} ", case.condition));
+            self.block(&case.block, &case.location, next);
+            if i < last {
+                // `else` goes on the same line as `}`.
+                self.out.pop();
+            }
+        }
+    }
+
+    /// Prints a plugin starting from its name. `location` is of the statement the plugin
+    /// belongs to, i.e. the plugin itself or an attribute with a codec.
+    fn plugin(&mut self, plugin: &Plugin, location: &Location, end: usize) {
+        self.out.push_str(&format!("{} ", name(&plugin.name)));
+        if plugin.attributes.is_empty() && !self.has_inner(self.indent + 1, end) {
+            self.out.push_str("{}");
+            self.end_line(location, end);
+            return;
+        }
+
+        let width = plugin.attributes.iter().map(|a| name(&a.name).chars().count()).max();
+        let width = width.unwrap_or(0);
+        self.nested('{', '}', end, |p| {
+            for (i, attribute) in plugin.attributes.iter().enumerate() {
+                let next = plugin.attributes.get(i + 1).map_or(end, |a| a.location.offset);
+                p.leading(attribute.location.offset);
+                p.line(&format!("{:width$} => ", name(&attribute.name), width = width));
+                p.value(&attribute.value, &attribute.location, next);
+            }
+        });
+    }
+
+    /// Prints an attribute value along with the rest of the line.
+    fn value(&mut self, value: &Value, location: &Location, end: usize) {
+        match value {
+            Value::Plugin(p) => self.plugin(p, location, end),
+            Value::Hash(entries) if !entries.is_empty() => {
+                let width = entries.iter().map(|e| key(&e.key).chars().count()).max();
+                let width = width.unwrap_or(0);
+                self.nested('{', '}', end, |p| {
+                    for (i, entry) in entries.iter().enumerate() {
+                        let next = entries.get(i + 1).map_or(end, |e| e.location.offset);
+                        p.leading(entry.location.offset);
+                        p.line(&format!("{:width$} => ", key(&entry.key), width = width));
+                        p.value(&entry.value, &entry.location, next);
+                    }
+                });
+            }
+            // Elements have no locations, so comments inside of an array are kept at its end.
+            Value::Array(values) if self.has_inner(self.indent + 1, end) => {
+                self.nested('[', ']', end, |p| {
+                    for (i, v) in values.iter().enumerate() {
+                        let comma = if i + 1 < values.len() { "," } else { "" };
+                        p.line(&format!("{}{}", inline(v), comma));
+                        p.out.push('\n');
+                    }
+                });
+            }
+            v => {
+                self.out.push_str(&inline(v));
+                self.end_line(location, end);
+            }
+        }
+    }
+}
+
+fn statement_location(statement: &BranchOrPlugin) -> &Location {
+    match statement {
+        BranchOrPlugin::Branch(b) => &b.location,
+        BranchOrPlugin::Plugin(p) => &p.location,
+    }
+}
+
+/// Formats values on one line, hashes and plugins included.
+fn inline(value: &Value) -> String {
+    match value {
+        Value::Bareword(s) => s.clone(),
+        Value::String(s) => quote(s),
+        Value::Number(n) => n.to_string(),
+        Value::Array(values) => {
+            let values: Vec<_> = values.iter().map(inline).collect();
+            format!("[{}]", values.join(", "))
+        }
+        Value::Hash(entries) => {
+            let entries = entries
+                .iter()
+                .map(|e| format!("{} => {}", key(&e.key), inline(&e.value)));
+            inline_block(entries.collect())
+        }
+        Value::Plugin(p) => {
+            let attributes = p.attributes
+                .iter()
+                .map(|a| format!("{} => {}", name(&a.name), inline(&a.value)));
+            format!("{} {}", name(&p.name), inline_block(attributes.collect()))
+        }
+    }
+}
+
+/// Formats `{ ... }` with the given entries on one line.
+fn inline_block(entries: Vec<String>) -> String {
+    if entries.is_empty() {
+        "{}".to_string()
+    } else {
+        format!("{{ {} }}", entries.join(" "))
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\\\""))
+}
+
+/// Formats plugin and attribute names, quoting them if necessary.
+fn name(s: &str) -> String {
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    if !s.is_empty() && s.chars().all(valid) && s != "if" && s != "else" {
+        s.to_string()
+    } else {
+        quote(s)
+    }
+}
+
+/// Formats hash keys, which are kept bare if they are barewords or numbers.
+fn key(s: &str) -> String {
+    let bareword = s.len() > 1 &&
+                   s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') &&
+                   s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    let (integer, fractional) = digits.split_once('.').unwrap_or((digits, ""));
+    let number = !integer.is_empty() && integer.chars().all(|c| c.is_ascii_digit()) &&
+                 fractional.chars().all(|c| c.is_ascii_digit());
+    if bareword || number {
+        s.to_string()
+    } else {
+        quote(s)
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for e in &self.elements {
+            write!(f, "[{}]", e)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Rvalue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rvalue::Number(n) => write!(f, "{}", n),
+            Rvalue::String(s) => write!(f, "{}", quote(s)),
+            Rvalue::Selector(s) => write!(f, "{}", s),
+            Rvalue::Regex(r) => write!(f, "/{}/", r.replace('/', "\\/")),
+            Rvalue::Array(values) => {
+                let values: Vec<_> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "[{}]", values.join(", "))
+            }
+            Rvalue::Hash(entries) if entries.is_empty() => write!(f, "{{}}"),
+            Rvalue::Hash(entries) => {
+                write!(f, "{{")?;
+                for (k, v) in entries {
+                    write!(f, " {} => {}", key(k), v)?;
+                }
+                write!(f, " }}")
+            }
+            Rvalue::MethodCall(call) => {
+                let args: Vec<_> = call.args.iter().map(|v| v.to_string()).collect();
+                write!(f, "{}({})", call.name, args.join(", "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for BoolExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoolExpr::Parens(c) => write!(f, "({})", c),
+            BoolExpr::Negative(e) => {
+                match **e {
+                    BoolExpr::Parens(_) |
+                    BoolExpr::Rvalue(Rvalue::Selector(_)) |
+                    BoolExpr::Rvalue(Rvalue::MethodCall(_)) => write!(f, "!{}", e),
+                    _ => write!(f, "!({})", e),
+                }
+            }
+            BoolExpr::Compare(op, lhs, rhs) => write!(f, "{} {} {}", lhs, op.to_string(), rhs),
+            BoolExpr::In(op, lhs, rhs) => write!(f, "{} {} {}", lhs, op.to_string(), rhs),
+            BoolExpr::Match(op, lhs, rhs) => write!(f, "{} {} {}", lhs, op.to_string(), rhs),
+            BoolExpr::Rvalue(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    /// Parenthesizes operands only where the precedence requires it, so parsed conditions are
+    /// printed without extra parentheses.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Condition::Leaf(e) => write!(f, "{}", e),
+            Condition::Branch(op, lhs, rhs) => {
+                let op_name = match op {
+                    BoolOperator::And => "and",
+                    BoolOperator::Or => "or",
+                };
+                let precedence = |c: &Condition| match c {
+                    Condition::Branch(op, ..) => op.precedence(),
+                    Condition::Leaf(_) => i32::MAX,
+                };
+                if precedence(lhs) < op.precedence() {
+                    write!(f, "({})", lhs)?;
+                } else {
+                    write!(f, "{}", lhs)?;
+                }
+                if precedence(rhs) <= op.precedence() {
+                    write!(f, " {} ({})", op_name, rhs)
+                } else {
+                    write!(f, " {} {}", op_name, rhs)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::parse::parse;
+    use super::*;
+
+    fn assets() -> Vec<&'static [u8]> {
+        vec![
+            &include_bytes!("./tests/assets/simplest.conf")[..],
+            &include_bytes!("./tests/assets/attributes.conf")[..],
+            &include_bytes!("./tests/assets/comments.conf")[..],
+        ]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let shapes: Vec<&[u8]> = vec![
+            b"filter { mutate { add_field => [{ ab => 1 }, {}] } }",
+            b"filter { ab { a => [json { charset => 'x' }, plain {}, [{ bb => [1] }]] } }",
+        ];
+        for conf in assets().into_iter().chain(shapes) {
+            let config = parse(conf).unwrap();
+            let printed = print(&config);
+            assert_eq!(config, parse(printed.as_bytes()).unwrap(), "{}", printed);
+            assert_eq!(printed, print(&parse(printed.as_bytes()).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_print() {
+        let conf = br#"
+# Inputs
+input { stdin {} file { path=>['/var/log/*.log',"a"] start_position => beginning # tail
+  codec => json { charset => 'UTF-8' } } }
+filter {
+  if [type]=="nginx" and ([status] >= 500 or "error" in [tags]) { # server errors
+    grok { match => { 'message' => "%{IP:client} \"%{WORD}\"" 200 => ok }
+      # overwrite it
+    }
+  }
+  # fallback
+  else if ![tags] and length([message]) > 1000 or [path] =~ /\/tmp\// {drop{}}
+  else {}
+}
+output { stdout { codec => rubydebug } }
+# the end
+"#;
+        let expected = r#"# Inputs
+input {
+  stdin {}
+  file {
+    path           => ["/var/log/*.log", "a"]
+    start_position => beginning # tail
+    codec          => json {
+      charset => "UTF-8"
+    }
+  }
+}
+
+filter {
+  if [type] == "nginx" and ([status] >= 500 or "error" in [tags]) {
+    # server errors
+    grok {
+      match => {
+        message => "%{IP:client} \"%{WORD}\""
+        200     => ok
+      }
+      # overwrite it
+    }
+  }
+  # fallback
+  else if ![tags] and length([message]) > 1000 or [path] =~ /\/tmp\// {
+    drop {}
+  } else {}
+}
+
+output {
+  stdout {
+    codec => rubydebug
+  }
+}
+# the end
+"#;
+        let config = parse(conf).unwrap();
+        assert_eq!(expected, print(&config));
+        assert_eq!(config, parse(expected.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_print_comment_scopes() {
+        let cases = vec![
+            ("filter { drop {\n # inner\n } }",
+             "filter {\n  drop {\n    # inner\n  }\n}\n"),
+            ("filter { ab { cd => 1 }\n# between\n}",
+             "filter {\n  ab {\n    cd => 1\n  }\n  # between\n}\n"),
+            ("filter { ab { cd => 1 } # after\n}",
+             "filter {\n  ab {\n    cd => 1\n  }\n  # after\n}\n"),
+            ("filter { ab { cd => [1, # one\n 2] ef => 3 } }",
+             "filter {\n  ab {\n    cd => [\n      1,\n      2\n      # one\n    ]\n    \
+              ef => 3\n  }\n}\n"),
+            ("filter { ab { cd => { aa => 0 bb => 1 # c\n } } }",
+             "filter {\n  ab {\n    cd => {\n      aa => 0\n      bb => 1 # c\n    }\n  }\n}\n"),
+            ("filter { if [a] { drop {} # c\n } else { # d\n } }",
+             "filter {\n  if [a] {\n    drop {} # c\n  } else {\n    # d\n  }\n}\n"),
+        ];
+        for (conf, expected) in cases {
+            let config = parse(conf.as_bytes()).unwrap();
+            assert_eq!(expected, print(&config));
+            assert_eq!(config, parse(expected.as_bytes()).unwrap());
+        }
+    }
+}
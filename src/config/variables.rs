@@ -0,0 +1,199 @@
+//! Substitution of `${VAR}` and `${VAR:default}` references in attribute values.
+//!
+//! Values come from the keystore first and from the environment then, so secrets never have
+//! to be put into config files.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+
+/// Local store of secrets, a file of `NAME=value` lines.
+///
+/// Empty lines and lines starting with `#` are ignored. The file is expected to be readable
+/// by its owner only.
+#[derive(Clone, Default, PartialEq)]
+pub struct Keystore {
+    secrets: HashMap<String, String>,
+}
+
+impl Keystore {
+    pub fn new() -> Keystore {
+        Keystore { secrets: HashMap::new() }
+    }
+
+    pub fn load(path: &str) -> io::Result<Keystore> {
+        let content = fs::read_to_string(path)?;
+        warn_if_shared(path);
+
+        let mut keystore = Keystore::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('=') {
+                Some((name, value)) if !name.trim().is_empty() => {
+                    keystore.set(name.trim(), value.trim());
+                }
+                _ => {
+                    let msg = format!("line {}: `NAME=value` expected", i + 1);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+                }
+            }
+        }
+        Ok(keystore)
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.secrets.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.secrets.get(name).map(|s| s.as_str())
+    }
+}
+
+impl fmt::Debug for Keystore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut names: Vec<_> = self.secrets.keys().collect();
+        names.sort();
+        f.debug_struct("Keystore").field("names", &names).finish()
+    }
+}
+
+#[cfg(unix)]
+fn warn_if_shared(path: &str) {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Ok(meta) = fs::metadata(path) {
+        if meta.permissions().mode() & 0o077 != 0 {
+            warn!("Keystore `{}` is accessible by other users", path);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn warn_if_shared(_path: &str) {}
+
+/// Values of variables referenced in configs.
+#[derive(Clone, Debug, Default)]
+pub struct Variables {
+    keystore: Keystore,
+    environment: HashMap<String, String>,
+}
+
+impl Variables {
+    pub fn new(keystore: Keystore, environment: HashMap<String, String>) -> Variables {
+        Variables { keystore, environment }
+    }
+
+    /// Takes values from the keystore and the environment of the process.
+    pub fn from_env(keystore: Keystore) -> Variables {
+        Variables::new(keystore, env::vars().collect())
+    }
+
+    /// Looks a variable up, the keystore overrides the environment.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.keystore.get(name).or_else(|| self.environment.get(name).map(|s| s.as_str()))
+    }
+
+    /// Replaces variable references in a string.
+    ///
+    /// Returns the name of the first undefined variable without a default, if any. Text which
+    /// only resembles a reference (e.g. `${1}` or an unterminated `${A`) is kept as is.
+    pub fn substitute(&self, s: &str) -> Result<String, String> {
+        let mut out = String::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("${") {
+            out.push_str(&rest[..start]);
+            let reference = &rest[start + 2..];
+            let end = match reference.find('}') {
+                Some(end) => end,
+                None => {
+                    out.push_str(&rest[start..]);
+                    return Ok(out);
+                }
+            };
+            let (name, default) = match reference[..end].split_once(':') {
+                Some((name, default)) => (name, Some(default)),
+                None => (&reference[..end], None),
+            };
+            if !is_variable_name(name) {
+                out.push_str(&rest[start..start + end + 3]);
+            } else {
+                match self.get(name).or(default) {
+                    Some(value) => out.push_str(value),
+                    None => return Err(name.to_string()),
+                }
+            }
+            rest = &reference[end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+/// Checks that the name is a valid variable name, i.e. `[A-Za-z_.][A-Za-z0-9_.]*`.
+fn is_variable_name(name: &str) -> bool {
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.';
+    name.chars().next().is_some_and(|c| !c.is_ascii_digit()) && name.chars().all(valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempdir::TempDir;
+    use super::*;
+
+    fn variables() -> Variables {
+        let mut keystore = Keystore::new();
+        keystore.set("ES_PASSWORD", "s3cr3t");
+        keystore.set("HOST", "from-keystore");
+        let environment = vec![("HOST", "from-env"), ("PORT", "9200"), ("EMPTY", "")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Variables::new(keystore, environment)
+    }
+
+    #[test]
+    fn test_substitute() {
+        let vars = variables();
+        let cases = vec![
+            ("${ES_PASSWORD}", "s3cr3t"),
+            ("http://${HOST}:${PORT}/", "http://from-keystore:9200/"),
+            ("${BEATS_PORT:5044}", "5044"),
+            ("${PORT:5044}", "9200"),
+            ("${MISSING:}", ""),
+            ("${EMPTY:x}", ""),
+            ("${URL:http://a:80}", "http://a:80"),
+            ("$HOME ${1} ${a b} ${HOST", "$HOME ${1} ${a b} ${HOST"),
+            ("%{[host]}", "%{[host]}"),
+        ];
+        for (s, expected) in cases {
+            assert_eq!(Ok(expected.to_string()), vars.substitute(s), "{}", s);
+        }
+        assert_eq!(Err("USER".to_string()), vars.substitute("${HOST} ${USER}"));
+    }
+
+    #[test]
+    fn test_keystore() {
+        let dir = TempDir::new("keystore");
+        let path = dir.join("keystore");
+        let path = path.to_str().unwrap();
+        let mut file = fs::File::create(path).unwrap();
+        writeln!(file, "# secrets\n\nES_PASSWORD = s3cr3t\nTOKEN=a=b").unwrap();
+
+        let keystore = Keystore::load(path).unwrap();
+        assert_eq!(Some("s3cr3t"), keystore.get("ES_PASSWORD"));
+        assert_eq!(Some("a=b"), keystore.get("TOKEN"));
+        assert_eq!("Keystore { names: [\"ES_PASSWORD\", \"TOKEN\"] }", format!("{:?}", keystore));
+
+        writeln!(file, "garbage").unwrap();
+        let err = Keystore::load(path).unwrap_err();
+        assert_eq!("line 5: `NAME=value` expected", err.to_string());
+    }
+}
@@ -1,3 +1,10 @@
+//! Traversal of the config AST.
+//!
+//! `Visitor` inspects the tree (e.g. for lint passes) while `VisitorMut` can rewrite it in
+//! place (e.g. to fold constants). Every `visit_*` method calls the matching `walk_*` function
+//! by default, which visits all the children of the node. Overriding implementations call it
+//! to go deeper or skip it to prune the subtree.
+
 use config::ast::*;
 
 pub trait Visitor<'ast>: Sized {
@@ -18,27 +25,53 @@ pub trait Visitor<'ast>: Sized {
     }
 
     fn visit_input_plugin(&mut self, plugin: &'ast Plugin) {
-
+        walk_plugin(self, plugin)
     }
 
     fn visit_input_branch(&mut self, branch: &'ast Branch) {
-
+        walk_input_branch(self, branch)
     }
 
     fn visit_filter_plugin(&mut self, plugin: &'ast Plugin) {
-
+        walk_plugin(self, plugin)
     }
 
     fn visit_filter_branch(&mut self, branch: &'ast Branch) {
-
+        walk_filter_branch(self, branch)
     }
 
     fn visit_output_plugin(&mut self, plugin: &'ast Plugin) {
-
+        walk_plugin(self, plugin)
     }
 
     fn visit_output_branch(&mut self, branch: &'ast Branch) {
+        walk_output_branch(self, branch)
+    }
+
+    /// Visits a case of a branch in the section of the given type.
+    fn visit_case(&mut self, section: PluginType, case: &'ast Case) {
+        walk_case(self, section, case)
+    }
+
+    fn visit_condition(&mut self, condition: &'ast Condition) {
+        walk_condition(self, condition)
+    }
 
+    fn visit_bool_expr(&mut self, expr: &'ast BoolExpr) {
+        walk_bool_expr(self, expr)
+    }
+
+    fn visit_rvalue(&mut self, rvalue: &'ast Rvalue) {
+        walk_rvalue(self, rvalue)
+    }
+
+    fn visit_attribute(&mut self, attribute: &'ast Attribute) {
+        walk_attribute(self, attribute)
+    }
+
+    /// Visits an attribute value. Plugins met here are codecs.
+    fn visit_value(&mut self, value: &'ast Value) {
+        walk_value(self, value)
     }
 }
 
@@ -58,24 +91,403 @@ pub fn walk_plugin_section<'a, V: Visitor<'a>>(visitor: &mut V, section: &'a Plu
 }
 
 macro_rules! walk_block {
-    ($visitor: expr, $visit_plugin_method: ident, $visit_branch_method: ident, $block: expr) => {
+    ($visitor: expr, $visit_plugin_method: ident, $visit_branch_method: ident, $block: expr,
+     $($mutability: tt)*) => {
         for branch_or_plugin in $block {
             match *branch_or_plugin {
-                BranchOrPlugin::Plugin(ref p) => $visitor.$visit_plugin_method(p),
-                BranchOrPlugin::Branch(ref b) => $visitor.$visit_branch_method(b),
+                BranchOrPlugin::Plugin(ref $($mutability)* p) => $visitor.$visit_plugin_method(p),
+                BranchOrPlugin::Branch(ref $($mutability)* b) => $visitor.$visit_branch_method(b),
             }
         }
     };
 }
 
 pub fn walk_input_block<'a, V: Visitor<'a>>(visitor: &mut V, block: &'a Block) {
-    walk_block!(visitor, visit_input_plugin, visit_input_branch, block)
+    walk_block!(visitor, visit_input_plugin, visit_input_branch, block,)
 }
 
 pub fn walk_filter_block<'a, V: Visitor<'a>>(visitor: &mut V, block: &'a Block) {
-    walk_block!(visitor, visit_filter_plugin, visit_filter_branch, block)
+    walk_block!(visitor, visit_filter_plugin, visit_filter_branch, block,)
 }
 
 pub fn walk_output_block<'a, V: Visitor<'a>>(visitor: &mut V, block: &'a Block) {
-    walk_block!(visitor, visit_output_plugin, visit_output_branch, block)
+    walk_block!(visitor, visit_output_plugin, visit_output_branch, block,)
+}
+
+pub fn walk_plugin<'a, V: Visitor<'a>>(visitor: &mut V, plugin: &'a Plugin) {
+    for attribute in &plugin.attributes {
+        visitor.visit_attribute(attribute);
+    }
+}
+
+pub fn walk_attribute<'a, V: Visitor<'a>>(visitor: &mut V, attribute: &'a Attribute) {
+    visitor.visit_value(&attribute.value)
+}
+
+pub fn walk_value<'a, V: Visitor<'a>>(visitor: &mut V, value: &'a Value) {
+    match *value {
+        Value::Plugin(ref p) => walk_plugin(visitor, p),
+        Value::Array(ref values) => {
+            for v in values {
+                visitor.visit_value(v);
+            }
+        }
+        Value::Hash(ref entries) => {
+            for e in entries {
+                visitor.visit_value(&e.value);
+            }
+        }
+        Value::Bareword(_) | Value::String(_) | Value::Number(_) => {}
+    }
+}
+
+macro_rules! walk_branch {
+    ($visitor: expr, $section: expr, $branch: expr) => {
+        for case in $branch.cases.iter() {
+            $visitor.visit_case($section, case);
+        }
+    };
+}
+
+pub fn walk_input_branch<'a, V: Visitor<'a>>(visitor: &mut V, branch: &'a Branch) {
+    walk_branch!(visitor, PluginType::Input, branch)
+}
+
+pub fn walk_filter_branch<'a, V: Visitor<'a>>(visitor: &mut V, branch: &'a Branch) {
+    walk_branch!(visitor, PluginType::Filter, branch)
+}
+
+pub fn walk_output_branch<'a, V: Visitor<'a>>(visitor: &mut V, branch: &'a Branch) {
+    walk_branch!(visitor, PluginType::Output, branch)
+}
+
+pub fn walk_case<'a, V: Visitor<'a>>(visitor: &mut V, section: PluginType, case: &'a Case) {
+    visitor.visit_condition(&case.condition);
+    match section {
+        PluginType::Input => visitor.visit_input_block(&case.block),
+        PluginType::Filter => visitor.visit_filter_block(&case.block),
+        PluginType::Output => visitor.visit_output_block(&case.block),
+    }
+}
+
+pub fn walk_condition<'a, V: Visitor<'a>>(visitor: &mut V, condition: &'a Condition) {
+    match *condition {
+        Condition::Leaf(ref expr) => visitor.visit_bool_expr(expr),
+        Condition::Branch(_, ref lhs, ref rhs) => {
+            visitor.visit_condition(lhs);
+            visitor.visit_condition(rhs);
+        }
+    }
+}
+
+pub fn walk_bool_expr<'a, V: Visitor<'a>>(visitor: &mut V, expr: &'a BoolExpr) {
+    match *expr {
+        BoolExpr::Parens(ref condition) => visitor.visit_condition(condition),
+        BoolExpr::Negative(ref expr) => visitor.visit_bool_expr(expr),
+        BoolExpr::Compare(_, ref lhs, ref rhs) |
+        BoolExpr::In(_, ref lhs, ref rhs) |
+        BoolExpr::Match(_, ref lhs, ref rhs) => {
+            visitor.visit_rvalue(lhs);
+            visitor.visit_rvalue(rhs);
+        }
+        BoolExpr::Rvalue(ref rvalue) => visitor.visit_rvalue(rvalue),
+    }
+}
+
+pub fn walk_rvalue<'a, V: Visitor<'a>>(visitor: &mut V, rvalue: &'a Rvalue) {
+    match *rvalue {
+        Rvalue::Array(ref values) => {
+            for v in values {
+                visitor.visit_rvalue(v);
+            }
+        }
+        Rvalue::Hash(ref entries) => {
+            for (_, v) in entries {
+                visitor.visit_rvalue(v);
+            }
+        }
+        Rvalue::MethodCall(ref call) => {
+            for arg in &call.args {
+                visitor.visit_rvalue(arg);
+            }
+        }
+        Rvalue::Number(_) | Rvalue::String(_) | Rvalue::Selector(_) | Rvalue::Regex(_) => {}
+    }
+}
+
+/// Same as `Visitor` but the nodes can be changed.
+pub trait VisitorMut: Sized {
+    fn visit_section(&mut self, section: &mut PluginSection) {
+        walk_plugin_section_mut(self, section)
+    }
+
+    fn visit_input_block(&mut self, block: &mut Block) {
+        walk_input_block_mut(self, block)
+    }
+
+    fn visit_filter_block(&mut self, block: &mut Block) {
+        walk_filter_block_mut(self, block)
+    }
+
+    fn visit_output_block(&mut self, block: &mut Block) {
+        walk_output_block_mut(self, block)
+    }
+
+    fn visit_input_plugin(&mut self, plugin: &mut Plugin) {
+        walk_plugin_mut(self, plugin)
+    }
+
+    fn visit_input_branch(&mut self, branch: &mut Branch) {
+        walk_input_branch_mut(self, branch)
+    }
+
+    fn visit_filter_plugin(&mut self, plugin: &mut Plugin) {
+        walk_plugin_mut(self, plugin)
+    }
+
+    fn visit_filter_branch(&mut self, branch: &mut Branch) {
+        walk_filter_branch_mut(self, branch)
+    }
+
+    fn visit_output_plugin(&mut self, plugin: &mut Plugin) {
+        walk_plugin_mut(self, plugin)
+    }
+
+    fn visit_output_branch(&mut self, branch: &mut Branch) {
+        walk_output_branch_mut(self, branch)
+    }
+
+    fn visit_case(&mut self, section: PluginType, case: &mut Case) {
+        walk_case_mut(self, section, case)
+    }
+
+    fn visit_condition(&mut self, condition: &mut Condition) {
+        walk_condition_mut(self, condition)
+    }
+
+    fn visit_bool_expr(&mut self, expr: &mut BoolExpr) {
+        walk_bool_expr_mut(self, expr)
+    }
+
+    fn visit_rvalue(&mut self, rvalue: &mut Rvalue) {
+        walk_rvalue_mut(self, rvalue)
+    }
+
+    fn visit_attribute(&mut self, attribute: &mut Attribute) {
+        walk_attribute_mut(self, attribute)
+    }
+
+    fn visit_value(&mut self, value: &mut Value) {
+        walk_value_mut(self, value)
+    }
+}
+
+pub fn walk_config_mut<V: VisitorMut>(visitor: &mut V, config: &mut Config) {
+    for section in &mut config.sections {
+        visitor.visit_section(section);
+    }
+}
+
+pub fn walk_plugin_section_mut<V: VisitorMut>(visitor: &mut V, section: &mut PluginSection) {
+    match section.plugin_type {
+        PluginType::Input => visitor.visit_input_block(&mut section.block),
+        PluginType::Filter => visitor.visit_filter_block(&mut section.block),
+        PluginType::Output => visitor.visit_output_block(&mut section.block),
+    }
+}
+
+pub fn walk_input_block_mut<V: VisitorMut>(visitor: &mut V, block: &mut Block) {
+    walk_block!(visitor, visit_input_plugin, visit_input_branch, block, mut)
+}
+
+pub fn walk_filter_block_mut<V: VisitorMut>(visitor: &mut V, block: &mut Block) {
+    walk_block!(visitor, visit_filter_plugin, visit_filter_branch, block, mut)
+}
+
+pub fn walk_output_block_mut<V: VisitorMut>(visitor: &mut V, block: &mut Block) {
+    walk_block!(visitor, visit_output_plugin, visit_output_branch, block, mut)
+}
+
+pub fn walk_plugin_mut<V: VisitorMut>(visitor: &mut V, plugin: &mut Plugin) {
+    for attribute in &mut plugin.attributes {
+        visitor.visit_attribute(attribute);
+    }
+}
+
+pub fn walk_attribute_mut<V: VisitorMut>(visitor: &mut V, attribute: &mut Attribute) {
+    visitor.visit_value(&mut attribute.value)
+}
+
+pub fn walk_value_mut<V: VisitorMut>(visitor: &mut V, value: &mut Value) {
+    match *value {
+        Value::Plugin(ref mut p) => walk_plugin_mut(visitor, p),
+        Value::Array(ref mut values) => {
+            for v in values {
+                visitor.visit_value(v);
+            }
+        }
+        Value::Hash(ref mut entries) => {
+            for e in entries {
+                visitor.visit_value(&mut e.value);
+            }
+        }
+        Value::Bareword(_) | Value::String(_) | Value::Number(_) => {}
+    }
+}
+
+macro_rules! walk_branch_mut {
+    ($visitor: expr, $section: expr, $branch: expr) => {
+        for case in $branch.cases.iter_mut() {
+            $visitor.visit_case($section, case);
+        }
+    };
+}
+
+pub fn walk_input_branch_mut<V: VisitorMut>(visitor: &mut V, branch: &mut Branch) {
+    walk_branch_mut!(visitor, PluginType::Input, branch)
+}
+
+pub fn walk_filter_branch_mut<V: VisitorMut>(visitor: &mut V, branch: &mut Branch) {
+    walk_branch_mut!(visitor, PluginType::Filter, branch)
+}
+
+pub fn walk_output_branch_mut<V: VisitorMut>(visitor: &mut V, branch: &mut Branch) {
+    walk_branch_mut!(visitor, PluginType::Output, branch)
+}
+
+pub fn walk_case_mut<V: VisitorMut>(visitor: &mut V, section: PluginType, case: &mut Case) {
+    visitor.visit_condition(&mut case.condition);
+    match section {
+        PluginType::Input => visitor.visit_input_block(&mut case.block),
+        PluginType::Filter => visitor.visit_filter_block(&mut case.block),
+        PluginType::Output => visitor.visit_output_block(&mut case.block),
+    }
+}
+
+pub fn walk_condition_mut<V: VisitorMut>(visitor: &mut V, condition: &mut Condition) {
+    match *condition {
+        Condition::Leaf(ref mut expr) => visitor.visit_bool_expr(expr),
+        Condition::Branch(_, ref mut lhs, ref mut rhs) => {
+            visitor.visit_condition(lhs);
+            visitor.visit_condition(rhs);
+        }
+    }
+}
+
+pub fn walk_bool_expr_mut<V: VisitorMut>(visitor: &mut V, expr: &mut BoolExpr) {
+    match *expr {
+        BoolExpr::Parens(ref mut condition) => visitor.visit_condition(condition),
+        BoolExpr::Negative(ref mut expr) => visitor.visit_bool_expr(expr),
+        BoolExpr::Compare(_, ref mut lhs, ref mut rhs) |
+        BoolExpr::In(_, ref mut lhs, ref mut rhs) |
+        BoolExpr::Match(_, ref mut lhs, ref mut rhs) => {
+            visitor.visit_rvalue(lhs);
+            visitor.visit_rvalue(rhs);
+        }
+        BoolExpr::Rvalue(ref mut rvalue) => visitor.visit_rvalue(rvalue),
+    }
+}
+
+pub fn walk_rvalue_mut<V: VisitorMut>(visitor: &mut V, rvalue: &mut Rvalue) {
+    match *rvalue {
+        Rvalue::Array(ref mut values) => {
+            for v in values {
+                visitor.visit_rvalue(v);
+            }
+        }
+        Rvalue::Hash(ref mut entries) => {
+            for (_, v) in entries {
+                visitor.visit_rvalue(v);
+            }
+        }
+        Rvalue::MethodCall(ref mut call) => {
+            for arg in &mut call.args {
+                visitor.visit_rvalue(arg);
+            }
+        }
+        Rvalue::Number(_) | Rvalue::String(_) | Rvalue::Selector(_) | Rvalue::Regex(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::parse::parse;
+    use config::print::print;
+    use super::*;
+
+    /// Collects field references and codecs used in a config.
+    #[derive(Default)]
+    struct Collector {
+        selectors: Vec<String>,
+        codecs: Vec<String>,
+    }
+
+    impl<'ast> Visitor<'ast> for Collector {
+        fn visit_rvalue(&mut self, rvalue: &'ast Rvalue) {
+            if let Rvalue::Selector(ref s) = *rvalue {
+                self.selectors.push(s.to_string());
+            }
+            walk_rvalue(self, rvalue)
+        }
+
+        fn visit_value(&mut self, value: &'ast Value) {
+            if let Value::Plugin(ref p) = *value {
+                self.codecs.push(p.name.clone());
+            }
+            walk_value(self, value)
+        }
+    }
+
+    /// Replaces comparisons of numeric constants with their results.
+    struct ConstantFolder;
+
+    impl VisitorMut for ConstantFolder {
+        fn visit_bool_expr(&mut self, expr: &mut BoolExpr) {
+            walk_bool_expr_mut(self, expr);
+            let folded = match *expr {
+                BoolExpr::Compare(op, Rvalue::Number(lhs), Rvalue::Number(rhs)) => {
+                    let result = match op {
+                        CompareOperator::Eq => lhs == rhs,
+                        CompareOperator::Ne => lhs != rhs,
+                        CompareOperator::Lt => lhs < rhs,
+                        CompareOperator::Gt => lhs > rhs,
+                        CompareOperator::Le => lhs <= rhs,
+                        CompareOperator::Ge => lhs >= rhs,
+                    };
+                    if result { 1.0 } else { 0.0 }
+                }
+                _ => return,
+            };
+            *expr = BoolExpr::Rvalue(Rvalue::Number(folded));
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let conf = br#"
+            input { stdin { codec => json { charset => "UTF-8" } } }
+            filter {
+                if [a] in [[b][c], "x"] or (!exists([d]) and [e] =~ /f/) {
+                    if { "k" => [g] } == [h] { drop {} }
+                } else if [i] { mutate {} }
+            }
+            output { if [j] { file { codec => line {} } } }
+        "#;
+        let config = parse(conf).unwrap();
+        let mut collector = Collector::default();
+        walk_config(&mut collector, &config);
+        assert_eq!(vec!["[a]", "[b][c]", "[d]", "[e]", "[g]", "[h]", "[i]", "[j]"],
+                   collector.selectors);
+        assert_eq!(vec!["json", "line"], collector.codecs);
+    }
+
+    #[test]
+    fn test_visitor_mut() {
+        let conf = b"filter { if 2 > 1 and ([a] or 1 == 2) { if !(3 != 3) { drop {} } } }";
+        let mut config = parse(conf).unwrap();
+        walk_config_mut(&mut ConstantFolder, &mut config);
+        let expected = "filter {\n  if 1 and ([a] or 0) {\n    if !(0) {\n      drop {}\n    }\n  \
+                        }\n}\n";
+        assert_eq!(expected, print(&config));
+    }
 }
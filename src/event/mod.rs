@@ -0,0 +1,341 @@
+//! Event is a unit of data flowing through the pipeline.
+
+use std::fmt;
+
+use chrono::Utc;
+
+use config::ast::Selector;
+
+pub use self::template::{Template, TemplateError};
+pub use self::value::*;
+
+mod template;
+mod value;
+
+pub const TIMESTAMP: &str = "@timestamp";
+pub const VERSION: &str = "@version";
+pub const METADATA: &str = "@metadata";
+pub const TAGS: &str = "tags";
+pub const MESSAGE: &str = "message";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldError {
+    /// Field reference doesn't contain any elements.
+    EmptyReference,
+    /// An intermediate element of the reference is neither a map nor an array.
+    NotAContainer(String),
+    /// The reference points beyond the end of an array.
+    IndexOutOfBounds(String),
+    /// The field can't be removed or replaced with a value of another type.
+    Protected(String),
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FieldError::EmptyReference => write!(f, "empty field reference"),
+            FieldError::NotAContainer(ref name) => {
+                write!(f, "field `{}` is neither a map nor an array", name)
+            }
+            FieldError::IndexOutOfBounds(ref index) => {
+                write!(f, "index {} is out of bounds", index)
+            }
+            FieldError::Protected(ref name) => write!(f, "field `{}` is protected", name),
+        }
+    }
+}
+
+/// Logstash-like event.
+///
+/// Fields form a tree of values. Top-level `@timestamp` and `@version` fields are always
+/// present. Fields under `[@metadata]` are kept separately and aren't a part of the event's
+/// data (i.e. outputs don't see them), but can be used in conditions and field references.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    fields: Value,
+    metadata: Value,
+}
+
+impl Default for Event {
+    fn default() -> Event {
+        Event::new()
+    }
+}
+
+impl Event {
+    pub fn new() -> Event {
+        let mut fields = Map::new();
+        fields.insert(TIMESTAMP.to_string(), Value::Timestamp(Utc::now()));
+        fields.insert(VERSION.to_string(), Value::from("1"));
+        Event {
+            fields: Value::Map(fields),
+            metadata: Value::Map(Map::new()),
+        }
+    }
+
+    pub fn with_message(message: &str) -> Event {
+        let mut event = Event::new();
+        event.fields_mut().insert(MESSAGE.to_string(), Value::from(message));
+        event
+    }
+
+    /// Top-level fields of the event, `@metadata` excluded.
+    pub fn fields(&self) -> &Map {
+        self.fields.as_map().expect("event fields must be a map")
+    }
+
+    /// Serializes the event's fields as a single-line JSON object.
+    pub fn to_json(&self) -> String {
+        // Maps are always formatted as JSON.
+        self.fields.to_string()
+    }
+
+    pub fn metadata(&self) -> &Map {
+        self.metadata.as_map().expect("event metadata must be a map")
+    }
+
+    pub fn timestamp(&self) -> Timestamp {
+        match self.fields().get(TIMESTAMP) {
+            Some(&Value::Timestamp(ts)) => ts,
+            _ => unreachable!("@timestamp is always present"),
+        }
+    }
+
+    pub fn set_timestamp(&mut self, ts: Timestamp) {
+        self.fields_mut().insert(TIMESTAMP.to_string(), Value::Timestamp(ts));
+    }
+
+    /// Returns a field by reference like `[a][b][0]`.
+    pub fn get(&self, field: &Selector) -> Option<&Value> {
+        let (root, path) = self.resolve(field)?;
+        path.iter().try_fold(root, |v, key| v.child(key))
+    }
+
+    pub fn get_mut(&mut self, field: &Selector) -> Option<&mut Value> {
+        let (root, path) = self.resolve_mut(field)?;
+        path.iter().try_fold(root, |v, key| v.child_mut(key))
+    }
+
+    /// Sets a field, creating missing intermediate maps. Returns the previous value.
+    pub fn set(&mut self, field: &Selector, value: Value) -> Result<Option<Value>, FieldError> {
+        check_protected(field, Some(&value))?;
+
+        let (root, path) = self.resolve_mut(field).ok_or(FieldError::EmptyReference)?;
+        let (last, init) = match path.split_last() {
+            Some(split) => split,
+            // Only the whole `[@metadata]` can be referenced by an empty path.
+            None => return Ok(Some(::std::mem::replace(root, value))),
+        };
+
+        let mut node = root;
+        let mut node_name = last;
+        for key in init {
+            node = match *node {
+                Value::Map(ref mut m) => {
+                    m.entry(key.clone()).or_insert_with(|| Value::Map(Map::new()))
+                }
+                ref mut v @ Value::Array(_) => {
+                    v.child_mut(key).ok_or_else(|| FieldError::IndexOutOfBounds(key.clone()))?
+                }
+                _ => return Err(FieldError::NotAContainer(node_name.clone())),
+            };
+            node_name = key;
+        }
+
+        match *node {
+            Value::Map(ref mut m) => Ok(m.insert(last.clone(), value)),
+            ref mut v @ Value::Array(_) => {
+                match v.child_mut(last) {
+                    Some(slot) => Ok(Some(::std::mem::replace(slot, value))),
+                    None => Err(FieldError::IndexOutOfBounds(last.clone())),
+                }
+            }
+            _ => Err(FieldError::NotAContainer(node_name.clone())),
+        }
+    }
+
+    /// Removes a field. Returns the removed value.
+    pub fn remove(&mut self, field: &Selector) -> Result<Option<Value>, FieldError> {
+        check_protected(field, None)?;
+
+        let (root, path) = self.resolve_mut(field).ok_or(FieldError::EmptyReference)?;
+        let (last, init) = path.split_last().ok_or(FieldError::EmptyReference)?;
+
+        let parent = init.iter().try_fold(root, |v, key| v.child_mut(key));
+        Ok(parent.and_then(|p| p.remove_child(last)))
+    }
+
+    pub fn tags(&self) -> Vec<&str> {
+        match self.fields().get(TAGS) {
+            Some(Value::Array(tags)) => tags.iter().filter_map(Value::as_str).collect(),
+            Some(Value::String(tag)) => vec![tag.as_str()],
+            _ => vec![],
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags().contains(&tag)
+    }
+
+    /// Adds a tag unless the event already has it.
+    pub fn add_tag(&mut self, tag: &str) {
+        if self.has_tag(tag) {
+            return;
+        }
+
+        let mut tags: Vec<Value> = self.tags().into_iter().map(Value::from).collect();
+        tags.push(Value::from(tag));
+        self.fields_mut().insert(TAGS.to_string(), Value::Array(tags));
+    }
+
+    pub fn remove_tag(&mut self, tag: &str) {
+        if !self.has_tag(tag) {
+            return;
+        }
+
+        let tags = self.tags().into_iter().filter(|&t| t != tag).map(Value::from).collect();
+        self.fields_mut().insert(TAGS.to_string(), Value::Array(tags));
+    }
+
+    fn fields_mut(&mut self) -> &mut Map {
+        match self.fields {
+            Value::Map(ref mut m) => m,
+            _ => unreachable!("event fields must be a map"),
+        }
+    }
+
+    /// Splits a field reference into the root value (fields or metadata) and the path in it.
+    fn resolve<'a, 'b>(&'a self, field: &'b Selector) -> Option<(&'a Value, &'b [String])> {
+        match field.elements.split_first() {
+            Some((first, rest)) if first == METADATA => Some((&self.metadata, rest)),
+            Some(_) => Some((&self.fields, &field.elements)),
+            None => None,
+        }
+    }
+
+    fn resolve_mut<'a, 'b>(&'a mut self,
+                           field: &'b Selector)
+                           -> Option<(&'a mut Value, &'b [String])> {
+        match field.elements.split_first() {
+            Some((first, rest)) if first == METADATA => Some((&mut self.metadata, rest)),
+            Some(_) => Some((&mut self.fields, &field.elements)),
+            None => None,
+        }
+    }
+}
+
+/// Checks that `@timestamp` and `@metadata` aren't removed or replaced with values of other types.
+fn check_protected(field: &Selector, value: Option<&Value>) -> Result<(), FieldError> {
+    match (field.elements.as_slice(), value) {
+        ([name], Some(&Value::Timestamp(_))) if name == TIMESTAMP => Ok(()),
+        ([name], Some(&Value::Map(_))) if name == METADATA => Ok(()),
+        ([name], _) if name == TIMESTAMP || name == METADATA => {
+            Err(FieldError::Protected(name.clone()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use config::ast::Selector;
+    use super::*;
+
+    fn sel(field: &str) -> Selector {
+        Selector::from(field)
+    }
+
+    #[test]
+    fn test_new() {
+        let event = Event::with_message("hello");
+        assert_eq!(Some(&Value::from("1")), event.get(&sel(VERSION)));
+        assert_eq!(Some(&Value::from("hello")), event.get(&sel("[message]")));
+        assert_eq!(Some(&Value::Timestamp(event.timestamp())), event.get(&sel(TIMESTAMP)));
+        assert!(event.tags().is_empty());
+        assert!(event.metadata().is_empty());
+    }
+
+    #[test]
+    fn test_get_set_nested() {
+        let mut event = Event::new();
+        assert_eq!(Ok(None), event.set(&sel("[a][b][c]"), Value::from(42)));
+        assert_eq!(Ok(None),
+                   event.set(&sel("[a][list]"),
+                             Value::from(vec![Value::from("x"), Value::from("y")])));
+        assert_eq!(Ok(Some(Value::from("y"))), event.set(&sel("[a][list][-1]"), Value::from("z")));
+
+        assert_eq!(Some(&Value::from(42)), event.get(&sel("[a][b][c]")));
+        assert_eq!(Some(&Value::from("x")), event.get(&sel("[a][list][0]")));
+        assert_eq!(Some(&Value::from("z")), event.get(&sel("[a][list][1]")));
+        assert_eq!(None, event.get(&sel("[a][list][2]")));
+        assert_eq!(None, event.get(&sel("[a][b][c][d]")));
+
+        assert_eq!(Err(FieldError::NotAContainer("c".to_string())),
+                   event.set(&sel("[a][b][c][d]"), Value::Null));
+        assert_eq!(Err(FieldError::IndexOutOfBounds("5".to_string())),
+                   event.set(&sel("[a][list][5]"), Value::Null));
+
+        if let Some(&mut Value::Int(ref mut n)) = event.get_mut(&sel("[a][b][c]")) {
+            *n += 1;
+        }
+        assert_eq!(Some(&Value::from(43)), event.get(&sel("[a][b][c]")));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut event = Event::with_message("hello");
+        event.set(&sel("[a][list]"), Value::from(vec![Value::from(1), Value::from(2)])).unwrap();
+
+        assert_eq!(Ok(Some(Value::from(1))), event.remove(&sel("[a][list][0]")));
+        assert_eq!(Some(&Value::from(vec![Value::from(2)])), event.get(&sel("[a][list]")));
+        assert_eq!(Ok(Some(Value::from("hello"))), event.remove(&sel("message")));
+        assert_eq!(Ok(None), event.remove(&sel("message")));
+        assert_eq!(Ok(None), event.remove(&sel("[x][y]")));
+        assert_eq!(Err(FieldError::Protected(TIMESTAMP.to_string())),
+                   event.remove(&sel(TIMESTAMP)));
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let mut event = Event::new();
+        let ts = Utc.with_ymd_and_hms(2017, 3, 8, 12, 30, 0).unwrap();
+
+        event.set_timestamp(ts);
+        assert_eq!(ts, event.timestamp());
+        assert_eq!(Err(FieldError::Protected(TIMESTAMP.to_string())),
+                   event.set(&sel(TIMESTAMP), Value::from("yesterday")));
+        assert_eq!(Ok(Some(Value::Timestamp(ts))),
+                   event.set(&sel(TIMESTAMP), Value::Timestamp(ts)));
+    }
+
+    #[test]
+    fn test_metadata() {
+        let mut event = Event::new();
+        event.set(&sel("[@metadata][index]"), Value::from("logs")).unwrap();
+
+        assert_eq!(Some(&Value::from("logs")), event.get(&sel("[@metadata][index]")));
+        assert_eq!(Some(&Value::from("logs")), event.metadata().get("index"));
+        assert_eq!(None, event.fields().get(METADATA));
+        assert_eq!(Err(FieldError::Protected(METADATA.to_string())),
+                   event.set(&sel(METADATA), Value::from("x")));
+
+        event.set(&sel(METADATA), Value::Map(Map::new())).unwrap();
+        assert!(event.metadata().is_empty());
+    }
+
+    #[test]
+    fn test_tags() {
+        let mut event = Event::new();
+        event.add_tag("foo");
+        event.add_tag("bar");
+        event.add_tag("foo");
+        assert_eq!(vec!["foo", "bar"], event.tags());
+        assert!(event.has_tag("bar"));
+
+        event.remove_tag("foo");
+        assert_eq!(vec!["bar"], event.tags());
+        assert!(!event.has_tag("foo"));
+    }
+}
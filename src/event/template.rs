@@ -0,0 +1,302 @@
+//! Sprintf-style templates, e.g. `/data/%{[host]}/%{+YYYY.MM.dd}.log`.
+
+use std::error;
+use std::fmt;
+use std::iter;
+use std::mem;
+
+use config::ast::Selector;
+use super::{Event, Value};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Literal(String),
+    /// Field reference along with its source text, which is rendered if the field is missing.
+    Field(Selector, String),
+    /// `@timestamp` formatted as a sequence of date items.
+    Timestamp(Vec<DateItem>),
+    /// `@timestamp` as seconds since the epoch, i.e. `%{+%s}`.
+    Epoch,
+}
+
+/// Piece of a `@timestamp` format.
+#[derive(Clone, Debug, PartialEq)]
+enum DateItem {
+    /// `strftime` format.
+    Format(String),
+    /// Fraction of a second with the given number of digits. `strftime` can't do 1 or 2 digits.
+    Fraction(usize),
+}
+
+/// Parsed template. Field references are resolved and rendered per event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    source: String,
+    parts: Vec<Part>,
+}
+
+/// Template parsing error. Offsets are in bytes from the template start.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TemplateError {
+    /// `%{` without the closing `}`.
+    Unterminated(usize),
+    EmptyReference(usize),
+    /// Date format letter which is not supported, e.g. `G` in `%{+G}`.
+    UnsupportedDateFormat(usize, char),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TemplateError::Unterminated(at) => write!(f, "unterminated `%{{` at {}", at),
+            TemplateError::EmptyReference(at) => write!(f, "empty field reference at {}", at),
+            TemplateError::UnsupportedDateFormat(at, c) => {
+                write!(f, "unsupported date format `{}` at {}", c, at)
+            }
+        }
+    }
+}
+
+impl error::Error for TemplateError {}
+
+impl Template {
+    /// Parses a template.
+    ///
+    /// `%{[a][b]}` (or just `%{a}`) is replaced with the field value and `%{+FORMAT}` with
+    /// `@timestamp` formatted using Joda-style `FORMAT` (e.g. `YYYY.MM.dd`) in UTC.
+    /// Everything else is kept as is.
+    pub fn parse(source: &str) -> Result<Template, TemplateError> {
+        let mut parts = vec![];
+        let mut rest = source;
+        while let Some(start) = rest.find("%{") {
+            let offset = source.len() - rest.len() + start;
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..].find('}').ok_or(TemplateError::Unterminated(offset))? + start;
+            let reference = &rest[start + 2..end];
+            parts.push(match reference.strip_prefix('+') {
+                Some("%s") => Part::Epoch,
+                Some(format) => Part::Timestamp(date_format(format, offset + 3)?),
+                None if reference.is_empty() => return Err(TemplateError::EmptyReference(offset)),
+                None => Part::Field(Selector::from(reference), rest[start..=end].to_string()),
+            });
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(Template { source: source.to_string(), parts })
+    }
+
+    /// Tells whether the template renders the same for all the events.
+    pub fn is_constant(&self) -> bool {
+        self.parts.iter().all(|p| matches!(p, Part::Literal(_)))
+    }
+
+    /// Renders the template. References to missing fields are kept verbatim, as in Logstash.
+    pub fn render(&self, event: &Event) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(s) => out.push_str(s),
+                Part::Field(selector, source) => {
+                    match event.get(selector) {
+                        Some(v) if *v != Value::Null => out.push_str(&v.to_string()),
+                        _ => out.push_str(source),
+                    }
+                }
+                Part::Timestamp(items) => {
+                    let timestamp = event.timestamp();
+                    for item in items {
+                        match item {
+                            DateItem::Format(f) => out.push_str(&timestamp.format(f).to_string()),
+                            DateItem::Fraction(digits) => {
+                                // Joda truncates the fraction and pads it with zeros.
+                                let nanos = format!("{:09}", timestamp.timestamp_subsec_nanos());
+                                out.extend(nanos.chars().chain(iter::repeat('0')).take(*digits));
+                            }
+                        }
+                    }
+                }
+                Part::Epoch => out.push_str(&event.timestamp().timestamp().to_string()),
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Converts a Joda date format (used by Logstash) to `strftime` formats and fractions of a
+/// second.
+///
+/// Text in single quotes is literal, `''` is a single quote both inside and outside of it.
+fn date_format(joda: &str, offset: usize) -> Result<Vec<DateItem>, TemplateError> {
+    let chars: Vec<char> = joda.chars().collect();
+    let mut items = vec![];
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let count = chars[i..].iter().take_while(|&&x| x == c).count();
+        if c == '\'' {
+            if count > 1 {
+                out.push('\'');
+                i += 2;
+                continue;
+            }
+            i += 1;
+            while i < chars.len() {
+                if chars[i] == '\'' {
+                    if chars.get(i + 1) != Some(&'\'') {
+                        break;
+                    }
+                    i += 1;
+                }
+                out.extend(escape(chars[i]));
+                i += 1;
+            }
+            i += 1;
+            continue;
+        }
+        if !c.is_ascii_alphabetic() {
+            out.extend(escape(c));
+            i += 1;
+            continue;
+        }
+
+        let spec = match (c, count) {
+            ('Y', 2) | ('y', 2) => "%y",
+            ('Y', _) | ('y', _) => "%Y",
+            ('x', 2) => "%g",
+            ('x', _) => "%G",
+            ('M', 1) => "%-m",
+            ('M', 2) => "%m",
+            ('M', 3) => "%b",
+            ('M', _) => "%B",
+            ('d', 1) => "%-d",
+            ('d', _) => "%d",
+            ('D', _) => "%j",
+            ('E', 1..=3) => "%a",
+            ('E', _) => "%A",
+            ('H', 1) => "%-H",
+            ('H', _) => "%H",
+            ('h', 1) => "%-I",
+            ('h', _) => "%I",
+            ('m', 1) => "%-M",
+            ('m', _) => "%M",
+            ('s', 1) => "%-S",
+            ('s', _) => "%S",
+            ('S', _) => {
+                if !out.is_empty() {
+                    items.push(DateItem::Format(mem::take(&mut out)));
+                }
+                items.push(DateItem::Fraction(count));
+                i += count;
+                continue;
+            }
+            ('a', _) => "%p",
+            ('w', _) => "%V",
+            ('Z', 1) => "%z",
+            ('Z', _) => "%:z",
+            _ => {
+                let at = offset + chars[..i].iter().map(|c| c.len_utf8()).sum::<usize>();
+                return Err(TemplateError::UnsupportedDateFormat(at, c));
+            }
+        };
+        out.push_str(spec);
+        i += count;
+    }
+    if !out.is_empty() {
+        items.push(DateItem::Format(out));
+    }
+    Ok(items)
+}
+
+fn escape(c: char) -> Vec<char> {
+    if c == '%' {
+        vec!['%', '%']
+    } else {
+        vec![c]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn event() -> Event {
+        let mut event = Event::with_message("hello");
+        event.set_timestamp(Utc.with_ymd_and_hms(2017, 3, 4, 5, 6, 7).unwrap());
+        event.set(&Selector::from("host"), Value::from("web-1")).unwrap();
+        event.set(&Selector::from("[http][status]"), Value::Int(200)).unwrap();
+        event.set(&Selector::from("tags"), Value::from(vec![Value::from("a"), Value::from("b")]))
+            .unwrap();
+        event
+    }
+
+    #[test]
+    fn test_render() {
+        let event = event();
+        let cases = vec![
+            ("/data/%{[host]}/%{+YYYY-MM-dd}.log", "/data/web-1/2017-03-04.log"),
+            ("%{host} %{[http][status]} %{tags}", "web-1 200 a,b"),
+            ("%{[http]}", "{\"status\":200}"),
+            ("%{+YYYY.MM.dd'T'HH:mm:ss.SSSZ}", "2017.03.04T05:06:07.000+0000"),
+            ("%{+d/M/yy 'at' h a}", "4/3/17 at 5 AM"),
+            ("%{+%s}", "1488603967"),
+            ("%{+'100%'}", "100%"),
+            ("%{+h 'o''clock' ''a''}", "5 o'clock 'AM'"),
+            ("%{@timestamp}", "2017-03-04T05:06:07.000Z"),
+            ("%{[missing]}-%{host}", "%{[missing]}-web-1"),
+            ("no refs, 100%", "no refs, 100%"),
+            ("", ""),
+        ];
+        for (source, expected) in cases {
+            let template = Template::parse(source).unwrap();
+            assert_eq!(expected, template.render(&event), "{}", source);
+            assert_eq!(source, template.to_string());
+        }
+    }
+
+    #[test]
+    fn test_render_date_formats() {
+        let mut event = Event::new();
+        event.set_timestamp(Utc.with_ymd_and_hms(2024, 12, 30, 1, 2, 3).unwrap() +
+                            chrono::Duration::nanoseconds(123_456_789));
+        let cases = vec![
+            // 2024-12-30 belongs to the first week of 2025.
+            ("logstash-%{+xxxx.ww}", "logstash-2025.01"),
+            ("%{+xx-YYYY}", "25-2024"),
+            ("%{+ss.S}", "03.1"),
+            ("%{+ss.SS}", "03.12"),
+            ("%{+ss.SSS}", "03.123"),
+            ("%{+SSSSSS}", "123456"),
+            ("%{+SSSSSSSSSSS}", "12345678900"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(expected, Template::parse(source).unwrap().render(&event), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(Template::parse("/var/log/a.log").unwrap().is_constant());
+        assert!(!Template::parse("/var/log/%{host}.log").unwrap().is_constant());
+        assert!(!Template::parse("%{+YYYY}").unwrap().is_constant());
+
+        assert_eq!(Err(TemplateError::Unterminated(3)), Template::parse("ab-%{host"));
+        assert_eq!(Err(TemplateError::EmptyReference(0)), Template::parse("%{}"));
+        assert_eq!(Err(TemplateError::UnsupportedDateFormat(10, 'G')),
+                   Template::parse("%{+YYYY-MMG}"));
+        assert_eq!("unsupported date format `G` at 10",
+                   Template::parse("%{+YYYY-MMG}").unwrap_err().to_string());
+    }
+}
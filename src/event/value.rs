@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+pub type Timestamp = DateTime<Utc>;
+
+pub type Map = BTreeMap<String, Value>;
+
+/// A node of the event's value tree.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Timestamp(Timestamp),
+    Array(Vec<Value>),
+    Map(Map),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a numeric node, integers are converted to floats.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Int(i) => Some(i as f64),
+            Value::Float(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// Tells whether the value is true in conditions. Only `null` and `false` are not.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(*self, Value::Null | Value::Bool(false))
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match *self {
+            Value::Array(ref a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&Map> {
+        match *self {
+            Value::Map(ref m) => Some(m),
+            _ => None,
+        }
+    }
+
+    /// Looks up a direct child of a container value.
+    ///
+    /// Maps are indexed by keys and arrays by (possibly negative) numbers.
+    pub fn child(&self, key: &str) -> Option<&Value> {
+        match *self {
+            Value::Map(ref m) => m.get(key),
+            Value::Array(ref a) => index(a.len(), key).map(|i| &a[i]),
+            _ => None,
+        }
+    }
+
+    pub fn child_mut(&mut self, key: &str) -> Option<&mut Value> {
+        match *self {
+            Value::Map(ref mut m) => m.get_mut(key),
+            Value::Array(ref mut a) => index(a.len(), key).map(move |i| &mut a[i]),
+            _ => None,
+        }
+    }
+
+    /// Removes a direct child of a container value.
+    pub fn remove_child(&mut self, key: &str) -> Option<Value> {
+        match *self {
+            Value::Map(ref mut m) => m.remove(key),
+            Value::Array(ref mut a) => index(a.len(), key).map(|i| a.remove(i)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    /// Formats the value the way Logstash interpolates it into strings.
+    ///
+    /// Arrays are joined with commas, maps are written as JSON and timestamps in ISO 8601
+    /// with milliseconds.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Null => Ok(()),
+            Value::String(ref s) => f.write_str(s),
+            Value::Timestamp(ts) => f.write_str(&ts.to_rfc3339_opts(SecondsFormat::Millis, true)),
+            Value::Array(ref a) => {
+                for (i, v) in a.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                Ok(())
+            }
+            _ => write_json(f, self),
+        }
+    }
+}
+
+fn write_json(f: &mut fmt::Formatter, value: &Value) -> fmt::Result {
+    match *value {
+        Value::Null => f.write_str("null"),
+        Value::Bool(b) => write!(f, "{}", b),
+        Value::Int(i) => write!(f, "{}", i),
+        // JSON has no NaN and infinities.
+        Value::Float(n) if !n.is_finite() => f.write_str("null"),
+        Value::Float(n) => write!(f, "{:?}", n),
+        Value::String(ref s) => write_json_string(f, s),
+        Value::Timestamp(_) => write!(f, "\"{}\"", value),
+        Value::Array(ref a) => {
+            f.write_str("[")?;
+            for (i, v) in a.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(",")?;
+                }
+                write_json(f, v)?;
+            }
+            f.write_str("]")
+        }
+        Value::Map(ref m) => {
+            f.write_str("{")?;
+            for (i, (k, v)) in m.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(",")?;
+                }
+                write_json_string(f, k)?;
+                f.write_str(":")?;
+                write_json(f, v)?;
+            }
+            f.write_str("}")
+        }
+    }
+}
+
+fn write_json_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            // Only C0 controls have to be escaped, DEL and C1 ones are for readability.
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+/// Converts Logstash-style array index (negative ones count from the end) to a regular one.
+fn index(len: usize, key: &str) -> Option<usize> {
+    let i: i64 = key.parse().ok()?;
+    let i = if i < 0 { len as i64 + i } else { i };
+    if i >= 0 && (i as usize) < len {
+        Some(i as usize)
+    } else {
+        None
+    }
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Bool(v)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Value::Int(v)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Float(v)
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::String(v)
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(v: &'a str) -> Self {
+        Value::String(v.to_string())
+    }
+}
+
+impl From<Timestamp> for Value {
+    fn from(v: Timestamp) -> Self {
+        Value::Timestamp(v)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(v: Vec<Value>) -> Self {
+        Value::Array(v)
+    }
+}
+
+impl From<Map> for Value {
+    fn from(v: Map) -> Self {
+        Value::Map(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let mut map = Map::new();
+        map.insert("a\"b".to_string(), Value::from("x\\y\n\r\t\u{1}\u{7f}é"));
+        map.insert("nan".to_string(), Value::Float(f64::NAN));
+        map.insert("n".to_string(), Value::Array(vec![Value::Float(1.5),
+                                                      Value::Float(f64::INFINITY),
+                                                      Value::Int(-2),
+                                                      Value::Null]));
+        assert_eq!(r#"{"a\"b":"x\\y\n\r\t\u0001\u007fé","n":[1.5,null,-2,null],"nan":null}"#,
+                   Value::Map(map).to_string());
+    }
+}
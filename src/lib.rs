@@ -1,15 +1,25 @@
+extern crate chrono;
+extern crate flate2;
+extern crate glob;
+extern crate libloading;
 #[macro_use]
 extern crate log;
 #[macro_use]
 extern crate nom;
 extern crate regex;
+extern crate tar;
 // extern crate serde;
 // extern crate serde_json;
 
+pub use pipeline::{ArgType, Function, FunctionRegistry, QueueWriter};
 pub use runner::*;
 
-mod config;
+pub mod config;
+pub mod event;
 mod macros;
 mod pipeline;
-mod plugin;
+pub mod plugin;
 mod runner;
+#[cfg(test)]
+mod tempdir;
+pub mod testing;
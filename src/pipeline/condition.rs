@@ -0,0 +1,531 @@
+//! Conditionals of filter and output sections compiled into an executable form.
+
+use std::borrow::{Borrow, Cow};
+use std::cmp::Ordering;
+
+use regex::{Error as RegexError, Regex};
+
+use config::ast::{BoolExpr, BoolOperator, CompareOperator, Condition, InOperator, MatchOperator};
+use config::ast::{Rvalue, Selector};
+use event::{Event, Map, Value};
+use super::function::{Function, FunctionRegistry};
+
+/// A plugin or a conditional routing events to plugins.
+#[derive(Clone, Debug)]
+pub enum Statement<P> {
+    Plugin(P),
+    Branch(Vec<Case<P>>),
+}
+
+impl<P> Statement<P> {
+    /// Counts plugins including the nested ones.
+    pub fn count(&self) -> usize {
+        match self {
+            Statement::Plugin(_) => 1,
+            Statement::Branch(cases) => {
+                cases.iter().map(|c| c.block.iter().map(|s| s.count()).sum::<usize>()).sum()
+            }
+        }
+    }
+}
+
+/// Block of an `if`, `else if` or `else` statement.
+#[derive(Clone, Debug)]
+pub struct Case<P> {
+    pub predicate: Predicate,
+    pub block: Vec<Statement<P>>,
+}
+
+impl<P> Case<P> {
+    pub fn new(predicate: Predicate, block: Vec<Statement<P>>) -> Case<P> {
+        Case { predicate, block }
+    }
+}
+
+/// Sorts events by the first case they match. Events matching no case are returned separately.
+pub fn route<P, E: Borrow<Event>>(cases: &[Case<P>], events: Vec<E>) -> (Vec<Vec<E>>, Vec<E>) {
+    let mut routed: Vec<Vec<E>> = cases.iter().map(|_| vec![]).collect();
+    let mut unmatched = vec![];
+    for event in events {
+        match cases.iter().position(|c| c.predicate.eval(event.borrow())) {
+            Some(i) => routed[i].push(event),
+            None => unmatched.push(event),
+        }
+    }
+    (routed, unmatched)
+}
+
+/// Splits events into runs of consecutive events matching the same case (or none of them).
+///
+/// Unlike `route`, keeps the order of the events across the runs.
+pub fn split_runs<P>(cases: &[Case<P>], events: Vec<Event>) -> Vec<(Option<usize>, Vec<Event>)> {
+    let mut runs: Vec<(Option<usize>, Vec<Event>)> = vec![];
+    for event in events {
+        let case = cases.iter().position(|c| c.predicate.eval(&event));
+        match runs.last_mut() {
+            Some((last, run)) if *last == case => run.push(event),
+            _ => runs.push((case, vec![event])),
+        }
+    }
+    runs
+}
+
+/// Problems found when compiling a condition.
+#[derive(Debug)]
+pub enum ConditionError {
+    InvalidRegex(RegexError),
+    UnknownFunction(String),
+    /// Regex operand which is not a literal, e.g. a field. The parser never produces those,
+    /// but rewritten configs may have them.
+    NonLiteralRegex,
+}
+
+#[derive(Clone, Debug)]
+pub enum Operand {
+    Const(Value),
+    Field(Selector),
+    /// Array literal referring to fields, it is built anew for every event.
+    Array(Vec<Operand>),
+    /// Hash literal referring to fields, it is built anew for every event.
+    Hash(Vec<(String, Operand)>),
+    Call(Function, Vec<Operand>),
+}
+
+impl Operand {
+    fn eval<'a>(&'a self, event: &'a Event) -> Option<Cow<'a, Value>> {
+        let owned = |o: &Operand| o.eval(event).map_or(Value::Null, Cow::into_owned);
+        match self {
+            Operand::Const(v) => Some(Cow::Borrowed(v)),
+            Operand::Field(s) => event.get(s).map(Cow::Borrowed),
+            Operand::Array(items) => {
+                Some(Cow::Owned(Value::Array(items.iter().map(owned).collect())))
+            }
+            Operand::Hash(entries) => {
+                let map = entries.iter().map(|(k, o)| (k.clone(), owned(o))).collect();
+                Some(Cow::Owned(Value::Map(map)))
+            }
+            Operand::Call(function, args) => {
+                let args: Vec<_> = args.iter().map(|a| a.eval(event)).collect();
+                let args: Vec<_> = args.iter().map(|a| a.as_deref()).collect();
+                (function.call)(&args).map(Cow::Owned)
+            }
+        }
+    }
+
+    /// Converts an rvalue, literals consisting of constants only are folded into a constant.
+    fn compile(v: &Rvalue, functions: &FunctionRegistry) -> Result<Operand, ConditionError> {
+        Ok(match v {
+            Rvalue::Number(n) => Operand::Const(Value::Float(*n)),
+            Rvalue::String(s) | Rvalue::Regex(s) => Operand::Const(Value::String(s.clone())),
+            Rvalue::Selector(s) => Operand::Field(s.clone()),
+            Rvalue::Array(items) => {
+                let items = items.iter()
+                    .map(|i| Operand::compile(i, functions))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match items.iter().map(|o| o.as_const().cloned()).collect() {
+                    Some(values) => Operand::Const(Value::Array(values)),
+                    None => Operand::Array(items),
+                }
+            }
+            Rvalue::Hash(entries) => {
+                let entries = entries.iter()
+                    .map(|(k, v)| Ok((k.clone(), Operand::compile(v, functions)?)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let consts: Option<Map> = entries
+                    .iter()
+                    .map(|(k, o)| o.as_const().map(|v| (k.clone(), v.clone())))
+                    .collect();
+                match consts {
+                    Some(map) => Operand::Const(Value::Map(map)),
+                    None => Operand::Hash(entries),
+                }
+            }
+            Rvalue::MethodCall(call) => {
+                let function = functions.get(&call.name)
+                    .ok_or_else(|| ConditionError::UnknownFunction(call.name.clone()))?;
+                let args = call.args
+                    .iter()
+                    .map(|a| Operand::compile(a, functions))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Operand::Call(function.clone(), args)
+            }
+        })
+    }
+
+    fn as_const(&self) -> Option<&Value> {
+        match self {
+            Operand::Const(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Predicate {
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+    Not(Box<Predicate>),
+    Compare(CompareOperator, Operand, Operand),
+    /// The left operand is an element of the right one.
+    In(Operand, Operand),
+    /// The operand is a string matching the regex.
+    Match(Operand, Regex),
+    Truthy(Operand),
+}
+
+impl Predicate {
+    pub fn eval(&self, event: &Event) -> bool {
+        match self {
+            Predicate::And(l, r) => l.eval(event) && r.eval(event),
+            Predicate::Or(l, r) => l.eval(event) || r.eval(event),
+            Predicate::Not(p) => !p.eval(event),
+            Predicate::Compare(op, l, r) => {
+                compare(*op, l.eval(event).as_deref(), r.eval(event).as_deref())
+            }
+            Predicate::In(l, r) => contains(r.eval(event).as_deref(), l.eval(event).as_deref()),
+            Predicate::Match(o, re) => {
+                o.eval(event).as_deref().and_then(|v| v.as_str()).is_some_and(|s| re.is_match(s))
+            }
+            Predicate::Truthy(o) => o.eval(event).is_some_and(|v| v.is_truthy()),
+        }
+    }
+}
+
+impl Predicate {
+    /// Compiles a condition. Functions are resolved and regular expressions are compiled once
+    /// here, so invalid ones are reported before the pipeline starts.
+    pub fn compile(condition: &Condition,
+                   functions: &FunctionRegistry)
+                   -> Result<Predicate, ConditionError> {
+        match condition {
+            Condition::Leaf(expr) => Predicate::compile_expr(expr, functions),
+            Condition::Branch(op, l, r) => {
+                let l = Box::new(Predicate::compile(l, functions)?);
+                let r = Box::new(Predicate::compile(r, functions)?);
+                match op {
+                    BoolOperator::And => Ok(Predicate::And(l, r)),
+                    BoolOperator::Or => Ok(Predicate::Or(l, r)),
+                }
+            }
+        }
+    }
+
+    fn compile_expr(expr: &BoolExpr,
+                    functions: &FunctionRegistry)
+                    -> Result<Predicate, ConditionError> {
+        let not = |p| Predicate::Not(Box::new(p));
+        let operand = |v| Operand::compile(v, functions);
+        Ok(match expr {
+            BoolExpr::Parens(c) => Predicate::compile(c, functions)?,
+            BoolExpr::Negative(e) => not(Predicate::compile_expr(e, functions)?),
+            BoolExpr::Compare(op, l, r) => Predicate::Compare(*op, operand(l)?, operand(r)?),
+            BoolExpr::In(InOperator::In, l, r) => Predicate::In(operand(l)?, operand(r)?),
+            BoolExpr::In(InOperator::NotIn, l, r) => {
+                not(Predicate::In(operand(l)?, operand(r)?))
+            }
+            BoolExpr::Match(op, l, Rvalue::String(re)) |
+            BoolExpr::Match(op, l, Rvalue::Regex(re)) => {
+                let re = Regex::new(re).map_err(ConditionError::InvalidRegex)?;
+                let p = Predicate::Match(operand(l)?, re);
+                match op {
+                    MatchOperator::Match => p,
+                    MatchOperator::NotMatch => not(p),
+                }
+            }
+            BoolExpr::Match(..) => return Err(ConditionError::NonLiteralRegex),
+            BoolExpr::Rvalue(v) => Predicate::Truthy(operand(v)?),
+        })
+    }
+}
+
+/// Compares values the way Logstash does.
+///
+/// Numbers are compared numerically regardless of their representation, strings and
+/// timestamps are ordered naturally. Missing fields and nulls are equal to each other only.
+/// Values of different types are never equal and never ordered.
+fn compare(op: CompareOperator, left: Option<&Value>, right: Option<&Value>) -> bool {
+    let left = left.filter(|v| **v != Value::Null);
+    let right = right.filter(|v| **v != Value::Null);
+    let ordering = match (left, right) {
+        (None, None) => Some(Ordering::Equal),
+        (Some(l), Some(r)) => order(l, r),
+        _ => None,
+    };
+
+    match op {
+        CompareOperator::Eq => ordering == Some(Ordering::Equal),
+        CompareOperator::Ne => ordering != Some(Ordering::Equal),
+        CompareOperator::Lt => ordering == Some(Ordering::Less),
+        CompareOperator::Gt => ordering == Some(Ordering::Greater),
+        CompareOperator::Le => matches!(ordering, Some(Ordering::Less) | Some(Ordering::Equal)),
+        CompareOperator::Ge => {
+            matches!(ordering, Some(Ordering::Greater) | Some(Ordering::Equal))
+        }
+    }
+}
+
+/// Checks membership the way Logstash does.
+///
+/// Arrays contain their elements, strings contain their substrings and maps contain their keys.
+fn contains(container: Option<&Value>, item: Option<&Value>) -> bool {
+    match (container, item) {
+        (Some(Value::Array(items)), Some(item)) => {
+            items.iter().any(|i| order(i, item) == Some(Ordering::Equal))
+        }
+        (Some(Value::String(s)), Some(Value::String(sub))) => s.contains(sub.as_str()),
+        (Some(Value::Map(m)), Some(Value::String(key))) => m.contains_key(key),
+        _ => false,
+    }
+}
+
+fn order(left: &Value, right: &Value) -> Option<Ordering> {
+    if let (Some(l), Some(r)) = (left.as_f64(), right.as_f64()) {
+        return l.partial_cmp(&r);
+    }
+
+    match (left, right) {
+        (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+        (Value::Timestamp(l), Value::Timestamp(r)) => Some(l.cmp(r)),
+        (l, r) if l == r => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::parse::parse;
+    use config::ast::{BranchOrPlugin, Selector};
+    use event::{Event, Value};
+    use super::*;
+
+    /// Compiles the condition of `filter { if <cond> {} }`.
+    fn predicate(cond: &str) -> Predicate {
+        let conf = format!("filter {{ if {} {{}} }}", cond);
+        let config = parse(conf.as_bytes()).unwrap();
+        match config.sections[0].block[0] {
+            BranchOrPlugin::Branch(ref b) => {
+                Predicate::compile(&b.cases[0].condition, &FunctionRegistry::builtin()).unwrap()
+            },
+            _ => panic!("branch expected"),
+        }
+    }
+
+    fn event() -> Event {
+        let mut event = Event::with_message("hello");
+        event.set(&Selector::from("[status]"), Value::Int(200)).unwrap();
+        event.set(&Selector::from("[latency]"), Value::Float(0.5)).unwrap();
+        event.set(&Selector::from("[ok]"), Value::Bool(false)).unwrap();
+        event.set(&Selector::from("[empty]"), Value::from("")).unwrap();
+        event.set(&Selector::from("[nothing]"), Value::Null).unwrap();
+        event.set(&Selector::from("[a][b]"), Value::from("x")).unwrap();
+        event
+    }
+
+    #[test]
+    fn test_truthiness() {
+        let event = event();
+        let cases = vec![
+            ("[message]", true),
+            ("[status]", true),
+            ("[empty]", true),
+            ("[ok]", false),
+            ("[nothing]", false),
+            ("[missing]", false),
+            ("![missing]", true),
+            ("[a][b]", true),
+            ("[a][c]", false),
+            ("0", true),
+            ("\"\"", true),
+        ];
+        for (cond, expected) in cases {
+            assert_eq!(expected, predicate(cond).eval(&event), "{}", cond);
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let event = event();
+        let cases = vec![
+            ("[status] == 200", true),
+            ("[status] == 200.0", true),
+            ("[status] != 200", false),
+            ("[status] == \"200\"", false),
+            ("[status] != \"200\"", true),
+            ("[status] >= 200", true),
+            ("[status] < 300", true),
+            ("[latency] > 0.1", true),
+            ("[latency] <= [status]", true),
+            ("[message] == \"hello\"", true),
+            ("[message] < \"world\"", true),
+            ("[message] > 1", false),
+            ("[message] <= 1", false),
+            ("[missing] == [nothing2]", true),
+            ("[missing] == \"\"", false),
+            ("[nothing] == [missing]", true),
+            ("[a][b] == 'x'", true),
+        ];
+        for (cond, expected) in cases {
+            assert_eq!(expected, predicate(cond).eval(&event), "{}", cond);
+        }
+    }
+
+    #[test]
+    fn test_in() {
+        let mut event = event();
+        let tags = Value::Array(vec![Value::from("error"), Value::from("web")]);
+        event.set(&Selector::from("tags"), tags).unwrap();
+        event.set(&Selector::from("[codes]"), Value::Array(vec![Value::Int(200)])).unwrap();
+
+        let cases = vec![
+            ("\"error\" in [tags]", true),
+            ("'debug' in [tags]", false),
+            ("'debug' not in [tags]", true),
+            ("[status] in [codes]", true),
+            ("200.0 in [codes]", true),
+            ("[latency] not  in [codes]", true),
+            ("'ell' in [message]", true),
+            ("'hello world' in [message]", false),
+            ("'b' in [a]", true),
+            ("'c' in [a]", false),
+            ("'x' in [missing]", false),
+            ("[missing] in [tags]", false),
+            ("[missing] not in [tags]", true),
+            ("'error' in [tags] and 200 in [codes]", true),
+            ("[status] in [500, 502, 503]", false),
+            ("[status] in [200, 204]", true),
+            ("[status] not in [500, 502, 503]", true),
+            ("'hello' in [[message], 'bye']", true),
+            ("[status] in [[codes], 'x']", false),
+            ("'web' in {'web' => 1}", true),
+            ("'status' in {'status' => [status]}", true),
+        ];
+        for (cond, expected) in cases {
+            assert_eq!(expected, predicate(cond).eval(&event), "{}", cond);
+        }
+    }
+
+    #[test]
+    fn test_match() {
+        let event = event();
+        let cases = vec![
+            ("[message] =~ /^hel+o$/", true),
+            ("[message] =~ \"^h\"", true),
+            ("[message] !~ /^H/", true),
+            ("[message] =~ /(?i)^H/", true),
+            ("[a][b] =~ /x/ and [message] !~ 'bye'", true),
+            ("[status] =~ /200/", false),
+            ("[missing] =~ /.*/", false),
+            ("[missing] !~ /.*/", true),
+        ];
+        for (cond, expected) in cases {
+            assert_eq!(expected, predicate(cond).eval(&event), "{}", cond);
+        }
+
+        let mut event = Event::new();
+        event.set(&Selector::from("path"), Value::from("/var/log/a.log")).unwrap();
+        assert!(predicate(r"[path] =~ /^\/var\/log\/.*\.log$/").eval(&event));
+
+        let expr = BoolExpr::Match(MatchOperator::Match,
+                                   Rvalue::Selector(Selector::from("a")),
+                                   Rvalue::Selector(Selector::from("b")));
+        match Predicate::compile(&Condition::Leaf(Box::new(expr)), &FunctionRegistry::builtin()) {
+            Err(ConditionError::NonLiteralRegex) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_functions() {
+        let mut event = event();
+        let tags = Value::Array(vec![Value::from("error"), Value::from("web")]);
+        event.set(&Selector::from("tags"), tags).unwrap();
+        event.set(&Selector::from("level"), Value::from("WARN")).unwrap();
+
+        let cases = vec![
+            ("length([tags]) == 2", true),
+            ("length([tags]) > 2", false),
+            ("length([message]) == 5", true),
+            ("length([status])", false),
+            ("length([missing]) == 0", false),
+            ("length([[tags], 1, 2]) == 3", true),
+            ("lowercase([level]) == \"warn\"", true),
+            ("lowercase([level]) =~ /^w/", true),
+            ("'warn' in lowercase([level])", true),
+            ("lowercase([status]) == '200'", false),
+            ("exists([status])", true),
+            ("exists([nothing])", false),
+            ("!exists([missing])", true),
+            ("length(lowercase([level])) == 4", true),
+        ];
+        for (cond, expected) in cases {
+            assert_eq!(expected, predicate(cond).eval(&event), "{}", cond);
+        }
+
+        let cond = parse(b"filter { if foo([a]) {} }").unwrap();
+        match cond.sections[0].block[0] {
+            BranchOrPlugin::Branch(ref b) => {
+                match Predicate::compile(&b.cases[0].condition, &FunctionRegistry::new()) {
+                    Err(ConditionError::UnknownFunction(name)) => assert_eq!("foo", name),
+                    r => panic!("unexpected result {:?}", r),
+                }
+            }
+            _ => panic!("branch expected"),
+        }
+    }
+
+    #[test]
+    fn test_bool_operators() {
+        let event = event();
+        let cases = vec![
+            ("[status] == 200 and [ok]", false),
+            ("[status] == 200 or [ok]", true),
+            ("!([status] == 200 and [ok])", true),
+            ("[ok] or [missing] or [message] == \"hello\"", true),
+            ("[message] and ([status] < 100 or [latency] < 1)", true),
+        ];
+        for (cond, expected) in cases {
+            assert_eq!(expected, predicate(cond).eval(&event), "{}", cond);
+        }
+    }
+
+    #[test]
+    fn test_route() {
+        let case = |cond, n: u32| Case::new(predicate(cond), vec![Statement::Plugin(n)]);
+        let cases = vec![case("[n] < 2", 1), case("[n] < 4", 2)];
+        let events = (0..6)
+            .map(|n| {
+                let mut e = Event::new();
+                e.set(&Selector::from("n"), Value::Int(n)).unwrap();
+                e
+            })
+            .collect();
+
+        let n = |events: &[Event]| -> Vec<f64> {
+            events.iter().map(|e| e.get(&Selector::from("n")).unwrap().as_f64().unwrap()).collect()
+        };
+        let (routed, unmatched) = route(&cases, events);
+        assert_eq!(vec![0.0, 1.0], n(&routed[0]));
+        assert_eq!(vec![2.0, 3.0], n(&routed[1]));
+        assert_eq!(vec![4.0, 5.0], n(&unmatched));
+        assert_eq!(2, Statement::Branch(cases).count());
+    }
+
+    #[test]
+    fn test_split_runs() {
+        let cases = vec![Case::new(predicate("[n] == 1"), vec![Statement::Plugin(1)])];
+        let events = [0, 1, 1, 2, 3, 1]
+            .iter()
+            .map(|&n| {
+                let mut e = Event::new();
+                e.set(&Selector::from("n"), Value::Int(n)).unwrap();
+                e
+            })
+            .collect();
+
+        let runs: Vec<_> = split_runs(&cases, events)
+            .into_iter()
+            .map(|(case, run)| (case, run.len()))
+            .collect();
+        assert_eq!(vec![(None, 1), (Some(0), 2), (None, 2), (Some(0), 1)], runs);
+    }
+}
@@ -1,9 +1,202 @@
-pub struct FilterSection {
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use event::Event;
+use plugin::FilterPlugin;
+use super::condition::{split_runs, Statement};
+use super::{OutputSection, PipelineSettings, QueueReader};
+
+pub type FilterStatement = Statement<FilterPlugin>;
 
+pub struct FilterSection {
+    filters: Vec<FilterStatement>,
+    workers: Vec<thread::JoinHandle<()>>,
 }
 
 impl FilterSection {
     pub fn new() -> FilterSection {
-        FilterSection {}
+        FilterSection {
+            filters: vec![],
+            workers: vec![],
+        }
+    }
+
+    pub fn add_plugin(&mut self, filter: FilterPlugin) {
+        self.filters.push(Statement::Plugin(filter));
+    }
+
+    pub fn add_statement(&mut self, statement: FilterStatement) {
+        self.filters.push(statement);
+    }
+
+    pub fn count(&self) -> usize {
+        self.filters.iter().map(|s| s.count()).sum()
+    }
+
+    /// Starts pipeline workers. Each of them takes batches of events from the queue, passes
+    /// them through its own copy of the filters and then hands them over to the outputs.
+    pub fn run(&mut self,
+               settings: &PipelineSettings,
+               queue: QueueReader,
+               outputs: Arc<OutputSection>) {
+        for n in 0..settings.workers.max(1) {
+            let mut filters = self.filters.clone();
+            register(&mut filters);
+
+            let mut w = FilterWorker {
+                filters,
+                queue: queue.clone(),
+                outputs: outputs.clone(),
+                batch_size: settings.batch_size.max(1),
+                batch_delay: settings.batch_delay,
+            };
+            // TODO: set thread name to "[#{pipeline_id}]>worker#{n}"
+            self.workers.push(thread::Builder::new()
+                .name(format!("[pipeline_id]>worker{}", n))
+                .spawn(move || w.run())
+                .expect("Cannot start Filter worker"));
+        }
+    }
+
+    pub fn wait(&mut self) {
+        while let Some(w) = self.workers.pop() {
+            if w.join().is_err() {
+                error!("Filter worker panicked");
+            }
+        }
+    }
+}
+
+impl Default for FilterSection {
+    fn default() -> FilterSection {
+        FilterSection::new()
+    }
+}
+
+fn register(filters: &mut [FilterStatement]) {
+    for statement in filters {
+        match statement {
+            Statement::Plugin(filter) => filter.register(),
+            Statement::Branch(cases) => {
+                for case in cases {
+                    register(&mut case.block);
+                }
+            }
+        }
+    }
+}
+
+fn close(filters: &mut [FilterStatement]) {
+    for statement in filters {
+        match statement {
+            Statement::Plugin(filter) => filter.close(),
+            Statement::Branch(cases) => {
+                for case in cases {
+                    close(&mut case.block);
+                }
+            }
+        }
+    }
+}
+
+/// Passes events through the filters. Conditionals split the events between their blocks,
+/// events satisfying no condition skip the conditional. The order of the events is kept.
+fn apply(filters: &mut [FilterStatement], mut events: Vec<Event>) -> Vec<Event> {
+    for statement in filters {
+        if events.is_empty() {
+            break;
+        }
+        match statement {
+            Statement::Plugin(filter) => filter.filter(&mut events),
+            Statement::Branch(cases) => {
+                let mut filtered = Vec::with_capacity(events.len());
+                for (case, mut run) in split_runs(cases, events) {
+                    match case {
+                        Some(i) => filtered.append(&mut apply(&mut cases[i].block, run)),
+                        None => filtered.append(&mut run),
+                    }
+                }
+                events = filtered;
+            }
+        }
+    }
+    events
+}
+
+struct FilterWorker {
+    filters: Vec<FilterStatement>,
+    queue: QueueReader,
+    outputs: Arc<OutputSection>,
+    batch_size: usize,
+    batch_delay: Duration,
+}
+
+impl FilterWorker {
+    pub fn run(&mut self) {
+        while let Some(batch) = self.queue.read_batch(self.batch_size, self.batch_delay) {
+            let _guard = BatchGuard(batch.len());
+            let batch = apply(&mut self.filters, batch);
+            self.outputs.receive(&batch);
+        }
+        close(&mut self.filters);
+    }
+}
+
+/// Reports the events of the batch being processed when the worker panics, they are lost.
+struct BatchGuard(usize);
+
+impl Drop for BatchGuard {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            error!("Filter worker panicked, dropping {} events of its batch", self.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::ast::{BranchOrPlugin, Selector};
+    use config::parse::parse;
+    use event::{Event, Value};
+    use plugin::Filter;
+    use plugin::settings::Settings;
+    use super::*;
+    use super::super::FunctionRegistry;
+    use super::super::condition::{Case, Predicate};
+
+    /// Passes events through as is.
+    #[derive(Clone)]
+    struct Noop;
+
+    impl Filter for Noop {
+        fn filter(&mut self, _events: &mut Vec<Event>) {}
+    }
+
+    #[test]
+    fn test_apply_keeps_order() {
+        let config = parse(b"filter { if [n] == 1 { mutate {} } }").unwrap();
+        let predicate = match config.sections[0].block[0] {
+            BranchOrPlugin::Branch(ref b) => {
+                Predicate::compile(&b.cases[0].condition, &FunctionRegistry::builtin()).unwrap()
+            }
+            _ => panic!("branch expected"),
+        };
+        let block = vec![Statement::Plugin(FilterPlugin::new(Settings::new(), Box::new(Noop)))];
+        let mut filters = vec![Statement::Branch(vec![Case::new(predicate, block)])];
+
+        let n = Selector::from("n");
+        let events = (0..4)
+            .map(|i| {
+                let mut e = Event::new();
+                e.set(&n, Value::Int(i)).unwrap();
+                e
+            })
+            .collect();
+        let numbers: Vec<_> = apply(&mut filters, events)
+            .iter()
+            .map(|e| e.get(&n).unwrap().as_f64().unwrap())
+            .collect();
+        assert_eq!(vec![0.0, 1.0, 2.0, 3.0], numbers);
     }
 }
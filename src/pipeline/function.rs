@@ -0,0 +1,154 @@
+//! Functions callable in conditions, e.g. `if length([tags]) > 2`.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use config::ast::Rvalue;
+use event::Value;
+
+/// Kind of arguments a function parameter accepts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArgType {
+    Any,
+    /// A field reference, e.g. `[tags]`. The field itself may be missing.
+    Field,
+    String,
+    /// A string, an array or a hash.
+    Sized,
+}
+
+impl ArgType {
+    /// Checks an argument as far as its type is known before the pipeline starts.
+    ///
+    /// Values of fields and results of calls are checked when the condition is evaluated.
+    pub fn accepts(&self, arg: &Rvalue) -> bool {
+        match (self, arg) {
+            (ArgType::Any, _) => true,
+            (ArgType::Field, Rvalue::Selector(_)) => true,
+            (ArgType::Field, _) => false,
+            (_, Rvalue::Selector(_)) | (_, Rvalue::MethodCall(_)) => true,
+            (ArgType::String, Rvalue::String(_)) => true,
+            (ArgType::Sized, Rvalue::String(_)) |
+            (ArgType::Sized, Rvalue::Array(_)) |
+            (ArgType::Sized, Rvalue::Hash(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for ArgType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            ArgType::Any => "any value",
+            ArgType::Field => "a field reference",
+            ArgType::String => "a string",
+            ArgType::Sized => "a string, an array or a hash",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Implementation of a function. Arguments referring to missing fields are `None`, so is the
+/// result if the function is not applicable to the arguments given.
+pub type Call = fn(&[Option<&Value>]) -> Option<Value>;
+
+#[derive(Clone, Debug)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<ArgType>,
+    pub call: Call,
+}
+
+impl Function {
+    pub fn new(name: &str, params: Vec<ArgType>, call: Call) -> Function {
+        Function { name: name.to_string(), params, call }
+    }
+}
+
+/// Functions known to the config compiler.
+#[derive(Clone, Debug, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, Function>,
+}
+
+impl FunctionRegistry {
+    /// Creates a registry without any functions.
+    pub fn new() -> FunctionRegistry {
+        FunctionRegistry { functions: HashMap::new() }
+    }
+
+    /// Creates a registry of `length()`, `lowercase()` and `exists()`.
+    pub fn builtin() -> FunctionRegistry {
+        let mut registry = FunctionRegistry::new();
+        registry.register(Function::new("length", vec![ArgType::Sized], length));
+        registry.register(Function::new("lowercase", vec![ArgType::String], lowercase));
+        registry.register(Function::new("exists", vec![ArgType::Field], exists));
+        registry
+    }
+
+    /// Adds a function replacing the one of the same name, if any.
+    pub fn register(&mut self, function: Function) {
+        self.functions.insert(function.name.clone(), function);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name)
+    }
+}
+
+/// Number of characters of a string, elements of an array or entries of a hash.
+fn length(args: &[Option<&Value>]) -> Option<Value> {
+    let len = match args[0]? {
+        Value::String(s) => s.chars().count(),
+        Value::Array(a) => a.len(),
+        Value::Map(m) => m.len(),
+        _ => return None,
+    };
+    Some(Value::Int(len as i64))
+}
+
+fn lowercase(args: &[Option<&Value>]) -> Option<Value> {
+    args[0]?.as_str().map(|s| Value::String(s.to_lowercase()))
+}
+
+/// Tells whether the field is set. Fields set to `null` do not exist, just like in comparisons.
+fn exists(args: &[Option<&Value>]) -> Option<Value> {
+    Some(Value::Bool(args[0].is_some_and(|v| *v != Value::Null)))
+}
+
+#[cfg(test)]
+mod tests {
+    use config::ast::Selector;
+    use super::*;
+
+    #[test]
+    fn test_accepts() {
+        let field = || Rvalue::from(Selector::from("a"));
+        assert!(ArgType::Field.accepts(&field()));
+        assert!(!ArgType::Field.accepts(&Rvalue::from("a")));
+        assert!(ArgType::String.accepts(&field()));
+        assert!(ArgType::String.accepts(&Rvalue::from("a")));
+        assert!(!ArgType::String.accepts(&Rvalue::from(1.0)));
+        assert!(ArgType::Sized.accepts(&Rvalue::from(vec![])));
+        assert!(!ArgType::Sized.accepts(&Rvalue::Regex("a".to_string())));
+        assert!(ArgType::Any.accepts(&Rvalue::from(1.0)));
+    }
+
+    #[test]
+    fn test_builtin() {
+        let registry = FunctionRegistry::builtin();
+        let call = |name, arg: Option<&Value>| (registry.get(name).unwrap().call)(&[arg]);
+
+        let tags = Value::Array(vec![Value::from("a"), Value::from("b")]);
+        assert_eq!(Some(Value::Int(2)), call("length", Some(&tags)));
+        assert_eq!(Some(Value::Int(4)), call("length", Some(&Value::from("żółw"))));
+        assert_eq!(None, call("length", Some(&Value::Int(1))));
+        assert_eq!(None, call("length", None));
+        assert_eq!(Some(Value::from("warn")), call("lowercase", Some(&Value::from("WaRN"))));
+        assert_eq!(None, call("lowercase", Some(&tags)));
+        assert_eq!(Some(Value::Bool(true)), call("exists", Some(&Value::Bool(false))));
+        assert_eq!(Some(Value::Bool(false)), call("exists", Some(&Value::Null)));
+        assert_eq!(Some(Value::Bool(false)), call("exists", None));
+        assert!(registry.get("uppercase").is_none());
+    }
+}
@@ -1,6 +1,7 @@
 use std::thread;
 
-use plugin::InputPlugin;
+use plugin::{InputPlugin, StopSignal};
+use super::QueueWriter;
 
 pub struct InputSection {
     inputs: Vec<InputPlugin>,
@@ -19,7 +20,14 @@ impl InputSection {
         self.inputs.push(input);
     }
 
-    pub fn run(&mut self) {
+    pub fn count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Starts input workers. Every worker gets its own handle of the queue, so the queue
+    /// is closed once all the inputs are done. Inputs check the stop signal on their own, so
+    /// they may take a while to finish once it's raised.
+    pub fn run(&mut self, queue: QueueWriter, stop: &StopSignal) {
         let mut extra_inputs = Vec::new();
         for input in &self.inputs {
             for _k in 1..input.threads_count() {
@@ -30,11 +38,13 @@ impl InputSection {
         self.inputs.append(&mut extra_inputs);
         while let Some(mut input) = self.inputs.pop() {
             input.register();
+            let queue = queue.clone();
+            let stop = stop.clone();
             // TODO: set thread name to "[#{pipeline_id}]<#{plugin.class.config_name}"
             self.workers.push(thread::Builder::new()
                 .name("[pipeline_id]<input_name".to_string())
                 .spawn(move || {
-                    let mut w = InputWorker { input: input };
+                    let mut w = InputWorker { input, queue, stop };
                     w.run();
                 }).expect("Cannot start Input worker"));
         }
@@ -42,24 +52,30 @@ impl InputSection {
 
     pub fn wait(&mut self) {
         while let Some(w) = self.workers.pop() {
-            match w.join() {
-                Err(e) => {
-                    // TODO: log
-                }
-                _ => {},
+            if w.join().is_err() {
+                error!("Input worker panicked");
             }
         }
     }
 }
 
+impl Default for InputSection {
+    fn default() -> InputSection {
+        InputSection::new()
+    }
+}
+
 // TODO: impl Drop for InputSection
 
 struct InputWorker {
     input: InputPlugin,
+    queue: QueueWriter,
+    stop: StopSignal,
 }
 
 impl InputWorker {
     pub fn run(&mut self) {
-        self.input.run();
+        self.input.run(&self.queue, &self.stop);
+        self.input.close();
     }
 }
@@ -1,9 +1,16 @@
+pub use self::condition::{Case, ConditionError, Predicate, Statement};
+pub use self::function::{ArgType, Function, FunctionRegistry};
 pub use self::input_section::InputSection;
-pub use self::filter_section::FilterSection;
-pub use self::output_section::OutputSection;
+pub use self::filter_section::{FilterSection, FilterStatement};
+pub use self::output_section::{OutputSection, OutputStatement};
 pub use self::pipeline::*;
+pub use self::queue::{queue, QueueReader, QueueWriter};
 
+mod condition;
+mod function;
 mod input_section;
 mod filter_section;
 mod output_section;
+#[allow(clippy::module_inception)]
 mod pipeline;
+mod queue;
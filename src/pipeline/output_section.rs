@@ -1,9 +1,100 @@
-pub struct OutputSection {
+use std::sync::{Mutex, PoisonError};
+
+use event::Event;
+use plugin::OutputPlugin;
+use super::condition::{route, Case, Statement};
 
+/// Outputs are shared between all the pipeline workers, so each of them is guarded by a lock.
+pub type OutputStatement = Statement<Mutex<OutputPlugin>>;
+
+pub struct OutputSection {
+    outputs: Vec<OutputStatement>,
 }
 
 impl OutputSection {
     pub fn new() -> OutputSection {
-        OutputSection {}
+        OutputSection { outputs: vec![] }
+    }
+
+    pub fn add_plugin(&mut self, output: OutputPlugin) {
+        self.outputs.push(Statement::Plugin(Mutex::new(output)));
+    }
+
+    pub fn add_statement(&mut self, statement: OutputStatement) {
+        self.outputs.push(statement);
+    }
+
+    pub fn count(&self) -> usize {
+        self.outputs.iter().map(|s| s.count()).sum()
+    }
+
+    pub fn register(&mut self) {
+        register(&mut self.outputs);
+    }
+
+    pub fn close(&mut self) {
+        close(&mut self.outputs);
+    }
+
+    pub fn receive(&self, events: &[Event]) {
+        receive(&self.outputs, events.iter().collect());
+    }
+}
+
+impl Default for OutputSection {
+    fn default() -> OutputSection {
+        OutputSection::new()
+    }
+}
+
+fn register(outputs: &mut [OutputStatement]) {
+    for statement in outputs {
+        match statement {
+            Statement::Plugin(output) => {
+                output.get_mut().unwrap_or_else(PoisonError::into_inner).register()
+            }
+            Statement::Branch(cases) => {
+                for case in cases {
+                    register(&mut case.block);
+                }
+            }
+        }
+    }
+}
+
+fn close(outputs: &mut [OutputStatement]) {
+    for statement in outputs {
+        match statement {
+            Statement::Plugin(output) => {
+                output.get_mut().unwrap_or_else(PoisonError::into_inner).close()
+            }
+            Statement::Branch(cases) => {
+                for case in cases {
+                    close(&mut case.block);
+                }
+            }
+        }
+    }
+}
+
+/// Sends every event to all the outputs of the block whose conditions it satisfies.
+fn receive(outputs: &[OutputStatement], events: Vec<&Event>) {
+    if events.is_empty() {
+        return;
+    }
+    for statement in outputs {
+        match statement {
+            // A panic of another worker in the output doesn't take this one down too.
+            Statement::Plugin(output) => {
+                output.lock().unwrap_or_else(PoisonError::into_inner).receive(&events)
+            }
+            Statement::Branch(cases) => {
+                let (routed, _) = route(cases, events.clone());
+                for (case, events) in cases.iter().zip(routed) {
+                    let Case { block, .. } = case;
+                    receive(block, events);
+                }
+            }
+        }
     }
 }
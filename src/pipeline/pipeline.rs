@@ -1,27 +1,191 @@
-use super::{InputSection, FilterSection, OutputSection};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use plugin::StopSignal;
+use super::{queue, InputSection, FilterSection, OutputSection};
+
+pub const DEFAULT_BATCH_SIZE: usize = 125;
+pub const DEFAULT_BATCH_DELAY_MS: u64 = 50;
+
+/// Tunes pipeline throughput versus latency (see `pipeline.*` settings of Logstash).
+#[derive(Clone, Debug, PartialEq)]
+pub struct PipelineSettings {
+    /// Number of workers executing filters and outputs.
+    pub workers: usize,
+    /// Maximum number of events a worker takes from the queue at once.
+    pub batch_size: usize,
+    /// How long a worker waits for a batch to fill up before processing it.
+    pub batch_delay: Duration,
+}
+
+impl Default for PipelineSettings {
+    fn default() -> PipelineSettings {
+        PipelineSettings {
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            batch_size: DEFAULT_BATCH_SIZE,
+            batch_delay: Duration::from_millis(DEFAULT_BATCH_DELAY_MS),
+        }
+    }
+}
 
 pub struct Pipeline {
+    settings: PipelineSettings,
     inputs: InputSection,
     filters: FilterSection,
-    outputs: OutputSection,
+    outputs: Arc<OutputSection>,
 }
 
 impl Pipeline {
-    pub fn new(inputs: InputSection, filters: FilterSection, outputs: OutputSection) -> Pipeline {
+    pub fn new(settings: PipelineSettings,
+               inputs: InputSection,
+               filters: FilterSection,
+               outputs: OutputSection)
+               -> Pipeline {
         Pipeline {
-            inputs: inputs,
-            filters: filters,
-            outputs: outputs,
+            settings,
+            inputs,
+            filters,
+            outputs: Arc::new(outputs),
         }
     }
 
-    pub fn run(&mut self) {
+    /// Runs the pipeline until all the inputs are done (or stopped) and all the queued events
+    /// are flushed to the outputs.
+    pub fn run(&mut self, stop: &StopSignal) {
         // Starts all the extra threads and waits them too.
-        self.start_workers();
+        self.start_workers(stop);
         self.inputs.wait();
+        self.filters.wait();
+
+        // All the workers are done, so the outputs aren't shared anymore.
+        if let Some(outputs) = Arc::get_mut(&mut self.outputs) {
+            outputs.close();
+        }
+    }
+
+    fn start_workers(&mut self, stop: &StopSignal) {
+        // In-flight events are limited to what the workers can take at once.
+        let (writer, reader) = queue(self.settings.workers * self.settings.batch_size);
+
+        if let Some(outputs) = Arc::get_mut(&mut self.outputs) {
+            outputs.register();
+        }
+        self.filters.run(&self.settings, reader, self.outputs.clone());
+        self.inputs.run(writer, stop);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use event::Event;
+    use plugin::{Filter, FilterPlugin, Input, InputPlugin, Output, OutputPlugin};
+    use plugin::settings::Settings;
+    use super::*;
+    use super::super::QueueWriter;
+
+    /// Produces events until stopped.
+    #[derive(Clone)]
+    struct Endless;
+
+    impl Input for Endless {
+        fn run(&mut self, queue: &QueueWriter, stop: &StopSignal) {
+            while !stop.is_stopped() && queue.push(Event::new()).is_ok() {}
+        }
+    }
+
+    /// Produces a fixed number of events.
+    #[derive(Clone)]
+    struct Finite(usize);
+
+    impl Input for Finite {
+        fn run(&mut self, queue: &QueueWriter, _stop: &StopSignal) {
+            for _ in 0..self.0 {
+                queue.push(Event::new()).unwrap();
+            }
+        }
+    }
+
+    #[derive(Clone)]
+    struct Panicking;
+
+    impl Filter for Panicking {
+        fn filter(&mut self, _events: &mut Vec<Event>) {
+            panic!("filter failed");
+        }
+    }
+
+    /// Fails on the first batch and counts the events of the following ones.
+    struct FailOnce {
+        failed: bool,
+        received: Arc<AtomicUsize>,
+    }
+
+    impl Output for FailOnce {
+        fn receive(&mut self, events: &[&Event]) {
+            if !self.failed {
+                self.failed = true;
+                panic!("output failed");
+            }
+            self.received.fetch_add(events.len(), Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_run_stops_when_inputs_are_done() {
+        let settings = PipelineSettings { workers: 4, ..Default::default() };
+        let mut pipeline = Pipeline::new(settings,
+                                         InputSection::new(),
+                                         FilterSection::new(),
+                                         OutputSection::new());
+        pipeline.run(&StopSignal::new());
     }
 
-    fn start_workers(&mut self) {
-        self.inputs.run();
+    #[test]
+    fn test_stop() {
+        let mut inputs = InputSection::new();
+        inputs.add_plugin(InputPlugin::new(Settings::new(), Box::new(Endless)));
+        let mut pipeline = Pipeline::new(PipelineSettings::default(),
+                                         inputs,
+                                         FilterSection::new(),
+                                         OutputSection::new());
+
+        let stop = StopSignal::new();
+        let stopper = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                stop.stop();
+            })
+        };
+        pipeline.run(&stop);
+        stopper.join().unwrap();
+    }
+
+    #[test]
+    fn test_run_returns_when_workers_panic() {
+        let settings = PipelineSettings { workers: 4, batch_size: 1, ..Default::default() };
+        let mut inputs = InputSection::new();
+        inputs.add_plugin(InputPlugin::new(Settings::new(), Box::new(Endless)));
+        let mut filters = FilterSection::new();
+        filters.add_plugin(FilterPlugin::new(Settings::new(), Box::new(Panicking)));
+        Pipeline::new(settings.clone(), inputs, filters, OutputSection::new())
+            .run(&StopSignal::new());
+
+        // A poisoned output lock doesn't take the other workers down, only the event of the
+        // failed batch is lost.
+        let mut inputs = InputSection::new();
+        inputs.add_plugin(InputPlugin::new(Settings::new(), Box::new(Finite(1000))));
+        let received = Arc::new(AtomicUsize::new(0));
+        let mut outputs = OutputSection::new();
+        outputs.add_plugin(OutputPlugin::new(Settings::new(),
+                                             Box::new(FailOnce {
+                                                 failed: false,
+                                                 received: received.clone(),
+                                             })));
+        Pipeline::new(settings, inputs, FilterSection::new(), outputs).run(&StopSignal::new());
+        assert_eq!(999, received.load(Ordering::SeqCst));
     }
 }
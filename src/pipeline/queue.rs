@@ -0,0 +1,236 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use event::Event;
+
+struct State {
+    events: VecDeque<Event>,
+    writers: usize,
+    readers: usize,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    capacity: usize,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+/// Creates a bounded in-memory queue of events.
+///
+/// Writers block when the queue is full, so slow filters and outputs apply backpressure
+/// to inputs. The queue is closed as soon as all the writers are dropped, then readers
+/// drain the remaining events and stop. Once all the readers are dropped, e.g. because the
+/// pipeline workers panicked, writers are refused instead of blocking forever.
+pub fn queue(capacity: usize) -> (QueueWriter, QueueReader) {
+    assert!(capacity > 0, "queue capacity must be positive");
+
+    let inner = Arc::new(Inner {
+        state: Mutex::new(State {
+            events: VecDeque::with_capacity(capacity),
+            writers: 1,
+            readers: 1,
+        }),
+        capacity,
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+    });
+    (QueueWriter { inner: inner.clone() }, QueueReader { inner })
+}
+
+pub struct QueueWriter {
+    inner: Arc<Inner>,
+}
+
+impl QueueWriter {
+    /// Pushes an event to the queue blocking while the queue is full.
+    ///
+    /// Gives the event back if there are no readers left to take it, inputs should stop then.
+    pub fn push(&self, event: Event) -> Result<(), Event> {
+        let mut state = self.inner.state.lock().unwrap();
+        while state.readers > 0 && state.events.len() >= self.inner.capacity {
+            state = self.inner.not_full.wait(state).unwrap();
+        }
+        if state.readers == 0 {
+            return Err(event);
+        }
+
+        state.events.push_back(event);
+        self.inner.not_empty.notify_one();
+        Ok(())
+    }
+}
+
+impl Clone for QueueWriter {
+    fn clone(&self) -> QueueWriter {
+        self.inner.state.lock().unwrap().writers += 1;
+        QueueWriter { inner: self.inner.clone() }
+    }
+}
+
+impl Drop for QueueWriter {
+    fn drop(&mut self) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.writers -= 1;
+        if state.writers == 0 {
+            // Wake up all the readers to let them notice the queue is closed.
+            self.inner.not_empty.notify_all();
+        }
+    }
+}
+
+pub struct QueueReader {
+    inner: Arc<Inner>,
+}
+
+impl Clone for QueueReader {
+    fn clone(&self) -> QueueReader {
+        self.inner.state.lock().unwrap().readers += 1;
+        QueueReader { inner: self.inner.clone() }
+    }
+}
+
+impl Drop for QueueReader {
+    fn drop(&mut self) {
+        // Readers may be dropped while unwinding a panic, a poisoned state is still usable.
+        let mut state = self.inner.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.readers -= 1;
+        if state.readers == 0 {
+            // Wake up all the writers to let them notice nobody reads the queue anymore.
+            self.inner.not_full.notify_all();
+        }
+    }
+}
+
+impl QueueReader {
+    /// Takes up to `size` events from the queue blocking while the queue is empty.
+    ///
+    /// Once the first event is available, waits at most `delay` for the batch to fill up.
+    /// Returns `None` when the queue is closed and drained.
+    pub fn read_batch(&self, size: usize, delay: Duration) -> Option<Vec<Event>> {
+        let mut state = self.inner.state.lock().unwrap();
+        while state.events.is_empty() {
+            if state.writers == 0 {
+                return None;
+            }
+            state = self.inner.not_empty.wait(state).unwrap();
+        }
+
+        let deadline = Instant::now() + delay;
+        while state.events.len() < size && state.writers > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            state = self.inner.not_empty.wait_timeout(state, deadline - now).unwrap().0;
+        }
+
+        let n = size.min(state.events.len());
+        let batch = state.events.drain(..n).collect();
+        self.inner.not_full.notify_all();
+        Some(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use event::{Event, MESSAGE};
+    use super::*;
+
+    const NO_DELAY: Duration = Duration::from_millis(0);
+
+    fn messages(batch: &[Event]) -> Vec<&str> {
+        batch.iter().map(|e| e.fields()[MESSAGE].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_read_batches() {
+        let (writer, reader) = queue(10);
+        for m in &["a", "b", "c"] {
+            writer.push(Event::with_message(m)).unwrap();
+        }
+        drop(writer);
+
+        assert_eq!(vec!["a", "b"], messages(&reader.read_batch(2, NO_DELAY).unwrap()));
+        assert_eq!(vec!["c"], messages(&reader.read_batch(2, NO_DELAY).unwrap()));
+        assert!(reader.read_batch(2, NO_DELAY).is_none());
+    }
+
+    #[test]
+    fn test_backpressure() {
+        let (writer, reader) = queue(2);
+        let (tx, rx) = mpsc::channel();
+
+        let producer = thread::spawn(move || {
+            for i in 0..3 {
+                writer.push(Event::with_message(&i.to_string())).unwrap();
+                tx.send(i).unwrap();
+            }
+        });
+
+        assert_eq!(Ok(0), rx.recv());
+        assert_eq!(Ok(1), rx.recv());
+        // The queue is full, so the producer is blocked on the third event.
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        assert_eq!(vec!["0"], messages(&reader.read_batch(1, NO_DELAY).unwrap()));
+        assert_eq!(Ok(2), rx.recv());
+
+        producer.join().unwrap();
+        assert_eq!(vec!["1", "2"], messages(&reader.read_batch(5, NO_DELAY).unwrap()));
+        assert!(reader.read_batch(5, NO_DELAY).is_none());
+    }
+
+    #[test]
+    fn test_batch_delay() {
+        let (writer, reader) = queue(10);
+        writer.push(Event::with_message("a")).unwrap();
+
+        let producer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            writer.push(Event::with_message("b")).unwrap();
+            thread::sleep(Duration::from_millis(500));
+            writer.push(Event::with_message("c")).unwrap();
+        });
+
+        // Waits for the batch to fill up, but no longer than the delay.
+        let started = Instant::now();
+        let batch = reader.read_batch(3, Duration::from_millis(200)).unwrap();
+        assert_eq!(vec!["a", "b"], messages(&batch));
+        assert!(started.elapsed() < Duration::from_millis(500));
+
+        producer.join().unwrap();
+        assert_eq!(vec!["c"], messages(&reader.read_batch(3, NO_DELAY).unwrap()));
+    }
+
+    #[test]
+    fn test_close_on_last_writer_drop() {
+        let (writer, reader) = queue(1);
+        let other = writer.clone();
+        drop(writer);
+
+        let consumer = thread::spawn(move || reader.read_batch(1, NO_DELAY).map(|b| b.len()));
+        thread::sleep(Duration::from_millis(50));
+        drop(other);
+
+        assert_eq!(None, consumer.join().unwrap());
+    }
+
+    #[test]
+    fn test_refuse_without_readers() {
+        let (writer, reader) = queue(1);
+        writer.push(Event::with_message("a")).unwrap();
+
+        let producer = thread::spawn(move || writer.push(Event::with_message("b")));
+        thread::sleep(Duration::from_millis(50));
+        drop(reader);
+
+        let refused = producer.join().unwrap().unwrap_err();
+        assert_eq!(vec!["b"], messages(&[refused]));
+    }
+}
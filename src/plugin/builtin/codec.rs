@@ -0,0 +1,112 @@
+use std::mem;
+
+use config::ast::Selector;
+use event::{Event, Template, MESSAGE};
+use plugin::codec::Codec;
+use plugin::settings::Settings;
+
+/// Encodes events with the `format` template, or as just their message if there's none.
+fn encode(format: &Option<Template>, event: &Event) -> String {
+    match format {
+        Some(format) => format.render(event),
+        None => {
+            event.get(&Selector::from(MESSAGE)).map_or_else(String::new, |m| m.to_string())
+        }
+    }
+}
+
+/// Takes every chunk of data as a whole event.
+#[derive(Clone, Debug)]
+pub struct Plain {
+    format: Option<Template>,
+}
+
+impl Plain {
+    pub fn new(settings: &Settings) -> Plain {
+        Plain { format: settings.get_template("format").cloned() }
+    }
+}
+
+impl Codec for Plain {
+    fn decode(&mut self, data: &[u8]) -> Vec<Event> {
+        vec![Event::with_message(&String::from_utf8_lossy(data))]
+    }
+
+    fn encode(&mut self, event: &Event) -> Vec<u8> {
+        encode(&self.format, event).into_bytes()
+    }
+}
+
+/// Splits data into events by `delimiter`, a newline by default.
+#[derive(Clone, Debug)]
+pub struct Line {
+    format: Option<Template>,
+    delimiter: String,
+    buffer: Vec<u8>,
+}
+
+impl Line {
+    pub fn new(settings: &Settings) -> Line {
+        Line {
+            format: settings.get_template("format").cloned(),
+            delimiter: settings.get_str("delimiter")
+                .filter(|d| !d.is_empty())
+                .unwrap_or("\n")
+                .to_string(),
+            buffer: vec![],
+        }
+    }
+}
+
+impl Codec for Line {
+    fn decode(&mut self, data: &[u8]) -> Vec<Event> {
+        self.buffer.extend_from_slice(data);
+        let delimiter = self.delimiter.as_bytes();
+        let mut events = vec![];
+        let mut start = 0;
+        while let Some(pos) = find(&self.buffer[start..], delimiter) {
+            let line = &self.buffer[start..start + pos];
+            events.push(Event::with_message(&String::from_utf8_lossy(line)));
+            start += pos + delimiter.len();
+        }
+        self.buffer.drain(..start);
+        events
+    }
+
+    fn flush(&mut self) -> Vec<Event> {
+        if self.buffer.is_empty() {
+            return vec![];
+        }
+        let rest = mem::take(&mut self.buffer);
+        vec![Event::with_message(&String::from_utf8_lossy(&rest))]
+    }
+
+    fn encode(&mut self, event: &Event) -> Vec<u8> {
+        let mut line = encode(&self.format, event);
+        line.push_str(&self.delimiter);
+        line.into_bytes()
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(events: Vec<Event>) -> Vec<String> {
+        events.iter().map(|e| e.get(&Selector::from(MESSAGE)).unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_line() {
+        let mut codec = Line::new(&Settings::new());
+        assert_eq!(vec!["a", "b"], messages(codec.decode(b"a\nb\nc")));
+        assert_eq!(vec!["cd"], messages(codec.decode(b"d\n")));
+        assert_eq!(Vec::<String>::new(), messages(codec.decode(b"e")));
+        assert_eq!(vec!["e"], messages(codec.flush()));
+        assert_eq!(b"x\n".to_vec(), codec.encode(&Event::with_message("x")));
+    }
+}
@@ -0,0 +1,146 @@
+use config::ast::Selector;
+use event::{Event, Template, Value};
+use plugin::filter::Filter;
+use plugin::settings::{SettingValue, Settings};
+
+/// Drops all the events.
+#[derive(Clone, Debug, Default)]
+pub struct DropEvents;
+
+impl DropEvents {
+    pub fn new(_settings: &Settings) -> DropEvents {
+        DropEvents
+    }
+}
+
+impl Filter for DropEvents {
+    fn filter(&mut self, events: &mut Vec<Event>) {
+        events.clear();
+    }
+}
+
+/// Modifies fields of the events: renames them, replaces their values with templates and
+/// changes the case of strings, in that order.
+#[derive(Clone, Debug)]
+pub struct Mutate {
+    rename: Vec<(Selector, Selector)>,
+    /// Values are templates, the ones which don't parse are kept verbatim.
+    replace: Vec<(Selector, String, Option<Template>)>,
+    uppercase: Vec<Selector>,
+    lowercase: Vec<Selector>,
+}
+
+impl Mutate {
+    pub fn new(settings: &Settings) -> Mutate {
+        let hash = |name| {
+            settings.get_hash(name).map_or(vec![], |h| {
+                h.iter()
+                    .filter_map(|(k, v)| match v {
+                        SettingValue::String(s) => Some((Selector::from(k.as_str()), s.clone())),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+        let fields = |name| {
+            settings.get_strings(name).into_iter().map(Selector::from).collect::<Vec<_>>()
+        };
+        Mutate {
+            rename: hash("rename")
+                .into_iter()
+                .map(|(from, to)| (from, Selector::from(to.as_str())))
+                .collect(),
+            replace: hash("replace")
+                .into_iter()
+                .map(|(field, raw)| {
+                    let template = Template::parse(&raw).ok();
+                    (field, raw, template)
+                })
+                .collect(),
+            uppercase: fields("uppercase"),
+            lowercase: fields("lowercase"),
+        }
+    }
+
+    fn mutate(&self, event: &mut Event) {
+        for (from, to) in &self.rename {
+            if let Ok(Some(value)) = event.remove(from) {
+                let _ = event.set(to, value);
+            }
+        }
+        for (field, raw, template) in &self.replace {
+            let value = template.as_ref().map_or_else(|| raw.clone(), |t| t.render(event));
+            let _ = event.set(field, Value::from(value));
+        }
+        for field in &self.uppercase {
+            change_case(event, field, str::to_uppercase);
+        }
+        for field in &self.lowercase {
+            change_case(event, field, str::to_lowercase);
+        }
+    }
+}
+
+/// Changes the case of a string field or of all the strings in an array field.
+fn change_case(event: &mut Event, field: &Selector, f: fn(&str) -> String) {
+    match event.get_mut(field) {
+        Some(Value::String(s)) => *s = f(s),
+        Some(Value::Array(values)) => {
+            for v in values {
+                if let Value::String(s) = v {
+                    *s = f(s);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+impl Filter for Mutate {
+    fn filter(&mut self, events: &mut Vec<Event>) {
+        for event in events {
+            self.mutate(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::ast::{Attribute, HashEntry, Value as AstValue};
+    use plugin::settings::{Schema, Setting, SettingType};
+    use super::*;
+
+    fn hash(entries: &[(&str, &str)]) -> AstValue {
+        AstValue::Hash(entries.iter()
+            .map(|&(k, v)| HashEntry::new(k.to_string(), AstValue::String(v.to_string())))
+            .collect())
+    }
+
+    #[test]
+    fn test_mutate() {
+        let schema = Schema::filter()
+            .setting(Setting::new("rename", SettingType::Hash))
+            .setting(Setting::new("replace", SettingType::Hash))
+            .setting(Setting::new("lowercase", SettingType::Array))
+            .setting(Setting::new("uppercase", SettingType::Array));
+        let attributes = vec![
+            Attribute::new("rename".to_string(), hash(&[("msg", "message")])),
+            Attribute::new("replace".to_string(), hash(&[("summary", "%{level}: %{message}")])),
+            Attribute::new("uppercase".to_string(), AstValue::from("level")),
+            Attribute::new("lowercase".to_string(), AstValue::from("message")),
+        ];
+        let mut mutate = Mutate::new(&schema.validate(&attributes).unwrap());
+
+        let mut event = Event::new();
+        event.set(&Selector::from("msg"), Value::from("Disk Full")).unwrap();
+        event.set(&Selector::from("level"), Value::from("warn")).unwrap();
+        let mut events = vec![event];
+        mutate.filter(&mut events);
+
+        let field = |name| events[0].get(&Selector::from(name)).cloned();
+        assert_eq!(None, field("msg"));
+        assert_eq!(Some(Value::from("disk full")), field("message"));
+        assert_eq!(Some(Value::from("WARN")), field("level"));
+        assert_eq!(Some(Value::from("warn: Disk Full")), field("summary"));
+    }
+}
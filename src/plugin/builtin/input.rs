@@ -0,0 +1,127 @@
+use std::io::{self, BufRead};
+
+use config::ast::Selector;
+use event::{Event, Value};
+use pipeline::QueueWriter;
+use plugin::input::{Decorator, Input, StopSignal};
+use plugin::settings::Settings;
+
+/// Reads events from the standard input, one per line.
+#[derive(Clone, Debug)]
+pub struct Stdin {
+    decorator: Decorator,
+}
+
+impl Stdin {
+    pub fn new(settings: &Settings) -> Stdin {
+        Stdin { decorator: Decorator::new(settings) }
+    }
+}
+
+impl Input for Stdin {
+    fn run(&mut self, queue: &QueueWriter, stop: &StopSignal) {
+        let stdin = io::stdin();
+        // Reading blocks, so the stop signal is only noticed between lines.
+        for line in stdin.lock().lines() {
+            if stop.is_stopped() {
+                break;
+            }
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    error!("Cannot read stdin: {}", e);
+                    break;
+                }
+            };
+            let mut event = Event::with_message(&line);
+            self.decorator.decorate(&mut event);
+            if queue.push(event).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Generates the same events over and over again, mostly for testing.
+///
+/// Produces `message` or all the `lines` in turn `count` times, forever if `count` is zero.
+#[derive(Clone, Debug)]
+pub struct Generator {
+    decorator: Decorator,
+    lines: Vec<String>,
+    count: u64,
+}
+
+impl Generator {
+    pub fn new(settings: &Settings) -> Generator {
+        let mut lines: Vec<_> =
+            settings.get_strings("lines").into_iter().map(String::from).collect();
+        if lines.is_empty() {
+            lines.push(settings.get_str("message").unwrap_or_default().to_string());
+        }
+        Generator {
+            decorator: Decorator::new(settings),
+            lines,
+            count: settings.get_number("count").map_or(0, |n| n.max(0.0) as u64),
+        }
+    }
+}
+
+impl Input for Generator {
+    fn run(&mut self, queue: &QueueWriter, stop: &StopSignal) {
+        let sequence = Selector::from("sequence");
+        let mut n = 0;
+        while (self.count == 0 || n < self.count) && !stop.is_stopped() {
+            for line in &self.lines {
+                let mut event = Event::with_message(line);
+                let _ = event.set(&sequence, Value::Int(n as i64));
+                self.decorator.decorate(&mut event);
+                if queue.push(event).is_err() {
+                    return;
+                }
+            }
+            n += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::ast::{Attribute, Value as AstValue};
+    use pipeline::queue;
+    use plugin::settings::{Schema, Setting, SettingType, SettingValue};
+    use super::*;
+
+    #[test]
+    fn test_generator() {
+        let schema = Schema::input()
+            .setting(Setting::new("message", SettingType::String)
+                .default(SettingValue::String("Hello world!".to_string())))
+            .setting(Setting::new("lines", SettingType::Array))
+            .setting(Setting::new("count", SettingType::Number));
+        let attributes = vec![
+            Attribute::new("lines".to_string(),
+                           AstValue::Array(vec![AstValue::from("a"), AstValue::from("b")])),
+            Attribute::new("count".to_string(), AstValue::Number(2.0)),
+            Attribute::new("type".to_string(), AstValue::from("test")),
+        ];
+        let settings = schema.validate(&attributes).unwrap();
+
+        let (writer, reader) = queue(10);
+        Generator::new(&settings).run(&writer, &StopSignal::new());
+        drop(writer);
+
+        let events = reader.read_batch(10, Default::default()).unwrap();
+        let fields = |e: &Event| {
+            (e.get(&Selector::from("message")).unwrap().to_string(),
+             e.get(&Selector::from("sequence")).unwrap().to_string(),
+             e.get(&Selector::from("type")).unwrap().to_string())
+        };
+        let fields: Vec<_> = events.iter().map(fields).collect();
+        let expected = [("a", "0"), ("b", "0"), ("a", "1"), ("b", "1")];
+        assert_eq!(expected.iter()
+                       .map(|&(m, n)| (m.to_string(), n.to_string(), "test".to_string()))
+                       .collect::<Vec<_>>(),
+                   fields);
+    }
+}
@@ -0,0 +1,11 @@
+//! Plugins shipped with echelon0.
+
+pub use self::codec::{Line, Plain};
+pub use self::filter::{DropEvents, Mutate};
+pub use self::input::{Generator, Stdin};
+pub use self::output::{File, Null, Stdout};
+
+mod codec;
+mod filter;
+mod input;
+mod output;
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Component, Path};
+use std::time::{Duration, Instant};
+
+use event::{Event, Template};
+use plugin::output::Output;
+use plugin::settings::Settings;
+
+/// Writes events to the standard output as JSON lines.
+#[derive(Debug, Default)]
+pub struct Stdout;
+
+impl Stdout {
+    pub fn new(_settings: &Settings) -> Stdout {
+        Stdout
+    }
+}
+
+impl Output for Stdout {
+    fn receive(&mut self, events: &[&Event]) {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        for event in events {
+            if let Err(e) = writeln!(out, "{}", event.to_json()) {
+                error!("Cannot write to stdout: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+/// Discards all the events.
+#[derive(Debug, Default)]
+pub struct Null;
+
+impl Null {
+    pub fn new(_settings: &Settings) -> Null {
+        Null
+    }
+}
+
+impl Output for Null {
+    fn receive(&mut self, _events: &[&Event]) {}
+}
+
+/// Appends events as JSON lines to files at `path`, which may refer to event fields.
+///
+/// Missing directories are created. Files are kept open while they are written to and closed
+/// after `IDLE_TIMEOUT` without events. Events the path of which leaves the directory given
+/// before the first field reference (e.g. a field value with `..`) are not written.
+#[derive(Debug)]
+pub struct File {
+    path: Template,
+    /// Static part of `path` up to its last `/` before the first field reference.
+    root: String,
+    files: HashMap<String, OpenFile>,
+    idle_timeout: Duration,
+}
+
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+struct OpenFile {
+    writer: BufWriter<fs::File>,
+    used: Instant,
+}
+
+impl File {
+    pub fn new(settings: &Settings) -> File {
+        let path = settings.get_template("path").expect("`path` is required").clone();
+        File::with_path(path, IDLE_TIMEOUT)
+    }
+
+    fn with_path(path: Template, idle_timeout: Duration) -> File {
+        let source = path.to_string();
+        let dynamic = source.find("%{").unwrap_or(source.len());
+        let root = source[..dynamic].rfind('/').map_or("", |i| &source[..=i]).to_string();
+        File { path, root, files: HashMap::new(), idle_timeout }
+    }
+
+    /// Tells whether the rendered path stays inside `root`, i.e. field values only add normal
+    /// path components to it.
+    fn is_inside_root(&self, path: &str) -> bool {
+        self.path.is_constant() ||
+        Path::new(&path[self.root.len()..])
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    }
+
+    fn write(&mut self, path: String, event: &Event) -> io::Result<()> {
+        if !self.files.contains_key(&path) {
+            if let Some(dir) = Path::new(&path).parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let file = OpenOptions::new().create(true).append(true).open(&path)?;
+            let writer = BufWriter::new(file);
+            self.files.insert(path.clone(), OpenFile { writer, used: Instant::now() });
+        }
+        let file = self.files.get_mut(&path).unwrap();
+        file.used = Instant::now();
+        writeln!(file.writer, "{}", event.to_json())
+    }
+}
+
+impl Output for File {
+    fn receive(&mut self, events: &[&Event]) {
+        for event in events {
+            let path = self.path.render(event);
+            if !self.is_inside_root(&path) {
+                error!("Not writing to {} as it is outside of {:?}", path, self.root);
+                continue;
+            }
+            if let Err(e) = self.write(path.clone(), event) {
+                error!("Cannot write to {}: {}", path, e);
+            }
+        }
+        for (path, file) in &mut self.files {
+            if let Err(e) = file.writer.flush() {
+                error!("Cannot write to {}: {}", path, e);
+            }
+        }
+        let idle_timeout = self.idle_timeout;
+        self.files.retain(|_, file| file.used.elapsed() < idle_timeout);
+    }
+
+    fn close(&mut self) {
+        self.files.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::ast::Selector;
+    use event::Value;
+    use tempdir::TempDir;
+    use super::*;
+
+    fn events(field: &str, values: &[&str]) -> Vec<Event> {
+        values.iter()
+            .map(|v| {
+                let mut event = Event::with_message(v);
+                event.set(&Selector::from(field), Value::from(*v)).unwrap();
+                event
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_file() {
+        let dir = TempDir::new("file");
+        let path = format!("{}/%{{level}}/out.log", dir.path().display());
+        let mut file = File::with_path(Template::parse(&path).unwrap(), IDLE_TIMEOUT);
+
+        let events = events("level", &["info", "error", "info"]);
+        file.receive(&events.iter().collect::<Vec<_>>());
+        assert_eq!(2, file.files.len());
+        file.close();
+
+        let read = |level| fs::read_to_string(dir.join(level).join("out.log")).unwrap();
+        assert_eq!(2, read("info").lines().count());
+        assert_eq!(format!("{}\n", events[1].to_json()), read("error"));
+    }
+
+    #[test]
+    fn test_file_closes_idle_files() {
+        let dir = TempDir::new("file-idle");
+        let path = format!("{}/%{{level}}.log", dir.path().display());
+        let mut file = File::with_path(Template::parse(&path).unwrap(), Duration::from_secs(0));
+
+        let events = events("level", &["info"]);
+        file.receive(&events.iter().collect::<Vec<_>>());
+        assert!(file.files.is_empty());
+        assert_eq!(1, fs::read_to_string(dir.join("info.log")).unwrap().lines().count());
+    }
+
+    #[test]
+    fn test_file_refuses_paths_outside_root() {
+        let dir = TempDir::new("file-root");
+        let path = format!("{}/logs/%{{name}}.log", dir.path().display());
+        let mut file = File::with_path(Template::parse(&path).unwrap(), IDLE_TIMEOUT);
+
+        let events = events("name", &["../escaped", "a/../../escaped", "a/./b"]);
+        file.receive(&events.iter().collect::<Vec<_>>());
+        file.close();
+        assert!(!dir.join("escaped.log").exists());
+        assert!(dir.join("logs/a/b.log").exists());
+    }
+}
@@ -0,0 +1,28 @@
+use event::Event;
+
+/// Converts between raw data and events, e.g. JSON lines or multiline logs.
+///
+/// Inputs decode the data they read and outputs encode events before writing them. Every
+/// plugin gets its own copy of the codec, so codecs may buffer data between calls.
+pub trait Codec: CodecClone + Send {
+    /// Turns a chunk of data into events. Incomplete data may be kept until the next chunk.
+    fn decode(&mut self, data: &[u8]) -> Vec<Event>;
+
+    /// Returns events from the data kept by `decode`, e.g. once the input is exhausted.
+    fn flush(&mut self) -> Vec<Event> {
+        vec![]
+    }
+
+    fn encode(&mut self, event: &Event) -> Vec<u8>;
+}
+
+/// Makes copies of boxed codecs. Implemented for every `Clone` codec.
+pub trait CodecClone {
+    fn clone_box(&self) -> Box<dyn Codec>;
+}
+
+impl<T: Codec + Clone + 'static> CodecClone for T {
+    fn clone_box(&self) -> Box<dyn Codec> {
+        Box::new(self.clone())
+    }
+}
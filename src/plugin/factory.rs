@@ -1,29 +1,285 @@
-use super::input::InputPlugin;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
 
+use super::builtin;
+use super::codec::Codec;
+use super::filter::Filter;
+use super::input::Input;
+use super::output::Output;
+use super::settings::{Schema, Setting, Settings, SettingType, SettingValue};
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Error {
-    PluginNotFound,
+    /// No plugin of the kind is registered under the name. Carries the registered names
+    /// which look like a misspelling of it.
+    PluginNotFound { name: String, suggestions: Vec<String> },
 }
 
-// TODO: impl From and std::error::Error for Error
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::PluginNotFound { name, suggestions } => {
+                write!(f, "plugin `{}` is not found{}", name, did_you_mean(suggestions))
+            }
+        }
+    }
+}
+
+impl error::Error for Error {}
 
 pub type Result<T> = ::std::result::Result<T, Error>;
 
+/// Formats suggestions as a hint to append to an error message, e.g.
+/// ``, did you mean `mutate`?``. Returns an empty string if there are no suggestions.
+pub fn did_you_mean(suggestions: &[String]) -> String {
+    let quoted: Vec<_> = suggestions.iter().map(|s| format!("`{}`", s)).collect();
+    match quoted.split_last() {
+        None => String::new(),
+        Some((last, [])) => format!(", did you mean {}?", last),
+        Some((last, init)) => format!(", did you mean {} or {}?", init.join(", "), last),
+    }
+}
+
 pub trait PluginProvider {
-    fn create_input(&self, name: &str) -> Result<InputPlugin>;
+    fn input_schema(&self, name: &str) -> Result<Schema>;
+
+    fn filter_schema(&self, name: &str) -> Result<Schema>;
+
+    fn output_schema(&self, name: &str) -> Result<Schema>;
+
+    fn codec_schema(&self, name: &str) -> Result<Schema>;
+
+    /// Creates a plugin from settings validated against its schema.
+    fn create_input(&self, name: &str, settings: &Settings) -> Result<Box<dyn Input>>;
+
+    fn create_filter(&self, name: &str, settings: &Settings) -> Result<Box<dyn Filter>>;
+
+    fn create_output(&self, name: &str, settings: &Settings) -> Result<Box<dyn Output>>;
+
+    fn create_codec(&self, name: &str, settings: &Settings) -> Result<Box<dyn Codec>>;
 }
 
+pub type InputConstructor = fn(&Settings) -> Box<dyn Input>;
+pub type FilterConstructor = fn(&Settings) -> Box<dyn Filter>;
+pub type OutputConstructor = fn(&Settings) -> Box<dyn Output>;
+pub type CodecConstructor = fn(&Settings) -> Box<dyn Codec>;
+
+/// Plugins of one kind by name.
+struct Registry<C> {
+    plugins: HashMap<String, (Schema, C)>,
+}
+
+impl<C: Copy> Registry<C> {
+    fn new() -> Registry<C> {
+        Registry { plugins: HashMap::new() }
+    }
+
+    fn get(&self, name: &str) -> Result<&(Schema, C)> {
+        self.plugins.get(name).ok_or_else(|| {
+            Error::PluginNotFound {
+                name: name.to_string(),
+                suggestions: suggestions(name, self.plugins.keys()),
+            }
+        })
+    }
+
+    fn schema(&self, name: &str) -> Result<Schema> {
+        self.get(name).map(|(schema, _)| schema.clone())
+    }
+
+    fn constructor(&self, name: &str) -> Result<C> {
+        self.get(name).map(|&(_, constructor)| constructor)
+    }
+}
+
+/// Picks the names within a few typos of the requested one, the closest first.
+fn suggestions<'a, I: Iterator<Item = &'a String>>(name: &str, names: I) -> Vec<String> {
+    let max = (name.chars().count() / 3).max(1);
+    let mut close: Vec<_> = names.map(|n| (distance(name, n), n))
+        .filter(|&(d, _)| d <= max)
+        .collect();
+    close.sort();
+    close.into_iter().map(|(_, n)| n.clone()).collect()
+}
+
+/// Levenshtein distance between two strings.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = prev + if ca == cb { 0 } else { 1 };
+            prev = row[j + 1];
+            row[j + 1] = substitution.min(prev + 1).min(row[j] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Registry of plugins the config compiler can create.
+///
+/// Built-in and third-party plugins alike register a settings schema and a constructor under
+/// a name for each kind.
 pub struct PluginFactory {
+    inputs: Registry<InputConstructor>,
+    filters: Registry<FilterConstructor>,
+    outputs: Registry<OutputConstructor>,
+    codecs: Registry<CodecConstructor>,
+}
 
+impl Default for PluginFactory {
+    fn default() -> PluginFactory {
+        PluginFactory::new()
+    }
 }
 
 impl PluginFactory {
+    /// Creates a factory without any plugins.
     pub fn new() -> PluginFactory {
-        PluginFactory {}
+        PluginFactory {
+            inputs: Registry::new(),
+            filters: Registry::new(),
+            outputs: Registry::new(),
+            codecs: Registry::new(),
+        }
+    }
+
+    /// Creates a factory of the plugins shipped with echelon0.
+    pub fn builtin() -> PluginFactory {
+        let mut factory = PluginFactory::new();
+
+        factory.register_input("stdin", Schema::input(), |s| Box::new(builtin::Stdin::new(s)));
+        factory.register_input("generator",
+                               Schema::input()
+                                   .setting(Setting::new("message", SettingType::String)
+                                       .default(SettingValue::String("Hello world!".to_string())))
+                                   .setting(Setting::new("lines", SettingType::Array))
+                                   .setting(Setting::new("count", SettingType::Number)
+                                       .default(SettingValue::Number(0.0))),
+                               |s| Box::new(builtin::Generator::new(s)));
+
+        factory.register_filter("drop", Schema::filter(), |s| {
+            Box::new(builtin::DropEvents::new(s))
+        });
+        factory.register_filter("mutate",
+                                Schema::filter()
+                                    .setting(Setting::new("rename", SettingType::Hash))
+                                    .setting(Setting::new("replace", SettingType::Hash))
+                                    .setting(Setting::new("lowercase", SettingType::Array))
+                                    .setting(Setting::new("uppercase", SettingType::Array)),
+                                |s| Box::new(builtin::Mutate::new(s)));
+
+        factory.register_output("stdout", Schema::output(), |s| {
+            Box::new(builtin::Stdout::new(s))
+        });
+        factory.register_output("null", Schema::output(), |s| Box::new(builtin::Null::new(s)));
+        factory.register_output("file",
+                                Schema::output()
+                                    .setting(Setting::new("path", SettingType::Template)
+                                        .required()),
+                                |s| Box::new(builtin::File::new(s)));
+
+        let format = || Setting::new("format", SettingType::Template);
+        factory.register_codec("plain",
+                               Schema::new().setting(format()),
+                               |s| Box::new(builtin::Plain::new(s)));
+        factory.register_codec("line",
+                               Schema::new()
+                                   .setting(format())
+                                   .setting(Setting::new("delimiter", SettingType::String)),
+                               |s| Box::new(builtin::Line::new(s)));
+        factory
+    }
+
+    /// Adds an input plugin replacing the one of the same name, if any.
+    pub fn register_input(&mut self, name: &str, schema: Schema, constructor: InputConstructor) {
+        self.inputs.plugins.insert(name.to_string(), (schema, constructor));
+    }
+
+    pub fn register_filter(&mut self,
+                           name: &str,
+                           schema: Schema,
+                           constructor: FilterConstructor) {
+        self.filters.plugins.insert(name.to_string(), (schema, constructor));
+    }
+
+    pub fn register_output(&mut self,
+                           name: &str,
+                           schema: Schema,
+                           constructor: OutputConstructor) {
+        self.outputs.plugins.insert(name.to_string(), (schema, constructor));
+    }
+
+    pub fn register_codec(&mut self, name: &str, schema: Schema, constructor: CodecConstructor) {
+        self.codecs.plugins.insert(name.to_string(), (schema, constructor));
     }
 }
 
 impl PluginProvider for PluginFactory {
-    fn create_input(&self, name: &str) -> Result<InputPlugin> {
-        Ok(InputPlugin {})
+    fn input_schema(&self, name: &str) -> Result<Schema> {
+        self.inputs.schema(name)
+    }
+
+    fn filter_schema(&self, name: &str) -> Result<Schema> {
+        self.filters.schema(name)
+    }
+
+    fn output_schema(&self, name: &str) -> Result<Schema> {
+        self.outputs.schema(name)
+    }
+
+    fn codec_schema(&self, name: &str) -> Result<Schema> {
+        self.codecs.schema(name)
+    }
+
+    fn create_input(&self, name: &str, settings: &Settings) -> Result<Box<dyn Input>> {
+        self.inputs.constructor(name).map(|c| c(settings))
+    }
+
+    fn create_filter(&self, name: &str, settings: &Settings) -> Result<Box<dyn Filter>> {
+        self.filters.constructor(name).map(|c| c(settings))
+    }
+
+    fn create_output(&self, name: &str, settings: &Settings) -> Result<Box<dyn Output>> {
+        self.outputs.constructor(name).map(|c| c(settings))
+    }
+
+    fn create_codec(&self, name: &str, settings: &Settings) -> Result<Box<dyn Codec>> {
+        self.codecs.constructor(name).map(|c| c(settings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(0, distance("mutate", "mutate"));
+        assert_eq!(1, distance("mutat", "mutate"));
+        assert_eq!(2, distance("stdout", "sdtout"));
+        assert_eq!(3, distance("", "abc"));
+    }
+
+    #[test]
+    fn test_not_found() {
+        let mut factory = PluginFactory::builtin();
+        factory.register_output("stdout2", Schema::output(), |s| Box::new(builtin::Null::new(s)));
+
+        let err = factory.create_output("stdout1", &Settings::new()).err().unwrap();
+        assert_eq!(Error::PluginNotFound {
+                       name: "stdout1".to_string(),
+                       suggestions: vec!["stdout".to_string(), "stdout2".to_string()],
+                   },
+                   err);
+        assert_eq!("plugin `stdout1` is not found, did you mean `stdout` or `stdout2`?",
+                   err.to_string());
+
+        let err = factory.filter_schema("grok").err().unwrap();
+        assert_eq!("plugin `grok` is not found", err.to_string());
+        assert!(factory.create_codec("line", &Settings::new()).is_ok());
     }
 }
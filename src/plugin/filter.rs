@@ -1,3 +1,72 @@
+use std::fmt;
+
+use event::Event;
+use super::settings::Settings;
+
+/// Transformation of events, e.g. parsing, enrichment or dropping.
+///
+/// Every pipeline worker gets its own copy of the filters, so filters have to be `Clone` and
+/// may keep per-worker state without locking. The pipeline calls `register` on each copy
+/// before the first batch and `close` after the last one.
+pub trait Filter: FilterClone + Send {
+    fn register(&mut self) {}
+
+    /// Processes a batch of events in place. Filters are free to drop or add events.
+    fn filter(&mut self, events: &mut Vec<Event>);
+
+    fn close(&mut self) {}
+}
+
+/// Makes copies of boxed filters for workers. Implemented for every `Clone` filter.
+pub trait FilterClone {
+    fn clone_box(&self) -> Box<dyn Filter>;
+}
+
+impl<T: Filter + Clone + 'static> FilterClone for T {
+    fn clone_box(&self) -> Box<dyn Filter> {
+        Box::new(self.clone())
+    }
+}
+
+/// Filter along with its settings as the pipeline runs it.
 pub struct FilterPlugin {
+    settings: Settings,
+    filter: Box<dyn Filter>,
+}
+
+impl Clone for FilterPlugin {
+    fn clone(&self) -> FilterPlugin {
+        FilterPlugin {
+            settings: self.settings.clone(),
+            filter: self.filter.clone_box(),
+        }
+    }
+}
+
+impl fmt::Debug for FilterPlugin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FilterPlugin").field("settings", &self.settings).finish()
+    }
+}
+
+impl FilterPlugin {
+    pub fn new(settings: Settings, filter: Box<dyn Filter>) -> FilterPlugin {
+        FilterPlugin { settings, filter }
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn register(&mut self) {
+        self.filter.register();
+    }
+
+    pub fn filter(&mut self, events: &mut Vec<Event>) {
+        self.filter.filter(events);
+    }
 
+    pub fn close(&mut self) {
+        self.filter.close();
+    }
 }
@@ -1,53 +1,157 @@
-#[derive(Debug)]
-pub struct InputPlugin {
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use config::ast::Selector;
+use event::{Event, Template, Value};
+use pipeline::QueueWriter;
+use super::settings::{SettingValue, Settings};
+
+/// Source of events, e.g. a file, a socket or a message broker.
+///
+/// The pipeline calls `register` once, then `run` in a thread of its own until the input is
+/// exhausted or the pipeline is stopped, and `close` after that. Threadable inputs run in as
+/// many copies as the `threads` setting says, so inputs have to be `Clone`.
+pub trait Input: InputClone + Send {
+    /// Prepares the input before running, e.g. opens a socket.
+    fn register(&mut self) {}
+
+    /// Produces events into the pipeline queue until the input is exhausted, `stop` is
+    /// raised or the queue refuses an event. Events should be passed through
+    /// `Decorator::decorate` before pushing.
+    fn run(&mut self, queue: &QueueWriter, stop: &StopSignal);
+
+    /// Releases resources once the input is done.
+    fn close(&mut self) {}
+
+    /// Tells whether several copies of the input can run at once.
+    fn threadable(&self) -> bool {
+        false
+    }
+}
+
+/// Makes copies of boxed inputs for extra threads. Implemented for every `Clone` input.
+pub trait InputClone {
+    fn clone_box(&self) -> Box<dyn Input>;
+}
+
+impl<T: Input + Clone + 'static> InputClone for T {
+    fn clone_box(&self) -> Box<dyn Input> {
+        Box::new(self.clone())
+    }
+}
+
+/// Asks inputs to stop, shared between the pipeline and all its inputs.
+#[derive(Clone, Debug, Default)]
+pub struct StopSignal(Arc<AtomicBool>);
+
+impl StopSignal {
+    pub fn new() -> StopSignal {
+        StopSignal::default()
+    }
+
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Applies settings common for all the inputs to the produced events: sets `type` unless the
+/// event already has one, adds `tags` and `add_field` fields.
+#[derive(Clone, Debug, Default)]
+pub struct Decorator {
+    event_type: Option<String>,
+    tags: Vec<String>,
+    /// Field values are templates, the ones which don't parse are kept verbatim.
+    add_field: Vec<(Selector, String, Option<Template>)>,
+}
+
+impl Decorator {
+    pub fn new(settings: &Settings) -> Decorator {
+        let add_field = settings.get_hash("add_field").map_or(vec![], |fields| {
+            fields.iter()
+                .filter_map(|(name, value)| match value {
+                    SettingValue::String(s) => {
+                        Some((Selector::from(name.as_str()), s.clone(), Template::parse(s).ok()))
+                    }
+                    _ => None,
+                })
+                .collect()
+        });
+        Decorator {
+            event_type: settings.get_str("type").map(String::from),
+            tags: settings.get_strings("tags").into_iter().map(String::from).collect(),
+            add_field,
+        }
+    }
 
-    // config_name = "input"  Read it from plugin impl
-    // type: String
-    // codec
-    // tags = []
-    // add_field
-    // plugin_type = input
-    // threadable = false
-    // stop_called = Concurrent::AtomicBoolean.new(false)
-
-    // init(params: Hash)
-    //     self.params = params
-
-    // to override:
-    // register()  - must be overriden
-    // run()       - must be overriden
-    // stop()      - optional (extra work on stopping)
-
-    // API:
-    // add_tag()
-    // do_stop()
-    // is_stopped() -> stop_called.value
-    // decorate()
-    // fix_streaming_codecs() ?
-    // threads_count() -> u32 if threadable
-
-    // From Plugin parent:
-    // id()
-    // do_close()
-    // close()
+    pub fn decorate(&self, event: &mut Event) {
+        let type_field = Selector::from("type");
+        if let Some(ref event_type) = self.event_type {
+            if event.get(&type_field).is_none() {
+                let _ = event.set(&type_field, Value::from(event_type.as_str()));
+            }
+        }
+        for tag in &self.tags {
+            event.add_tag(tag);
+        }
+        for (field, raw, template) in &self.add_field {
+            let value = template.as_ref().map_or_else(|| raw.clone(), |t| t.render(event));
+            let _ = event.set(field, Value::from(value));
+        }
+    }
+}
+
+/// Input along with its settings as the pipeline runs it.
+pub struct InputPlugin {
+    settings: Settings,
+    input: Box<dyn Input>,
 }
 
 impl Clone for InputPlugin {
     fn clone(&self) -> InputPlugin {
-        InputPlugin {}
+        InputPlugin {
+            settings: self.settings.clone(),
+            input: self.input.clone_box(),
+        }
+    }
+}
+
+impl fmt::Debug for InputPlugin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InputPlugin").field("settings", &self.settings).finish()
     }
 }
 
 impl InputPlugin {
+    pub fn new(settings: Settings, input: Box<dyn Input>) -> InputPlugin {
+        InputPlugin { settings, input }
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
     pub fn register(&mut self) {
+        self.input.register();
+    }
 
+    pub fn run(&mut self, queue: &QueueWriter, stop: &StopSignal) {
+        self.input.run(queue, stop);
     }
 
-    pub fn run(&mut self) {
-        ::std::thread::sleep(::std::time::Duration::from_millis(10000));
+    pub fn close(&mut self) {
+        self.input.close();
     }
 
+    /// Number of copies to run, it's always one unless the input is threadable.
     pub fn threads_count(&self) -> usize {
-        1  // TODO: read it from the plugin impl
+        if !self.input.threadable() {
+            return 1;
+        }
+        self.settings.get_number("threads").map_or(1, |n| n.max(1.0) as usize)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,328 @@
+//! Plugins loaded from shared libraries.
+//!
+//! A plugin library is a `cdylib` crate depending on echelon0 which declares a function
+//! registering its plugins:
+//!
+//! ```ignore
+//! #[macro_use]
+//! extern crate echelon0;
+//!
+//! use echelon0::plugin::factory::PluginFactory;
+//! use echelon0::plugin::settings::Schema;
+//!
+//! fn register(factory: &mut PluginFactory) {
+//!     factory.register_filter("foo", Schema::filter(), |s| Box::new(Foo::new(s)));
+//! }
+//!
+//! declare_plugins!(register);
+//! ```
+//!
+//! Plugins are passed around as Rust trait objects, so libraries have to be built with the
+//! same compiler as echelon0. The macro exports an `extern "C"` function returning the
+//! declaration, which is made of C types only. It records the ABI version, the version of
+//! echelon0 the library was built against and the version of rustc it was built with, all of
+//! them are checked before anything else is touched.
+
+use std::env::consts::DLL_EXTENSION;
+use std::error;
+use std::ffi::CStr;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::mem;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+
+use libloading::{Library, Symbol};
+
+use super::factory::PluginFactory;
+
+/// Version of the layout of `PluginDeclaration` and of the plugin traits. Bumped whenever
+/// either changes incompatibly.
+pub const ABI_VERSION: u32 = 1;
+
+/// Version of echelon0, libraries built against another major (or minor before 1.0) version
+/// are rejected.
+pub const CORE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Version of rustc echelon0 is built with, e.g. `rustc 1.80.0 (051478957 2024-07-21)`.
+pub const RUSTC_VERSION: &str = env!("ECHELON0_RUSTC_VERSION");
+
+/// NUL-terminated `CORE_VERSION` and `RUSTC_VERSION` for `declare_plugins!`.
+#[doc(hidden)]
+pub const CORE_VERSION_C: &[u8] = concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes();
+#[doc(hidden)]
+pub const RUSTC_VERSION_C: &[u8] = concat!(env!("ECHELON0_RUSTC_VERSION"), "\0").as_bytes();
+
+/// Name of the function `declare_plugins!` exports.
+const DECLARATION_SYMBOL: &[u8] = b"echelon0_plugin_declaration\0";
+
+/// Entry point of a plugin library, returned by the function `declare_plugins!` exports.
+#[repr(C)]
+pub struct PluginDeclaration {
+    /// Always goes first, so it can be read whatever the rest of the layout is.
+    pub abi_version: u32,
+    /// NUL-terminated version of echelon0 the library is built against.
+    pub core_version: *const c_char,
+    /// NUL-terminated version of rustc the library is built with.
+    pub rustc_version: *const c_char,
+    /// Registers the plugins, only called once all the versions are checked.
+    pub register: unsafe extern "C" fn(*mut PluginFactory),
+}
+
+// Declarations only point to static strings.
+unsafe impl Sync for PluginDeclaration {}
+
+type DeclarationFn = unsafe extern "C" fn() -> *const PluginDeclaration;
+
+/// Exports the entry point of a plugin library. Takes a `fn(&mut PluginFactory)` which
+/// registers the plugins of the library.
+#[macro_export]
+macro_rules! declare_plugins {
+    ($register:expr) => {
+        #[no_mangle]
+        pub extern "C" fn echelon0_plugin_declaration()
+            -> *const $crate::plugin::library::PluginDeclaration {
+            use $crate::plugin::factory::PluginFactory;
+            use $crate::plugin::library::*;
+
+            // Not `register`, which would shadow a function of that name passed to the macro.
+            unsafe extern "C" fn echelon0_register_plugins(factory: *mut PluginFactory) {
+                let register: fn(&mut PluginFactory) = $register;
+                register(&mut *factory)
+            }
+
+            static DECLARATION: PluginDeclaration = PluginDeclaration {
+                abi_version: ABI_VERSION,
+                core_version: CORE_VERSION_C.as_ptr() as *const _,
+                rustc_version: RUSTC_VERSION_C.as_ptr() as *const _,
+                register: echelon0_register_plugins,
+            };
+            &DECLARATION
+        }
+    };
+}
+
+#[derive(Debug)]
+pub enum LibraryError {
+    /// The plugins directory can't be read.
+    Io(PathBuf, io::Error),
+    /// The file is not a shared library or its dependencies are missing.
+    Open(PathBuf, String),
+    /// The library doesn't declare any plugins with `declare_plugins!`.
+    NoDeclaration(PathBuf),
+    AbiVersion { path: PathBuf, found: u32 },
+    CoreVersion { path: PathBuf, found: String },
+    RustcVersion { path: PathBuf, found: String },
+}
+
+impl fmt::Display for LibraryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LibraryError::Io(path, err) => {
+                write!(f, "Cannot read plugins directory `{}`: {}", path.display(), err)
+            }
+            LibraryError::Open(path, err) => {
+                write!(f, "Cannot load plugin library `{}`: {}", path.display(), err)
+            }
+            LibraryError::NoDeclaration(path) => {
+                write!(f,
+                       "`{}` is not a plugin library: no plugins are declared",
+                       path.display())
+            }
+            LibraryError::AbiVersion { path, found } => {
+                write!(f,
+                       "Plugin library `{}` uses plugin ABI version {}, expected {}",
+                       path.display(),
+                       found,
+                       ABI_VERSION)
+            }
+            LibraryError::CoreVersion { path, found } => {
+                write!(f,
+                       "Plugin library `{}` is built for echelon0 {}, incompatible with {}",
+                       path.display(),
+                       found,
+                       CORE_VERSION)
+            }
+            LibraryError::RustcVersion { path, found } => {
+                write!(f,
+                       "Plugin library `{}` is built with {}, expected {}",
+                       path.display(),
+                       found,
+                       RUSTC_VERSION)
+            }
+        }
+    }
+}
+
+impl error::Error for LibraryError {}
+
+/// Tells whether plugins built against one version of echelon0 work with another. Versions
+/// may be partial, e.g. `0.1`.
+pub fn is_compatible(version: &str, core_version: &str) -> bool {
+    let release = |v: &str| -> Vec<String> {
+        let parts: Vec<_> = v.split('.').map(String::from).collect();
+        let significant = if parts.first().is_some_and(|major| major == "0") { 2 } else { 1 };
+        parts.into_iter().take(significant).collect()
+    };
+    release(version) == release(core_version)
+}
+
+/// Checks that the library declaring the plugins can be used with this build of echelon0.
+///
+/// # Safety
+///
+/// `declaration` must point to a declaration with `abi_version` first, the rest of it is only
+/// read if the ABI version matches.
+unsafe fn check(path: &Path, declaration: *const PluginDeclaration) -> Result<(), LibraryError> {
+    let abi_version = (*declaration).abi_version;
+    if abi_version != ABI_VERSION {
+        return Err(LibraryError::AbiVersion { path: path.to_path_buf(), found: abi_version });
+    }
+    let core_version = CStr::from_ptr((*declaration).core_version).to_string_lossy();
+    if !is_compatible(&core_version, CORE_VERSION) {
+        return Err(LibraryError::CoreVersion {
+            path: path.to_path_buf(),
+            found: core_version.into_owned(),
+        });
+    }
+    let rustc_version = CStr::from_ptr((*declaration).rustc_version).to_string_lossy();
+    if rustc_version != RUSTC_VERSION {
+        return Err(LibraryError::RustcVersion {
+            path: path.to_path_buf(),
+            found: rustc_version.into_owned(),
+        });
+    }
+    Ok(())
+}
+
+impl PluginFactory {
+    /// Registers the plugins of a shared library.
+    ///
+    /// Libraries are never unloaded, since plugins created by them may outlive the factory.
+    pub fn load_library(&mut self, path: &Path) -> Result<(), LibraryError> {
+        let library = unsafe { Library::new(path) }
+            .map_err(|e| LibraryError::Open(path.to_path_buf(), e.to_string()))?;
+
+        unsafe {
+            let declare: Symbol<DeclarationFn> = library.get(DECLARATION_SYMBOL)
+                .map_err(|_| LibraryError::NoDeclaration(path.to_path_buf()))?;
+            let declaration = declare();
+            if declaration.is_null() {
+                return Err(LibraryError::NoDeclaration(path.to_path_buf()));
+            }
+            check(path, declaration)?;
+            ((*declaration).register)(self);
+        }
+        mem::forget(library);
+        Ok(())
+    }
+
+    /// Registers the plugins of all the shared libraries in a directory, in the order of
+    /// their names. Stops at the first library which fails to load.
+    pub fn load_dir(&mut self, dir: &Path) -> Result<(), LibraryError> {
+        let io_error = |e| LibraryError::Io(dir.to_path_buf(), e);
+        let mut paths = vec![];
+        for entry in fs::read_dir(dir).map_err(io_error)? {
+            let path = entry.map_err(io_error)?.path();
+            if path.is_file() && path.extension().is_some_and(|e| e == DLL_EXTENSION) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        for path in paths {
+            self.load_library(&path)?;
+            info!("Loaded plugins from {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plugin::factory::PluginProvider;
+    use plugin::settings::Schema;
+    use tempdir::TempDir;
+    use super::*;
+
+    fn register(factory: &mut PluginFactory) {
+        factory.register_filter("declared", Schema::filter(), |s| {
+            Box::new(::plugin::builtin::DropEvents::new(s))
+        });
+    }
+
+    declare_plugins!(register);
+
+    #[test]
+    fn test_is_compatible() {
+        assert!(is_compatible("0.1.0", "0.1.3"));
+        assert!(!is_compatible("0.2.0", "0.1.3"));
+        assert!(is_compatible("1.4.0", "1.0.0"));
+        assert!(!is_compatible("2.0.0", "1.0.0"));
+    }
+
+    #[test]
+    fn test_check() {
+        let path = Path::new("libfoo.so");
+        let declaration = echelon0_plugin_declaration();
+        assert!(unsafe { check(path, declaration) }.is_ok());
+
+        let declared = unsafe { &*declaration };
+        let mut factory = PluginFactory::new();
+        unsafe { (declared.register)(&mut factory) };
+        assert!(factory.filter_schema("declared").is_ok());
+
+        let other = |abi_version, core_version: &[u8], rustc_version: &[u8]| {
+            PluginDeclaration {
+                abi_version,
+                core_version: core_version.as_ptr() as *const c_char,
+                rustc_version: rustc_version.as_ptr() as *const c_char,
+                register: declared.register,
+            }
+        };
+        let check = |d: &PluginDeclaration| unsafe { check(path, d) }.unwrap_err().to_string();
+        assert_eq!(format!("Plugin library `libfoo.so` uses plugin ABI version 0, expected {}",
+                           ABI_VERSION),
+                   check(&other(0, CORE_VERSION_C, RUSTC_VERSION_C)));
+        assert_eq!(format!("Plugin library `libfoo.so` is built for echelon0 99.0.0, \
+                            incompatible with {}",
+                           CORE_VERSION),
+                   check(&other(ABI_VERSION, b"99.0.0\0", RUSTC_VERSION_C)));
+        assert_eq!(format!("Plugin library `libfoo.so` is built with rustc 1.0.0, expected {}",
+                           RUSTC_VERSION),
+                   check(&other(ABI_VERSION, CORE_VERSION_C, b"rustc 1.0.0\0")));
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    fn test_load_library_without_declaration() {
+        let path = Path::new("libc.so.6");
+        match PluginFactory::new().load_library(path) {
+            Err(LibraryError::NoDeclaration(p)) => assert_eq!(path, p),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = TempDir::new("plugins");
+        fs::write(dir.join("README"), "not a library").unwrap();
+
+        let mut factory = PluginFactory::new();
+        assert!(factory.load_dir(dir.path()).is_ok());
+
+        let fake = dir.join(format!("libfake.{}", DLL_EXTENSION));
+        fs::write(&fake, "not a library either").unwrap();
+        match factory.load_dir(dir.path()) {
+            Err(LibraryError::Open(path, _)) => assert_eq!(fake, path),
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        let missing = dir.join("missing");
+        match factory.load_dir(&missing) {
+            Err(LibraryError::Io(path, _)) => assert_eq!(missing, path),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+}
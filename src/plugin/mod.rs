@@ -1,8 +1,15 @@
 pub use self::input::*;
 pub use self::filter::*;
 pub use self::output::*;
+pub use self::codec::*;
 
 pub mod factory;
+pub mod library;
+pub mod package;
+pub mod scaffold;
+pub mod settings;
+mod builtin;
 mod input;
 mod filter;
 mod output;
+mod codec;
@@ -1,3 +1,52 @@
+use std::fmt;
+
+use event::Event;
+use super::settings::Settings;
+
+/// Destination of events, e.g. a file, a database or a message broker.
+///
+/// Outputs are shared between all the pipeline workers, which take turns sending their
+/// batches. The pipeline calls `register` before the first batch and `close` once all the
+/// workers are done.
+pub trait Output: Send {
+    fn register(&mut self) {}
+
+    /// Sends a batch of events. Events are borrowed since every output gets the same ones.
+    fn receive(&mut self, events: &[&Event]);
+
+    fn close(&mut self) {}
+}
+
+/// Output along with its settings as the pipeline runs it.
 pub struct OutputPlugin {
+    settings: Settings,
+    output: Box<dyn Output>,
+}
+
+impl fmt::Debug for OutputPlugin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OutputPlugin").field("settings", &self.settings).finish()
+    }
+}
+
+impl OutputPlugin {
+    pub fn new(settings: Settings, output: Box<dyn Output>) -> OutputPlugin {
+        OutputPlugin { settings, output }
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn register(&mut self) {
+        self.output.register();
+    }
+
+    pub fn receive(&mut self, events: &[&Event]) {
+        self.output.receive(events);
+    }
 
+    pub fn close(&mut self) {
+        self.output.close();
+    }
 }
@@ -0,0 +1,614 @@
+//! Plugin packages managed by `epm`.
+//!
+//! A package is a directory or a `.tar` (`.tar.gz`, `.tgz`) archive with a `plugin.toml`
+//! manifest and the plugin library. Installed plugins live in a flat plugins directory, the
+//! one echelon0 loads with `--path.plugins`, as `<name>.so` next to `<name>.toml`.
+
+use std::collections::HashMap;
+use std::env;
+use std::env::consts::DLL_EXTENSION;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+
+use flate2::read::GzDecoder;
+use tar::Archive;
+
+use super::factory::{PluginFactory, PluginProvider};
+use super::library::{is_compatible, LibraryError, CORE_VERSION};
+
+/// File name of the manifest within a package.
+pub const MANIFEST: &str = "plugin.toml";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PluginKind {
+    Input,
+    Filter,
+    Output,
+    Codec,
+}
+
+impl FromStr for PluginKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PluginKind, String> {
+        match s {
+            "input" => Ok(PluginKind::Input),
+            "filter" => Ok(PluginKind::Filter),
+            "output" => Ok(PluginKind::Output),
+            "codec" => Ok(PluginKind::Codec),
+            _ => Err(format!("unknown plugin kind `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for PluginKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            PluginKind::Input => "input",
+            PluginKind::Filter => "filter",
+            PluginKind::Output => "output",
+            PluginKind::Codec => "codec",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Description of a plugin package.
+///
+/// The manifest is a flat subset of TOML: `key = "value"` lines, comments and blank lines.
+/// Unknown keys are ignored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Manifest {
+    /// Name the plugin is registered under, also the name of the package.
+    pub name: String,
+    pub version: String,
+    pub kind: PluginKind,
+    /// Version of echelon0 the plugin is built for, e.g. `0.1`.
+    pub echelon0: String,
+    /// Path of the library relative to the manifest, `lib<name>.so` by default as Cargo
+    /// names `cdylib`s.
+    pub library: String,
+    pub description: Option<String>,
+}
+
+impl Manifest {
+    pub fn parse(source: &str) -> Result<Manifest, String> {
+        let mut values = HashMap::new();
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once('=')
+                .and_then(|(k, v)| unquote(v.trim()).map(|v| (k.trim(), v)))
+                .ok_or_else(|| format!("line {}: `key = \"value\"` expected", i + 1))?;
+            values.insert(key.to_string(), value);
+        }
+
+        let mut take = |key: &str| values.remove(key);
+        let mut required = |key: &str| take(key).ok_or_else(|| format!("`{}` is missing", key));
+        let name = required("name")?;
+        if name.is_empty() ||
+           !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("invalid plugin name `{}`", name));
+        }
+        let version = required("version")?;
+        let kind = required("kind")?.parse()?;
+        let echelon0 = required("echelon0")?;
+        let library = take("library")
+            .unwrap_or_else(|| format!("lib{}.{}", name.replace('-', "_"), DLL_EXTENSION));
+        Ok(Manifest {
+            name,
+            version,
+            kind,
+            echelon0,
+            library,
+            description: take("description"),
+        })
+    }
+
+    /// Tells whether the plugin is built for this version of echelon0.
+    pub fn is_compatible(&self) -> bool {
+        is_compatible(&self.echelon0, CORE_VERSION)
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "name = {:?}", self.name)?;
+        writeln!(f, "version = {:?}", self.version)?;
+        writeln!(f, "kind = \"{}\"", self.kind)?;
+        writeln!(f, "echelon0 = {:?}", self.echelon0)?;
+        writeln!(f, "library = {:?}", self.library)?;
+        if let Some(ref description) = self.description {
+            writeln!(f, "description = {:?}", description)?;
+        }
+        Ok(())
+    }
+}
+
+/// Strips quotes of a basic TOML string and resolves `\"` and `\\` escapes.
+fn unquote(s: &str) -> Option<String> {
+    let inner = s.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                c @ '"' | c @ '\\' => out.push(c),
+                _ => return None,
+            },
+            '"' => return None,
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+#[derive(Debug)]
+pub enum PackageError {
+    Io(PathBuf, io::Error),
+    /// A package without `plugin.toml` or with a broken one.
+    Manifest(PathBuf, String),
+    MissingLibrary(PathBuf, String),
+    Incompatible(Box<Manifest>),
+    AlreadyInstalled(Box<Manifest>),
+    NotInstalled(String),
+    /// The library can't be loaded.
+    Library(LibraryError),
+    /// The library loads but doesn't register the plugin its manifest describes.
+    NotRegistered(Box<Manifest>),
+}
+
+impl fmt::Display for PackageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PackageError::Io(path, err) => write!(f, "`{}`: {}", path.display(), err),
+            PackageError::Manifest(path, err) => {
+                write!(f, "Invalid manifest of `{}`: {}", path.display(), err)
+            }
+            PackageError::MissingLibrary(path, library) => {
+                write!(f, "Package `{}` has no library `{}`", path.display(), library)
+            }
+            PackageError::Incompatible(m) => {
+                write!(f,
+                       "Plugin `{}` {} is built for echelon0 {}, incompatible with {}",
+                       m.name,
+                       m.version,
+                       m.echelon0,
+                       CORE_VERSION)
+            }
+            PackageError::AlreadyInstalled(m) => {
+                write!(f, "Plugin `{}` {} is already installed", m.name, m.version)
+            }
+            PackageError::NotInstalled(name) => write!(f, "Plugin `{}` is not installed", name),
+            PackageError::Library(err) => write!(f, "{}", err),
+            PackageError::NotRegistered(m) => {
+                write!(f, "Library of `{}` doesn't register {} plugin `{0}`", m.name, m.kind)
+            }
+        }
+    }
+}
+
+impl error::Error for PackageError {}
+
+/// Plugin package read into memory.
+#[derive(Debug)]
+pub struct Package {
+    pub manifest: Manifest,
+    manifest_source: String,
+    library: Vec<u8>,
+}
+
+impl Package {
+    /// Reads a package directory or archive.
+    pub fn open(path: &Path) -> Result<Package, PackageError> {
+        if path.is_dir() {
+            return Package::open_dir(path);
+        }
+
+        let io_error = |e| PackageError::Io(path.to_path_buf(), e);
+        let file = fs::File::open(path).map_err(io_error)?;
+        let name = path.to_string_lossy();
+        let files = if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            read_archive(Archive::new(GzDecoder::new(file))).map_err(io_error)?
+        } else {
+            read_archive(Archive::new(file)).map_err(io_error)?
+        };
+
+        // Archives usually have everything in a top-level directory.
+        let (root, manifest_source) = files.iter()
+            .filter(|(p, _)| p.file_name().is_some_and(|n| n == MANIFEST))
+            .min_by_key(|(p, _)| p.components().count())
+            .map(|(p, content)| {
+                (p.parent().unwrap_or(Path::new("")), String::from_utf8_lossy(content))
+            })
+            .ok_or_else(|| no_manifest(path))?;
+        let manifest = Manifest::parse(&manifest_source)
+            .map_err(|e| PackageError::Manifest(path.to_path_buf(), e))?;
+        let library = files.get(&root.join(&manifest.library))
+            .cloned()
+            .ok_or_else(|| PackageError::MissingLibrary(path.to_path_buf(),
+                                                        manifest.library.clone()))?;
+        Ok(Package {
+            manifest_source: manifest_source.into_owned(),
+            manifest,
+            library,
+        })
+    }
+
+    /// Reads a package directory, e.g. the crate of a plugin with the library built in
+    /// `target`.
+    fn open_dir(dir: &Path) -> Result<Package, PackageError> {
+        let manifest_source = match fs::read_to_string(dir.join(MANIFEST)) {
+            Ok(source) => source,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Err(no_manifest(dir)),
+            Err(e) => return Err(PackageError::Io(dir.join(MANIFEST), e)),
+        };
+        let manifest = Manifest::parse(&manifest_source)
+            .map_err(|e| PackageError::Manifest(dir.to_path_buf(), e))?;
+        let library = match fs::read(dir.join(&manifest.library)) {
+            Ok(library) => library,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(PackageError::MissingLibrary(dir.to_path_buf(),
+                                                        manifest.library.clone()))
+            }
+            Err(e) => return Err(PackageError::Io(dir.join(&manifest.library), e)),
+        };
+        Ok(Package {
+            manifest_source,
+            manifest,
+            library,
+        })
+    }
+
+    /// Loads the library from a temporary file and checks it registers the plugin the
+    /// manifest describes, so a broken package never replaces an installed plugin.
+    ///
+    /// The file is put into a new directory only the current user can access, so nobody can
+    /// swap the library between writing and loading it.
+    pub fn verify(&self) -> Result<(), PackageError> {
+        let dir = private_temp_dir().map_err(|e| PackageError::Io(env::temp_dir(), e))?;
+        let path = dir.join(format!("{}.{}", self.manifest.name, DLL_EXTENSION));
+        let verified = fs::write(&path, &self.library)
+            .map_err(|e| PackageError::Io(path.clone(), e))
+            .and_then(|_| verify_library(&self.manifest, &path));
+        let _ = fs::remove_dir_all(&dir);
+        verified
+    }
+}
+
+/// Creates a new directory in the system temporary directory. Existing directories (e.g.
+/// created by someone else in advance) are never reused.
+fn private_temp_dir() -> io::Result<PathBuf> {
+    let builder = private_dir_builder();
+    let mut n = 0;
+    loop {
+        let path = env::temp_dir().join(format!("epm-{}-{}", process::id(), n));
+        match builder.create(&path) {
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => n += 1,
+            r => return r.map(|_| path),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn private_dir_builder() -> fs::DirBuilder {
+    use std::os::unix::fs::DirBuilderExt;
+
+    let mut builder = fs::DirBuilder::new();
+    builder.mode(0o700);
+    builder
+}
+
+#[cfg(not(unix))]
+fn private_dir_builder() -> fs::DirBuilder {
+    fs::DirBuilder::new()
+}
+
+fn no_manifest(path: &Path) -> PackageError {
+    PackageError::Manifest(path.to_path_buf(), format!("no `{}` found", MANIFEST))
+}
+
+/// Reads regular files of an archive by their paths in it.
+fn read_archive<R: Read>(mut archive: Archive<R>) -> io::Result<HashMap<PathBuf, Vec<u8>>> {
+    let mut files = HashMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        // Leading `./` and anything like `..` are dropped.
+        let path = entry.path()?
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .collect::<PathBuf>();
+        let mut content = vec![];
+        entry.read_to_end(&mut content)?;
+        files.insert(path, content);
+    }
+    Ok(files)
+}
+
+/// Directory of installed plugins.
+#[derive(Clone, Debug)]
+pub struct PluginDir {
+    path: PathBuf,
+}
+
+impl PluginDir {
+    pub fn new<P: Into<PathBuf>>(path: P) -> PluginDir {
+        PluginDir { path: path.into() }
+    }
+
+    pub fn library_path(&self, name: &str) -> PathBuf {
+        self.path.join(format!("{}.{}", name, DLL_EXTENSION))
+    }
+
+    fn manifest_path(&self, name: &str) -> PathBuf {
+        self.path.join(format!("{}.toml", name))
+    }
+
+    /// Returns manifests of the installed plugins sorted by name. A missing directory has no
+    /// plugins.
+    pub fn list(&self) -> Result<Vec<Manifest>, PackageError> {
+        let io_error = |e| PackageError::Io(self.path.clone(), e);
+        let entries = match fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(io_error(e)),
+        };
+
+        let mut manifests = vec![];
+        for entry in entries {
+            let path = entry.map_err(io_error)?.path();
+            if path.extension().is_some_and(|e| e == "toml") {
+                manifests.push(read_manifest(&path)?);
+            }
+        }
+        manifests.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(manifests)
+    }
+
+    pub fn get(&self, name: &str) -> Result<Manifest, PackageError> {
+        let path = self.manifest_path(name);
+        if !path.is_file() {
+            return Err(PackageError::NotInstalled(name.to_string()));
+        }
+        read_manifest(&path)
+    }
+
+    /// Installs a package. An installed plugin of the same name is replaced only if `force`
+    /// is set.
+    ///
+    /// Both files are written next to their destinations first and then renamed over them, the
+    /// manifest last. So a failed install never leaves a partially written plugin behind.
+    pub fn install(&self, package: &Package, force: bool) -> Result<(), PackageError> {
+        let manifest = &package.manifest;
+        if !manifest.is_compatible() {
+            return Err(PackageError::Incompatible(Box::new(manifest.clone())));
+        }
+        if !force {
+            if let Ok(installed) = self.get(&manifest.name) {
+                return Err(PackageError::AlreadyInstalled(Box::new(installed)));
+            }
+        }
+
+        fs::create_dir_all(&self.path).map_err(|e| PackageError::Io(self.path.clone(), e))?;
+        let library = StagedFile::write(self.library_path(&manifest.name), &package.library)?;
+        let manifest_file = StagedFile::write(self.manifest_path(&manifest.name),
+                                              package.manifest_source.as_bytes())?;
+        library.commit()?;
+        manifest_file.commit()
+    }
+
+    /// Removes an installed plugin. Returns its manifest.
+    pub fn remove(&self, name: &str) -> Result<Manifest, PackageError> {
+        let manifest = self.get(name)?;
+        for path in &[self.library_path(name), self.manifest_path(name)] {
+            match fs::remove_file(path) {
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+                r => r.map_err(|e| PackageError::Io(path.clone(), e))?,
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Loads the library of an installed plugin and checks it registers the plugin its
+    /// manifest describes.
+    pub fn verify(&self, name: &str) -> Result<(), PackageError> {
+        verify_library(&self.get(name)?, &self.library_path(name))
+    }
+}
+
+/// File written under a temporary name next to its destination. It is removed unless it is
+/// committed, i.e. renamed to the destination.
+struct StagedFile {
+    path: PathBuf,
+    destination: PathBuf,
+}
+
+impl StagedFile {
+    fn write(destination: PathBuf, content: &[u8]) -> Result<StagedFile, PackageError> {
+        let name = destination.file_name().unwrap_or_default().to_string_lossy();
+        // Not picked up as a plugin by its extension if left behind.
+        let path = destination.with_file_name(format!(".{}.{}.tmp", name, process::id()));
+        let _ = fs::remove_file(&path);
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| PackageError::Io(path.clone(), e))?;
+        let staged = StagedFile { path, destination };
+        file.write_all(content)
+            .and_then(|_| file.sync_all())
+            .map_err(|e| PackageError::Io(staged.path.clone(), e))?;
+        Ok(staged)
+    }
+
+    fn commit(self) -> Result<(), PackageError> {
+        fs::rename(&self.path, &self.destination)
+            .map_err(|e| PackageError::Io(self.destination.clone(), e))
+    }
+}
+
+impl Drop for StagedFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn verify_library(manifest: &Manifest, path: &Path) -> Result<(), PackageError> {
+    let mut factory = PluginFactory::new();
+    factory.load_library(path).map_err(PackageError::Library)?;
+
+    let name = &manifest.name;
+    let registered = match manifest.kind {
+        PluginKind::Input => factory.input_schema(name).is_ok(),
+        PluginKind::Filter => factory.filter_schema(name).is_ok(),
+        PluginKind::Output => factory.output_schema(name).is_ok(),
+        PluginKind::Codec => factory.codec_schema(name).is_ok(),
+    };
+    if registered {
+        Ok(())
+    } else {
+        Err(PackageError::NotRegistered(Box::new(manifest.clone())))
+    }
+}
+
+fn read_manifest(path: &Path) -> Result<Manifest, PackageError> {
+    let source = fs::read_to_string(path).map_err(|e| PackageError::Io(path.to_path_buf(), e))?;
+    Manifest::parse(&source).map_err(|e| PackageError::Manifest(path.to_path_buf(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use tar::{Builder, Header};
+    use tempdir::TempDir;
+    use super::*;
+
+    fn manifest(name: &str, echelon0: &str) -> String {
+        format!("# A test plugin\nname = \"{}\"\nversion = \"1.2.0\"\nkind = \"filter\"\n\
+                 echelon0 = \"{}\"\ndescription = \"Says \\\"hi\\\"\"\n",
+                name,
+                echelon0)
+    }
+
+    #[test]
+    fn test_manifest() {
+        let m = Manifest::parse(&manifest("greet", "0.1")).unwrap();
+        assert_eq!(Manifest {
+                       name: "greet".to_string(),
+                       version: "1.2.0".to_string(),
+                       kind: PluginKind::Filter,
+                       echelon0: "0.1".to_string(),
+                       library: format!("libgreet.{}", DLL_EXTENSION),
+                       description: Some("Says \"hi\"".to_string()),
+                   },
+                   m);
+        assert_eq!(m, Manifest::parse(&m.to_string()).unwrap());
+        assert!(m.is_compatible());
+
+        assert_eq!(Err("`kind` is missing".to_string()),
+                   Manifest::parse("name = \"a\"\nversion = \"1\""));
+        assert_eq!(Err("line 2: `key = \"value\"` expected".to_string()),
+                   Manifest::parse("name = \"a\"\nversion = 1"));
+        assert_eq!(Err("invalid plugin name `../a`".to_string()),
+                   Manifest::parse(&manifest("../a", "0.1")));
+    }
+
+    #[test]
+    fn test_install_from_archive() {
+        let dir = TempDir::new("epm");
+        let archive = dir.join("greet-1.2.0.tar.gz");
+        let mut builder = Builder::new(GzEncoder::new(fs::File::create(&archive).unwrap(),
+                                                      Compression::default()));
+        let library = format!("greet-1.2.0/libgreet.{}", DLL_EXTENSION);
+        let manifest = manifest("greet", "0.1");
+        for &(path, content) in &[("greet-1.2.0/plugin.toml", manifest.as_bytes()),
+                                  (library.as_str(), b"ELF".as_ref())] {
+            let mut header = Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, content).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let plugins = PluginDir::new(dir.join("plugins"));
+        assert!(plugins.list().unwrap().is_empty());
+
+        let package = Package::open(&archive).unwrap();
+        plugins.install(&package, false).unwrap();
+        assert_eq!(b"ELF".to_vec(), fs::read(plugins.library_path("greet")).unwrap());
+        // Nothing but the library and the manifest is left.
+        assert_eq!(2, fs::read_dir(dir.join("plugins")).unwrap().count());
+        assert_eq!(vec![package.manifest.clone()], plugins.list().unwrap());
+
+        match plugins.install(&package, false) {
+            Err(PackageError::AlreadyInstalled(m)) => assert_eq!("greet", m.name),
+            r => panic!("unexpected result {:?}", r),
+        }
+        plugins.install(&package, true).unwrap();
+        match plugins.verify("greet") {
+            Err(PackageError::Library(LibraryError::Open(..))) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        assert_eq!("greet", plugins.remove("greet").unwrap().name);
+        assert!(plugins.list().unwrap().is_empty());
+        match plugins.remove("greet") {
+            Err(PackageError::NotInstalled(name)) => assert_eq!("greet", name),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_open_dir() {
+        let dir = TempDir::new("package");
+        fs::write(dir.join(MANIFEST), manifest("old", "0.0.9")).unwrap();
+        match Package::open(dir.path()) {
+            Err(PackageError::MissingLibrary(_, library)) => {
+                assert_eq!(format!("libold.{}", DLL_EXTENSION), library)
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        fs::write(dir.join(format!("libold.{}", DLL_EXTENSION)), b"ELF").unwrap();
+        let package = Package::open(dir.path()).unwrap();
+        match PluginDir::new(dir.join("plugins")).install(&package, false) {
+            Err(PackageError::Incompatible(m)) => assert_eq!("0.0.9", m.echelon0),
+            r => panic!("unexpected result {:?}", r),
+        }
+        match package.verify() {
+            // The staging directory is gone along with the library.
+            Err(PackageError::Library(LibraryError::Open(path, _))) => {
+                assert!(!path.parent().unwrap().exists())
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_private_temp_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let first = private_temp_dir().unwrap();
+        let second = private_temp_dir().unwrap();
+        assert_ne!(first, second);
+        let mode = fs::metadata(&first).unwrap().permissions().mode();
+        fs::remove_dir(&first).unwrap();
+        fs::remove_dir(&second).unwrap();
+        assert_eq!(0o700, mode & 0o777);
+    }
+}
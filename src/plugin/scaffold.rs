@@ -0,0 +1,543 @@
+//! Skeletons of plugin crates generated by `epm generate`.
+//!
+//! A skeleton is a `cdylib` crate with a working plugin of the requested kind, its settings
+//! schema, a test running it through `TestPipeline` and a `plugin.toml` pointing to the
+//! release build of the library, so the crate directory is a package `epm install` takes
+//! as is.
+
+use std::env::consts::DLL_EXTENSION;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::library::CORE_VERSION;
+use super::package::{Manifest, PluginKind, MANIFEST};
+
+#[derive(Debug)]
+pub enum ScaffoldError {
+    /// The name can't be used in configs.
+    InvalidName(String),
+    /// The crate directory exists and has files in it.
+    NotEmpty(PathBuf),
+    Io(PathBuf, io::Error),
+}
+
+impl fmt::Display for ScaffoldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScaffoldError::InvalidName(name) => {
+                write!(f,
+                       "Invalid plugin name `{}`: expected letters, digits and underscores, \
+                        not starting with a digit",
+                       name)
+            }
+            ScaffoldError::NotEmpty(path) => {
+                write!(f, "Directory `{}` already exists and is not empty", path.display())
+            }
+            ScaffoldError::Io(path, err) => write!(f, "`{}`: {}", path.display(), err),
+        }
+    }
+}
+
+impl error::Error for ScaffoldError {}
+
+/// Crate of a new plugin.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Skeleton {
+    name: String,
+    kind: PluginKind,
+    core_path: Option<PathBuf>,
+}
+
+impl Skeleton {
+    /// Takes the name the plugin is registered under, which is also the name of the crate.
+    /// Names are barewords as configs refer to plugins with them, e.g. `my_filter`.
+    pub fn new(name: &str, kind: PluginKind) -> Result<Skeleton, ScaffoldError> {
+        let mut chars = name.chars();
+        let head = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+        if !head || name.len() < 2 || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(ScaffoldError::InvalidName(name.to_string()));
+        }
+        Ok(Skeleton {
+            name: name.to_string(),
+            kind,
+            core_path: None,
+        })
+    }
+
+    /// Makes the crate depend on a local checkout of echelon0 rather than on the released
+    /// version.
+    pub fn core_path<P: Into<PathBuf>>(mut self, path: P) -> Skeleton {
+        self.core_path = Some(path.into());
+        self
+    }
+
+    /// Returns contents of the files of the crate by their paths relative to its directory.
+    pub fn files(&self) -> Vec<(PathBuf, String)> {
+        let source = match self.kind {
+            PluginKind::Input => INPUT,
+            PluginKind::Filter => FILTER,
+            PluginKind::Output => OUTPUT,
+            PluginKind::Codec => CODEC,
+        };
+        vec![(PathBuf::from("Cargo.toml"), self.expand(CARGO_TOML)),
+             (PathBuf::from(MANIFEST), self.manifest().to_string()),
+             (PathBuf::from(".gitignore"), "/target\nCargo.lock\n".to_string()),
+             (Path::new("src").join("lib.rs"), self.expand(source))]
+    }
+
+    /// Writes the crate to a directory, which is created if it doesn't exist. The directory
+    /// must be empty otherwise, nothing is ever overwritten.
+    pub fn write(&self, dir: &Path) -> Result<(), ScaffoldError> {
+        match fs::read_dir(dir) {
+            Ok(mut entries) => {
+                if entries.next().is_some() {
+                    return Err(ScaffoldError::NotEmpty(dir.to_path_buf()));
+                }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(ScaffoldError::Io(dir.to_path_buf(), e)),
+        }
+
+        for (path, content) in self.files() {
+            let path = dir.join(path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| ScaffoldError::Io(parent.into(), e))?;
+            }
+            fs::write(&path, content).map_err(|e| ScaffoldError::Io(path, e))?;
+        }
+        Ok(())
+    }
+
+    fn manifest(&self) -> Manifest {
+        Manifest {
+            name: self.name.clone(),
+            version: "0.1.0".to_string(),
+            kind: self.kind,
+            echelon0: CORE_VERSION.to_string(),
+            library: format!("target/release/lib{}.{}", self.name, DLL_EXTENSION),
+            description: Some(format!("The {} {} plugin", self.name, self.kind)),
+        }
+    }
+
+    /// Fills in `@NAME@`, `@TYPE@` (the name in camel case) and `@ECHELON0@` (the dependency
+    /// on echelon0) in a template.
+    fn expand(&self, template: &str) -> String {
+        let type_name: String = self.name
+            .split('_')
+            .flat_map(|word| {
+                let mut chars = word.chars();
+                chars.next().map(|c| c.to_ascii_uppercase()).into_iter().chain(chars)
+            })
+            .collect();
+        let dependency = match self.core_path {
+            Some(ref path) => format!("{{ path = {:?} }}", path.display().to_string()),
+            None => format!("{:?}", CORE_VERSION),
+        };
+        template.replace("@NAME@", &self.name)
+            .replace("@TYPE@", &type_name)
+            .replace("@ECHELON0@", &dependency)
+    }
+}
+
+const CARGO_TOML: &str = r#"[package]
+name = "@NAME@"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+# Plugins are passed to echelon0 as Rust trait objects, so build them with the same compiler
+# and version of echelon0 as the echelon0 binary loading them.
+crate-type = ["cdylib"]
+
+[dependencies]
+echelon0 = @ECHELON0@
+"#;
+
+const INPUT: &str = r#"#[macro_use]
+extern crate echelon0;
+
+use echelon0::QueueWriter;
+use echelon0::event::Event;
+use echelon0::plugin::factory::PluginFactory;
+use echelon0::plugin::settings::{Schema, Setting, SettingType, SettingValue, Settings};
+use echelon0::plugin::{Decorator, Input, StopSignal};
+
+/// Produces `count` events with `message`.
+#[derive(Clone, Debug)]
+pub struct @TYPE@ {
+    decorator: Decorator,
+    message: String,
+    count: u64,
+}
+
+impl @TYPE@ {
+    /// Settings of the plugin in addition to the ones common for all the inputs.
+    pub fn schema() -> Schema {
+        Schema::input()
+            .setting(Setting::new("message", SettingType::String).required())
+            .setting(Setting::new("count", SettingType::Number)
+                .default(SettingValue::Number(1.0)))
+    }
+
+    pub fn new(settings: &Settings) -> @TYPE@ {
+        @TYPE@ {
+            decorator: Decorator::new(settings),
+            message: settings.get_str("message").unwrap_or_default().to_string(),
+            count: settings.get_number("count").map_or(0, |n| n.max(0.0) as u64),
+        }
+    }
+}
+
+impl Input for @TYPE@ {
+    fn run(&mut self, queue: &QueueWriter, stop: &StopSignal) {
+        for _ in 0..self.count {
+            if stop.is_stopped() {
+                break;
+            }
+            let mut event = Event::with_message(&self.message);
+            self.decorator.decorate(&mut event);
+            // The pipeline is gone, e.g. its workers panicked.
+            if queue.push(event).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+fn register(factory: &mut PluginFactory) {
+    factory.register_input("@NAME@", @TYPE@::schema(), |s| Box::new(@TYPE@::new(s)));
+}
+
+declare_plugins!(register);
+
+#[cfg(test)]
+mod tests {
+    use echelon0::config::ast::Selector;
+    use echelon0::event::Value;
+    use echelon0::testing::TestPipeline;
+
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let mut pipeline = TestPipeline::new();
+        register(pipeline.plugins());
+
+        let conf = r"input { @NAME@ { message => 'hello' count => 3 } }";
+        let events = pipeline.run(conf, vec![]).unwrap();
+        assert_eq!(3, events.len());
+        assert_eq!(Some(&Value::String("hello".to_string())),
+                   events[0].get(&Selector::from("message")));
+    }
+}
+"#;
+
+const FILTER: &str = r#"#[macro_use]
+extern crate echelon0;
+
+use echelon0::config::ast::Selector;
+use echelon0::event::{Event, Value};
+use echelon0::plugin::factory::PluginFactory;
+use echelon0::plugin::settings::{Schema, Setting, SettingType, SettingValue, Settings};
+use echelon0::plugin::Filter;
+
+/// Sets `field` of every event to `value`.
+#[derive(Clone, Debug)]
+pub struct @TYPE@ {
+    field: Selector,
+    value: String,
+}
+
+impl @TYPE@ {
+    /// Settings of the plugin in addition to the ones common for all the filters.
+    pub fn schema() -> Schema {
+        Schema::filter()
+            .setting(Setting::new("field", SettingType::String)
+                .default(SettingValue::String("@NAME@".to_string())))
+            .setting(Setting::new("value", SettingType::String).required())
+    }
+
+    pub fn new(settings: &Settings) -> @TYPE@ {
+        @TYPE@ {
+            field: Selector::from(settings.get_str("field").unwrap_or_default()),
+            value: settings.get_str("value").unwrap_or_default().to_string(),
+        }
+    }
+}
+
+impl Filter for @TYPE@ {
+    fn filter(&mut self, events: &mut Vec<Event>) {
+        for event in events {
+            let _ = event.set(&self.field, Value::String(self.value.clone()));
+        }
+    }
+}
+
+fn register(factory: &mut PluginFactory) {
+    factory.register_filter("@NAME@", @TYPE@::schema(), |s| Box::new(@TYPE@::new(s)));
+}
+
+declare_plugins!(register);
+
+#[cfg(test)]
+mod tests {
+    use echelon0::testing::TestPipeline;
+
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let mut pipeline = TestPipeline::new();
+        register(pipeline.plugins());
+
+        let conf = r"filter { @NAME@ { value => 'bar' } }";
+        let events = pipeline.run(conf, vec![Event::with_message("hello")]).unwrap();
+        assert_eq!(1, events.len());
+        assert_eq!(Some(&Value::String("bar".to_string())),
+                   events[0].get(&Selector::from("@NAME@")));
+    }
+}
+"#;
+
+const OUTPUT: &str = r#"#[macro_use]
+extern crate echelon0;
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use echelon0::event::Event;
+use echelon0::plugin::factory::PluginFactory;
+use echelon0::plugin::settings::{Schema, Setting, SettingType, Settings};
+use echelon0::plugin::Output;
+
+/// Appends events to the file at `path` as JSON lines.
+pub struct @TYPE@ {
+    path: PathBuf,
+    file: Option<BufWriter<File>>,
+}
+
+impl @TYPE@ {
+    /// Settings of the plugin in addition to the ones common for all the outputs.
+    pub fn schema() -> Schema {
+        Schema::output().setting(Setting::new("path", SettingType::Path).required())
+    }
+
+    pub fn new(settings: &Settings) -> @TYPE@ {
+        @TYPE@ {
+            path: settings.get_path("path").map(PathBuf::from).unwrap_or_default(),
+            file: None,
+        }
+    }
+}
+
+impl Output for @TYPE@ {
+    fn register(&mut self) {
+        match OpenOptions::new().create(true).append(true).open(&self.path) {
+            Ok(file) => self.file = Some(BufWriter::new(file)),
+            Err(e) => eprintln!("Cannot open `{}`: {}", self.path.display(), e),
+        }
+    }
+
+    fn receive(&mut self, events: &[&Event]) {
+        if let Some(ref mut file) = self.file {
+            for event in events {
+                let _ = writeln!(file, "{}", event.to_json());
+            }
+            let _ = file.flush();
+        }
+    }
+
+    fn close(&mut self) {
+        self.file = None;
+    }
+}
+
+fn register(factory: &mut PluginFactory) {
+    factory.register_output("@NAME@", @TYPE@::schema(), |s| Box::new(@TYPE@::new(s)));
+}
+
+declare_plugins!(register);
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use echelon0::testing::TestPipeline;
+
+    use super::*;
+
+    #[test]
+    fn test_receive() {
+        let mut pipeline = TestPipeline::new();
+        register(pipeline.plugins());
+
+        let path = env::temp_dir().join(format!("@NAME@-{}.log", process::id()));
+        let conf = format!("output {{ @NAME@ {{ path => '{}' }} }}", path.display());
+        let events = vec![Event::with_message("hello"), Event::with_message("world")];
+        pipeline.run(&conf, events).unwrap();
+
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(2, written.lines().count());
+        assert!(written.contains("\"hello\""));
+    }
+}
+"#;
+
+const CODEC: &str = r#"#[macro_use]
+extern crate echelon0;
+
+use echelon0::config::ast::Selector;
+use echelon0::event::{Event, Value};
+use echelon0::plugin::factory::PluginFactory;
+use echelon0::plugin::settings::{Schema, Setting, SettingType, SettingValue, Settings};
+use echelon0::plugin::Codec;
+
+/// Decodes lines of `key=value` pairs separated by `separator` into fields and encodes the
+/// string fields of events the same way, except `@timestamp` and the like.
+#[derive(Clone, Debug)]
+pub struct @TYPE@ {
+    separator: String,
+    buffer: Vec<u8>,
+}
+
+impl @TYPE@ {
+    pub fn schema() -> Schema {
+        Schema::new().setting(Setting::new("separator", SettingType::String)
+            .default(SettingValue::String(" ".to_string())))
+    }
+
+    pub fn new(settings: &Settings) -> @TYPE@ {
+        @TYPE@ {
+            separator: settings.get_str("separator").unwrap_or(" ").to_string(),
+            buffer: vec![],
+        }
+    }
+
+    fn decode_line(&self, line: &[u8]) -> Event {
+        let line = String::from_utf8_lossy(line);
+        let mut event = Event::new();
+        for pair in line.split(self.separator.as_str()) {
+            if let Some((key, value)) = pair.split_once('=') {
+                let _ = event.set(&Selector::from(key), Value::String(value.to_string()));
+            }
+        }
+        event
+    }
+}
+
+impl Codec for @TYPE@ {
+    fn decode(&mut self, data: &[u8]) -> Vec<Event> {
+        self.buffer.extend_from_slice(data);
+        let mut events = vec![];
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            events.push(self.decode_line(&line[..end]));
+        }
+        events
+    }
+
+    fn flush(&mut self) -> Vec<Event> {
+        if self.buffer.is_empty() {
+            return vec![];
+        }
+        let line = std::mem::take(&mut self.buffer);
+        vec![self.decode_line(&line)]
+    }
+
+    fn encode(&mut self, event: &Event) -> Vec<u8> {
+        let pairs: Vec<_> = event.fields()
+            .iter()
+            .filter(|(key, _)| !key.starts_with('@'))
+            .filter_map(|(key, value)| value.as_str().map(|v| format!("{}={}", key, v)))
+            .collect();
+        format!("{}\n", pairs.join(&self.separator)).into_bytes()
+    }
+}
+
+fn register(factory: &mut PluginFactory) {
+    factory.register_codec("@NAME@", @TYPE@::schema(), |s| Box::new(@TYPE@::new(s)));
+}
+
+declare_plugins!(register);
+
+#[cfg(test)]
+mod tests {
+    use echelon0::plugin::factory::PluginProvider;
+
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let mut factory = PluginFactory::new();
+        register(&mut factory);
+        let settings = factory.codec_schema("@NAME@").unwrap().validate(&[]).unwrap();
+        let mut codec = factory.create_codec("@NAME@", &settings).unwrap();
+
+        let events = codec.decode(b"a=1 b=2\nc=");
+        assert_eq!(1, events.len());
+        assert_eq!(Some(&Value::String("2".to_string())), events[0].get(&Selector::from("b")));
+        assert_eq!(1, codec.flush().len());
+
+        let mut event = Event::new();
+        event.set(&Selector::from("a"), Value::String("1".to_string())).unwrap();
+        assert_eq!(b"a=1\n".to_vec(), codec.encode(&event));
+    }
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use plugin::package::{Package, PackageError};
+    use tempdir::TempDir;
+    use super::*;
+
+    #[test]
+    fn test_skeleton() {
+        assert!(Skeleton::new("my_filter", PluginKind::Filter).is_ok());
+        for name in &["my-filter", "1filter", "f", ""] {
+            match Skeleton::new(name, PluginKind::Filter) {
+                Err(ScaffoldError::InvalidName(n)) => assert_eq!(name, &n),
+                r => panic!("unexpected result {:?}", r),
+            }
+        }
+
+        let skeleton = Skeleton::new("geo_ip", PluginKind::Filter).unwrap().core_path("/src/e0");
+        let files = skeleton.files();
+        let lib = &files.iter().find(|(p, _)| p.ends_with("lib.rs")).unwrap().1;
+        assert!(lib.contains("pub struct GeoIp {"));
+        assert!(lib.contains("register_filter(\"geo_ip\", GeoIp::schema()"));
+        assert!(files[0].1.contains("echelon0 = { path = \"/src/e0\" }"));
+
+        let manifest = Manifest::parse(&files[1].1).unwrap();
+        assert_eq!(PluginKind::Filter, manifest.kind);
+        assert!(manifest.is_compatible());
+    }
+
+    #[test]
+    fn test_write() {
+        let temp = TempDir::new("scaffold");
+        let dir = temp.join("greet");
+        let skeleton = Skeleton::new("greet", PluginKind::Output).unwrap();
+        skeleton.write(&dir).unwrap();
+        assert!(dir.join("src").join("lib.rs").is_file());
+
+        match skeleton.write(&dir) {
+            Err(ScaffoldError::NotEmpty(path)) => assert_eq!(dir, path),
+            r => panic!("unexpected result {:?}", r),
+        }
+        // The crate is a package as soon as the library is built.
+        match Package::open(&dir) {
+            Err(PackageError::MissingLibrary(_, library)) => {
+                assert_eq!(format!("target/release/libgreet.{}", DLL_EXTENSION), library)
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+}
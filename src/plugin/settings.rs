@@ -0,0 +1,553 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use config::ast::{Attribute, Value};
+use event::{Template, TemplateError};
+
+/// Type of a plugin setting.
+///
+/// Mimics Logstash's `:validate => ...` config option.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SettingType {
+    String,
+    Number,
+    Bool,
+    Array,
+    Hash,
+    Path,
+    Password,
+    Codec,
+    /// String with field references rendered per event, e.g. `%{[host]}.log`.
+    Template,
+}
+
+impl fmt::Display for SettingType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::SettingType::*;
+        let name = match *self {
+            String => "string",
+            Number => "number",
+            Bool => "boolean",
+            Array => "array",
+            Hash => "hash",
+            Path => "path",
+            Password => "password",
+            Codec => "codec",
+            Template => "template",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Secret string which never shows up in logs or debug output.
+#[derive(Clone, PartialEq)]
+pub struct Password(String);
+
+impl Password {
+    pub fn value(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Password {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("<password>")
+    }
+}
+
+/// Validated value of a plugin setting.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingValue {
+    String(String),
+    Number(f64),
+    Bool(bool),
+    Array(Vec<SettingValue>),
+    Hash(Vec<(String, SettingValue)>),
+    Path(PathBuf),
+    Password(Password),
+    Codec(String, Vec<(String, SettingValue)>),
+    Template(Template),
+}
+
+impl SettingValue {
+    /// Converts an attribute value without any type checks.
+    ///
+    /// Barewords become strings and plugins become codecs, the rest is kept as is.
+    fn untyped(value: &Value) -> SettingValue {
+        match *value {
+            Value::Bareword(ref s) | Value::String(ref s) => SettingValue::String(s.clone()),
+            Value::Number(n) => SettingValue::Number(n),
+            Value::Array(ref vs) => {
+                SettingValue::Array(vs.iter().map(SettingValue::untyped).collect())
+            }
+            Value::Hash(ref entries) => {
+                SettingValue::Hash(entries.iter()
+                    .map(|e| (e.key.clone(), SettingValue::untyped(&e.value)))
+                    .collect())
+            }
+            Value::Plugin(ref p) => {
+                SettingValue::Codec(p.name.clone(), untyped_attributes(&p.attributes))
+            }
+        }
+    }
+
+    /// Converts an attribute value to the specified type.
+    ///
+    /// Follows Logstash coercion rules, e.g. `"5044"` is a valid number and a single
+    /// value is a valid array.
+    fn typed(value: &Value, kind: SettingType) -> Option<SettingValue> {
+        match (kind, value) {
+            (SettingType::String, Value::Bareword(s)) |
+            (SettingType::String, Value::String(s)) => {
+                Some(SettingValue::String(s.clone()))
+            }
+            (SettingType::Number, Value::Number(n)) => Some(SettingValue::Number(*n)),
+            (SettingType::Number, Value::String(s)) => {
+                s.trim().parse().ok().map(SettingValue::Number)
+            }
+            (SettingType::Bool, Value::Bareword(s)) |
+            (SettingType::Bool, Value::String(s)) => {
+                match s.as_str() {
+                    "true" => Some(SettingValue::Bool(true)),
+                    "false" => Some(SettingValue::Bool(false)),
+                    _ => None,
+                }
+            }
+            (SettingType::Array, Value::Array(_)) => Some(SettingValue::untyped(value)),
+            (SettingType::Array, Value::Plugin(_)) => None,
+            (SettingType::Array, v) => Some(SettingValue::Array(vec![SettingValue::untyped(v)])),
+            (SettingType::Hash, Value::Hash(_)) => Some(SettingValue::untyped(value)),
+            (SettingType::Path, Value::String(s)) => {
+                Some(SettingValue::Path(PathBuf::from(s)))
+            }
+            (SettingType::Password, Value::String(s)) => {
+                Some(SettingValue::Password(Password(s.clone())))
+            }
+            (SettingType::Codec, Value::Bareword(s)) |
+            (SettingType::Codec, Value::String(s)) => {
+                Some(SettingValue::Codec(s.clone(), vec![]))
+            }
+            (SettingType::Codec, Value::Plugin(_)) => Some(SettingValue::untyped(value)),
+            _ => None,
+        }
+    }
+}
+
+fn untyped_attributes(attributes: &[Attribute]) -> Vec<(String, SettingValue)> {
+    attributes.iter().map(|a| (a.name.clone(), SettingValue::untyped(&a.value))).collect()
+}
+
+/// Description of a single plugin setting.
+#[derive(Clone, Debug)]
+pub struct Setting {
+    name: &'static str,
+    kind: SettingType,
+    required: bool,
+    default: Option<SettingValue>,
+    allowed: Vec<&'static str>,
+    deprecated: Option<&'static str>,
+    obsolete: Option<&'static str>,
+}
+
+impl Setting {
+    pub fn new(name: &'static str, kind: SettingType) -> Setting {
+        Setting {
+            name,
+            kind,
+            required: false,
+            default: None,
+            allowed: vec![],
+            deprecated: None,
+            obsolete: None,
+        }
+    }
+
+    pub fn required(mut self) -> Setting {
+        self.required = true;
+        self
+    }
+
+    pub fn default(mut self, value: SettingValue) -> Setting {
+        self.default = Some(value);
+        self
+    }
+
+    /// Restricts values of a string setting to the given list.
+    pub fn allowed(mut self, values: &[&'static str]) -> Setting {
+        self.allowed = values.to_vec();
+        self
+    }
+
+    /// Marks the setting as deprecated. Such settings still work but produce a warning.
+    pub fn deprecated(mut self, message: &'static str) -> Setting {
+        self.deprecated = Some(message);
+        self
+    }
+
+    /// Marks the setting as obsolete. Usage of such settings is an error.
+    pub fn obsolete(mut self, message: &'static str) -> Setting {
+        self.obsolete = Some(message);
+        self
+    }
+}
+
+/// Set of settings supported by a plugin.
+#[derive(Clone, Debug, Default)]
+pub struct Schema {
+    settings: Vec<Setting>,
+}
+
+impl Schema {
+    pub fn new() -> Schema {
+        Schema { settings: vec![] }
+    }
+
+    /// Settings common for all the input plugins.
+    pub fn input() -> Schema {
+        Schema::new()
+            .setting(Setting::new("id", SettingType::String))
+            .setting(Setting::new("type", SettingType::String))
+            .setting(Setting::new("tags", SettingType::Array))
+            .setting(Setting::new("add_field", SettingType::Hash)
+                .default(SettingValue::Hash(vec![])))
+            .setting(Setting::new("codec", SettingType::Codec).default(plain_codec()))
+            .setting(Setting::new("enable_metric", SettingType::Bool)
+                .default(SettingValue::Bool(true)))
+    }
+
+    /// Settings common for all the filter plugins.
+    pub fn filter() -> Schema {
+        Schema::new()
+            .setting(Setting::new("id", SettingType::String))
+            .setting(Setting::new("add_field", SettingType::Hash)
+                .default(SettingValue::Hash(vec![])))
+            .setting(Setting::new("add_tag", SettingType::Array)
+                .default(SettingValue::Array(vec![])))
+            .setting(Setting::new("remove_field", SettingType::Array)
+                .default(SettingValue::Array(vec![])))
+            .setting(Setting::new("remove_tag", SettingType::Array)
+                .default(SettingValue::Array(vec![])))
+            .setting(Setting::new("periodic_flush", SettingType::Bool)
+                .default(SettingValue::Bool(false)))
+            .setting(Setting::new("enable_metric", SettingType::Bool)
+                .default(SettingValue::Bool(true)))
+    }
+
+    /// Settings common for all the output plugins.
+    pub fn output() -> Schema {
+        Schema::new()
+            .setting(Setting::new("id", SettingType::String))
+            .setting(Setting::new("codec", SettingType::Codec).default(plain_codec()))
+            .setting(Setting::new("enable_metric", SettingType::Bool)
+                .default(SettingValue::Bool(true)))
+            .setting(Setting::new("workers", SettingType::Number)
+                .obsolete("use pipeline workers instead"))
+    }
+
+    /// Adds a setting. Settings with the same name are overridden.
+    pub fn setting(mut self, setting: Setting) -> Schema {
+        self.settings.retain(|s| s.name != setting.name);
+        self.settings.push(setting);
+        self
+    }
+
+    /// Checks attributes against the schema and converts them to typed settings.
+    ///
+    /// All the problems found are reported at once.
+    pub fn validate(&self, attributes: &[Attribute]) -> Result<Settings, Vec<SettingError>> {
+        let mut errors = vec![];
+        let mut values = HashMap::new();
+        let mut seen = HashSet::new();
+
+        for attr in attributes {
+            if !seen.insert(attr.name.as_str()) {
+                errors.push(SettingError::Duplicate(attr.name.clone()));
+                continue;
+            }
+
+            let setting = match self.settings.iter().find(|s| s.name == attr.name) {
+                Some(s) => s,
+                None => {
+                    errors.push(SettingError::Unknown(attr.name.clone()));
+                    continue;
+                }
+            };
+
+            if let Some(message) = setting.obsolete {
+                errors.push(SettingError::Obsolete(attr.name.clone(), message));
+                continue;
+            }
+
+            if let Some(message) = setting.deprecated {
+                warn!("Setting `{}` is deprecated: {}", setting.name, message);
+            }
+
+            let typed = match (setting.kind, &attr.value) {
+                // Templates are parsed here, so broken ones are reported before the start.
+                (SettingType::Template, Value::String(s)) |
+                (SettingType::Template, Value::Bareword(s)) => {
+                    match Template::parse(s) {
+                        Ok(t) => Some(SettingValue::Template(t)),
+                        Err(e) => {
+                            errors.push(SettingError::InvalidTemplate(attr.name.clone(), e));
+                            continue;
+                        }
+                    }
+                }
+                _ => SettingValue::typed(&attr.value, setting.kind),
+            };
+            match typed {
+                Some(SettingValue::String(ref s)) if !setting.allowed.is_empty() &&
+                                                     !setting.allowed.contains(&s.as_str()) => {
+                    errors.push(SettingError::NotAllowed(attr.name.clone(),
+                                                         s.clone(),
+                                                         setting.allowed.clone()));
+                }
+                Some(v) => {
+                    values.insert(attr.name.clone(), v);
+                }
+                None => errors.push(SettingError::InvalidType(attr.name.clone(), setting.kind)),
+            }
+        }
+
+        for setting in &self.settings {
+            if values.contains_key(setting.name) || seen.contains(setting.name) {
+                continue;
+            }
+            if setting.required {
+                errors.push(SettingError::Missing(setting.name.to_string()));
+            } else if let Some(ref default) = setting.default {
+                values.insert(setting.name.to_string(), default.clone());
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(Settings { values })
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn plain_codec() -> SettingValue {
+    SettingValue::Codec("plain".to_string(), vec![])
+}
+
+/// Validated plugin settings.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Settings {
+    values: HashMap<String, SettingValue>,
+}
+
+impl Settings {
+    pub fn new() -> Settings {
+        Settings { values: HashMap::new() }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SettingValue> {
+        self.values.get(name)
+    }
+
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        match self.get(name) {
+            Some(SettingValue::String(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn get_number(&self, name: &str) -> Option<f64> {
+        match self.get(name) {
+            Some(SettingValue::Number(n)) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        match self.get(name) {
+            Some(SettingValue::Bool(b)) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn get_path(&self, name: &str) -> Option<&Path> {
+        match self.get(name) {
+            Some(SettingValue::Path(p)) => Some(p),
+            _ => None,
+        }
+    }
+
+    pub fn get_template(&self, name: &str) -> Option<&Template> {
+        match self.get(name) {
+            Some(SettingValue::Template(t)) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn get_array(&self, name: &str) -> Option<&[SettingValue]> {
+        match self.get(name) {
+            Some(SettingValue::Array(a)) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn get_hash(&self, name: &str) -> Option<&[(String, SettingValue)]> {
+        match self.get(name) {
+            Some(SettingValue::Hash(h)) => Some(h),
+            _ => None,
+        }
+    }
+
+    /// Returns string elements of an array setting, other elements are skipped.
+    pub fn get_strings(&self, name: &str) -> Vec<&str> {
+        self.get_array(name).map_or(vec![], |a| {
+            a.iter()
+                .filter_map(|v| match v {
+                    SettingValue::String(s) => Some(s.as_str()),
+                    _ => None,
+                })
+                .collect()
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingError {
+    Unknown(String),
+    Duplicate(String),
+    Missing(String),
+    Obsolete(String, &'static str),
+    InvalidType(String, SettingType),
+    NotAllowed(String, String, Vec<&'static str>),
+    InvalidTemplate(String, TemplateError),
+}
+
+impl fmt::Display for SettingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::SettingError::*;
+        match *self {
+            Unknown(ref name) => write!(f, "unknown setting `{}`", name),
+            Duplicate(ref name) => write!(f, "setting `{}` is specified more than once", name),
+            Missing(ref name) => write!(f, "required setting `{}` is missing", name),
+            Obsolete(ref name, message) => write!(f, "setting `{}` is obsolete, {}", name, message),
+            InvalidType(ref name, kind) => write!(f, "setting `{}` must be of type {}", name, kind),
+            NotAllowed(ref name, ref value, ref allowed) => {
+                write!(f,
+                       "setting `{}` has invalid value `{}`, expected one of: {}",
+                       name,
+                       value,
+                       allowed.join(", "))
+            }
+            InvalidTemplate(ref name, ref err) => {
+                write!(f, "setting `{}` is an invalid template: {}", name, err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::ast::*;
+    use super::*;
+
+    fn attr(name: &str, value: Value) -> Attribute {
+        Attribute::new(name.to_string(), value)
+    }
+
+    fn file_schema() -> Schema {
+        Schema::input()
+            .setting(Setting::new("path", SettingType::Array).required())
+            .setting(Setting::new("start_position", SettingType::String)
+                .allowed(&["beginning", "end"])
+                .default(SettingValue::String("end".to_string())))
+            .setting(Setting::new("stat_interval", SettingType::Number))
+            .setting(Setting::new("sincedb_path", SettingType::Path))
+            .setting(Setting::new("password", SettingType::Password))
+            .setting(Setting::new("close_older", SettingType::Number)
+                .deprecated("use `ignore_older`"))
+            .setting(Setting::new("discover_interval", SettingType::Number).obsolete("it's gone"))
+    }
+
+    #[test]
+    fn test_validate_templates() {
+        let schema = Schema::output()
+            .setting(Setting::new("path", SettingType::Template).required())
+            .setting(Setting::new("index", SettingType::Template));
+
+        let attributes = vec![attr("path", Value::from("/data/%{[host]}/%{+YYYY-MM-dd}.log")),
+                              attr("index", Value::Bareword("logs".to_string()))];
+        let settings = schema.validate(&attributes).unwrap();
+        let path = settings.get_template("path").unwrap();
+        assert_eq!("/data/%{[host]}/%{+YYYY-MM-dd}.log", path.to_string());
+        assert!(!path.is_constant());
+        assert!(settings.get_template("index").unwrap().is_constant());
+
+        let attributes = vec![attr("path", Value::from("/data/%{host")),
+                              attr("index", Value::from(1.0))];
+        let errors = schema.validate(&attributes).unwrap_err();
+        assert_eq!(vec!["setting `path` is an invalid template: unterminated `%{` at 6",
+                        "setting `index` must be of type template"],
+                   errors.iter().map(|e| e.to_string()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_validate_ok() {
+        let attributes = vec![
+            attr("path", Value::from("/var/log/*.log")),
+            attr("stat_interval", Value::from("1.5")),
+            attr("sincedb_path", Value::from("/dev/null")),
+            attr("password", Value::from("s3cr3t")),
+            attr("close_older", Value::from(3600.0)),
+            attr("enable_metric", Value::Bareword("false".to_string())),
+            attr("codec", Value::from(Plugin::new("json".to_string(),
+                                                  vec![attr("charset", Value::from("UTF-8"))]))),
+        ];
+
+        let settings = file_schema().validate(&attributes).unwrap();
+        assert_eq!(Some(&SettingValue::Array(vec![SettingValue::String("/var/log/*.log"
+                       .to_string())])),
+                   settings.get("path"));
+        assert_eq!(Some("end"), settings.get_str("start_position"));
+        assert_eq!(Some(1.5), settings.get_number("stat_interval"));
+        assert_eq!(Some(&SettingValue::Path(PathBuf::from("/dev/null"))),
+                   settings.get("sincedb_path"));
+        assert_eq!(Some(false), settings.get_bool("enable_metric"));
+        assert_eq!(Some(&SettingValue::Codec("json".to_string(),
+                                             vec![("charset".to_string(),
+                                                   SettingValue::String("UTF-8".to_string()))])),
+                   settings.get("codec"));
+        assert_eq!(Some(&SettingValue::Hash(vec![])), settings.get("add_field"));
+        assert_eq!(None, settings.get("type"));
+        assert_eq!("Some(Password(<password>))", format!("{:?}", settings.get("password")));
+    }
+
+    #[test]
+    fn test_validate_errors() {
+        let attributes = vec![
+            attr("pathh", Value::from("/var/log/*.log")),
+            attr("start_position", Value::Bareword("middle".to_string())),
+            attr("stat_interval", Value::from("often")),
+            attr("discover_interval", Value::from(15.0)),
+            attr("tags", Value::from("foo")),
+            attr("tags", Value::from("bar")),
+        ];
+
+        let errors = file_schema().validate(&attributes).unwrap_err();
+        assert_eq!(vec![
+            SettingError::Unknown("pathh".to_string()),
+            SettingError::NotAllowed("start_position".to_string(),
+                                     "middle".to_string(),
+                                     vec!["beginning", "end"]),
+            SettingError::InvalidType("stat_interval".to_string(), SettingType::Number),
+            SettingError::Obsolete("discover_interval".to_string(), "it's gone"),
+            SettingError::Duplicate("tags".to_string()),
+            SettingError::Missing("path".to_string()),
+        ], errors);
+
+        assert_eq!("unknown setting `pathh`", errors[0].to_string());
+        assert_eq!("setting `start_position` has invalid value `middle`, expected one of: \
+                    beginning, end",
+                   errors[1].to_string());
+    }
+}
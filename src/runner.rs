@@ -1,24 +1,228 @@
-use config::compile::compile;
-use config::parse::parse;
-use pipeline::Pipeline;
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use config::ast::Config;
+use config::compile::{compile, CompileError};
+use config::load::{load, LoadError};
+use config::parse::{parse, ParseError};
+use config::variables::{Keystore, Variables};
+use pipeline::{Function, FunctionRegistry, Pipeline, PipelineSettings};
+use plugin::StopSignal;
 use plugin::factory::PluginFactory;
+use plugin::library::LibraryError;
+
+/// Options of a program run (usually taken from the command line).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Options {
+    /// Path to the config file, directory or glob pattern (`-f`).
+    pub config_path: Option<String>,
+    /// Inline config (`-e`).
+    pub config_string: Option<String>,
+    /// Check the config and exit without starting the pipeline (`-t`).
+    pub test_and_exit: bool,
+    /// Path to the keystore with values of `${VAR}` references (`--path.keystore`).
+    pub keystore_path: Option<String>,
+    /// Directory with plugin libraries to load in addition to the built-in plugins
+    /// (`--path.plugins`).
+    pub plugins_path: Option<String>,
+    pub pipeline: PipelineSettings,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    NoConfig,
+    AmbiguousConfig,
+    Load(LoadError),
+    Keystore(String, io::Error),
+    Plugins(LibraryError),
+    Parse(ParseError),
+    Compile(Vec<CompileError>),
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Error {
+        Error::Parse(err)
+    }
+}
+
+impl From<LoadError> for Error {
+    fn from(err: LoadError) -> Error {
+        Error::Load(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoConfig => write!(f, "No config given, use either -f or -e"),
+            Error::AmbiguousConfig => write!(f, "Options -f and -e cannot be used together"),
+            Error::Load(err) => write!(f, "{}", err),
+            Error::Keystore(path, err) => write!(f, "Cannot read keystore `{}`: {}", path, err),
+            Error::Plugins(err) => write!(f, "{}", err),
+            Error::Parse(err) => write!(f, "{}", err),
+            Error::Compile(errors) => {
+                for (i, e) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "error: {}", e)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl error::Error for Error {}
 
-/// Handles program runs (i.e. parses command line params and dispatches executors).
+/// Handles program runs (i.e. loads the config and dispatches executors).
 pub struct Runner {
-    pipeline: Pipeline,
+    options: Options,
+    functions: FunctionRegistry,
+    plugins: PluginFactory,
+    stop: StopSignal,
 }
 
 impl Runner {
-    pub fn new() -> Runner {
-        let config = parse(include_bytes!("./config/tests/assets/simplest.conf")).unwrap();
-        let plugin_factory = PluginFactory::new();
-        let session = compile(&config, &plugin_factory);
-        let pipeline = Pipeline::new(session.inputs, session.filters, session.outputs);
-        Runner { pipeline: pipeline }
+    pub fn new(options: Options) -> Runner {
+        Runner {
+            options,
+            functions: FunctionRegistry::builtin(),
+            plugins: PluginFactory::builtin(),
+            stop: StopSignal::new(),
+        }
+    }
+
+    /// Makes a function callable in conditions in addition to the built-in ones.
+    pub fn register_function(&mut self, function: Function) {
+        self.functions.register(function);
+    }
+
+    /// Gives access to the plugin registry to add plugins besides the built-in ones.
+    pub fn plugins(&mut self) -> &mut PluginFactory {
+        &mut self.plugins
+    }
+
+    /// Returns a handle to stop the pipeline from another thread, e.g. on a signal.
+    pub fn stop_signal(&self) -> StopSignal {
+        self.stop.clone()
+    }
+
+    pub fn run(&mut self) -> Result<(), Error> {
+        let config = self.load_config()?;
+        let keystore = match self.options.keystore_path {
+            Some(ref path) => Keystore::load(path).map_err(|e| Error::Keystore(path.clone(), e))?,
+            None => Keystore::new(),
+        };
+        if let Some(ref path) = self.options.plugins_path {
+            self.plugins.load_dir(Path::new(path)).map_err(Error::Plugins)?;
+        }
+        let variables = Variables::from_env(keystore);
+        let session = compile(&config, &self.plugins, &self.functions, &variables);
+        if !session.errors.is_empty() {
+            err!(Error::Compile(session.errors));
+        }
+
+        if self.options.test_and_exit {
+            println!("Configuration OK");
+            return Ok(());
+        }
+
+        info!("Starting pipeline with {} workers", self.options.pipeline.workers);
+        let mut pipeline = Pipeline::new(self.options.pipeline.clone(),
+                                         session.inputs,
+                                         session.filters,
+                                         session.outputs);
+        pipeline.run(&self.stop);
+        Ok(())
+    }
+
+    fn load_config(&self) -> Result<Config, Error> {
+        match (&self.options.config_path, &self.options.config_string) {
+            (Some(path), None) => Ok(load(path)?),
+            (None, Some(conf)) => Ok(parse(conf.as_bytes())?),
+            (None, None) => Err(Error::NoConfig),
+            (Some(_), Some(_)) => Err(Error::AmbiguousConfig),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use event::Value;
+    use pipeline::ArgType;
+    use super::*;
+
+    fn options(conf: &str) -> Options {
+        Options {
+            config_string: Some(conf.to_string()),
+            test_and_exit: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_run_test_and_exit() {
+        assert!(Runner::new(options("input { stdin {} } output { stdout {} }")).run().is_ok());
+
+        match Runner::new(options("input { stdin {} } output { kafka {} }")).run() {
+            Err(Error::Compile(errors)) => {
+                assert_eq!("config:1:29: Unknown output plugin `kafka`", errors[0].to_string())
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        match Runner::new(options("input { stdin {} } output { stdot {} }")).run() {
+            Err(Error::Compile(errors)) => {
+                assert_eq!("config:1:29: Unknown output plugin `stdot`, did you mean `stdout`?",
+                           errors[0].to_string())
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        match Runner::new(options("input { stdin {")).run() {
+            Err(Error::Parse(err)) => assert_eq!(1, err.line),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_register_function() {
+        let conf = "input { stdin {} } filter { if twice([a]) { } } output { stdout {} }";
+        assert!(Runner::new(options(conf)).run().is_err());
+
+        let mut runner = Runner::new(options(conf));
+        runner.register_function(Function::new("twice", vec![ArgType::Any], |args| {
+            args[0].and_then(|v| v.as_f64()).map(|n| Value::Float(n * 2.0))
+        }));
+        assert!(runner.run().is_ok());
     }
 
-    pub fn run(&mut self) {
-        println!("Hello from Echelon0 runner!");
-        self.pipeline.run();
+    #[test]
+    fn test_load_config_errors() {
+        let mut opts = Options::default();
+        match Runner::new(opts.clone()).run() {
+            Err(Error::NoConfig) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        opts.config_path = Some("/no/such/file.conf".to_string());
+        match Runner::new(opts.clone()).run() {
+            Err(Error::Load(LoadError::Io(path, _))) => assert_eq!("/no/such/file.conf", path),
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        opts.config_string = Some("input {}".to_string());
+        match Runner::new(opts.clone()).run() {
+            Err(Error::AmbiguousConfig) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        opts.config_path = None;
+        opts.keystore_path = Some("/no/such/keystore".to_string());
+        match Runner::new(opts).run() {
+            Err(Error::Keystore(path, _)) => assert_eq!("/no/such/keystore", path),
+            r => panic!("unexpected result {:?}", r),
+        }
     }
 }
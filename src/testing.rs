@@ -0,0 +1,140 @@
+//! Harness to test plugins by running events through a real pipeline.
+//!
+//! ```ignore
+//! let mut pipeline = TestPipeline::new();
+//! pipeline.plugins().register_filter("foo", Schema::filter(), |s| Box::new(Foo::new(s)));
+//!
+//! let events = pipeline.run("filter { foo {} }", vec![Event::with_message("hello")]).unwrap();
+//! assert_eq!(1, events.len());
+//! ```
+
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use config::compile::compile;
+use config::parse::parse;
+use config::variables::{Keystore, Variables};
+use event::Event;
+use pipeline::{FunctionRegistry, Pipeline, PipelineSettings, QueueWriter};
+use plugin::{Input, InputPlugin, Output, OutputPlugin, StopSignal};
+use plugin::factory::PluginFactory;
+use plugin::settings::Settings;
+use runner::Error;
+
+/// Runs a config through the pipeline feeding it the given events and collecting the events
+/// which reach the output section.
+///
+/// There's a single worker, so events come out in the order they went in unless plugins
+/// reorder them. `${VAR}` references are left to the environment.
+pub struct TestPipeline {
+    plugins: PluginFactory,
+    functions: FunctionRegistry,
+}
+
+impl Default for TestPipeline {
+    fn default() -> TestPipeline {
+        TestPipeline::new()
+    }
+}
+
+impl TestPipeline {
+    /// Creates a harness knowing the built-in plugins and functions.
+    pub fn new() -> TestPipeline {
+        TestPipeline {
+            plugins: PluginFactory::builtin(),
+            functions: FunctionRegistry::builtin(),
+        }
+    }
+
+    /// Gives access to the plugin registry to add the plugins under test.
+    pub fn plugins(&mut self) -> &mut PluginFactory {
+        &mut self.plugins
+    }
+
+    /// Runs the pipeline until its inputs are done.
+    ///
+    /// The events given are produced by an extra input, in addition to the inputs of the
+    /// config, if any. Outputs of the config get the events along with the harness.
+    pub fn run(&self, config: &str, events: Vec<Event>) -> Result<Vec<Event>, Error> {
+        let config = parse(config.as_bytes())?;
+        let variables = Variables::from_env(Keystore::new());
+        let mut session = compile(&config, &self.plugins, &self.functions, &variables);
+        if !session.errors.is_empty() {
+            return Err(Error::Compile(session.errors));
+        }
+
+        let collected = Arc::new(Mutex::new(vec![]));
+        session.inputs.add_plugin(InputPlugin::new(Settings::new(), Box::new(Feed { events })));
+        session.outputs.add_plugin(OutputPlugin::new(Settings::new(),
+                                                     Box::new(Collect {
+                                                         events: collected.clone(),
+                                                     })));
+
+        let settings = PipelineSettings {
+            workers: 1,
+            batch_delay: Duration::from_millis(0),
+            ..Default::default()
+        };
+        Pipeline::new(settings, session.inputs, session.filters, session.outputs)
+            .run(&StopSignal::new());
+
+        let events = mem::take(&mut *collected.lock().unwrap());
+        Ok(events)
+    }
+}
+
+/// Produces the events given to the harness.
+#[derive(Clone)]
+struct Feed {
+    events: Vec<Event>,
+}
+
+impl Input for Feed {
+    fn run(&mut self, queue: &QueueWriter, stop: &StopSignal) {
+        for event in self.events.drain(..) {
+            if stop.is_stopped() || queue.push(event).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Keeps copies of the events reaching the outputs.
+struct Collect {
+    events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl Output for Collect {
+    fn receive(&mut self, events: &[&Event]) {
+        self.events.lock().unwrap().extend(events.iter().map(|&e| e.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use event::Value;
+    use config::ast::Selector;
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let pipeline = TestPipeline::new();
+        let events: Vec<_> = (0..300).map(|n| Event::with_message(&n.to_string())).collect();
+        let conf = "filter { if [message] == '7' { drop {} } else { mutate { rename => { \
+                    'message' => 'n' } } } }";
+        let events = pipeline.run(conf, events).unwrap();
+
+        assert_eq!(299, events.len());
+        assert_eq!(Some(&Value::String("6".to_string())), events[6].get(&Selector::from("n")));
+        assert_eq!(Some(&Value::String("8".to_string())), events[7].get(&Selector::from("n")));
+
+        let conf = "input { generator { count => 2 } } output { null {} }";
+        assert_eq!(3, pipeline.run(conf, vec![Event::new()]).unwrap().len());
+
+        match pipeline.run("filter { foo {} }", vec![]) {
+            Err(Error::Compile(errors)) => assert_eq!(1, errors.len()),
+            r => panic!("unexpected result {:?}", r.map(|_| ())),
+        }
+    }
+}